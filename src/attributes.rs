@@ -0,0 +1,345 @@
+use crate::error::FrontmatterError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// The document-level attributes of a path, keyed by name. For Markdown, these are all the keys
+/// in the frontmatter, and, for Org, these are all the `#+KEY: value` lines, with the keys
+/// lowercased.
+pub type Attributes = HashMap<String, AttributeValue>;
+
+/// A single typed value in a document's attributes. Org attributes will always be strings (or
+/// lists of strings, if a key is given more than once).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<AttributeValue>),
+    Map(HashMap<String, AttributeValue>),
+    Null,
+}
+impl From<toml::Value> for AttributeValue {
+    fn from(value: toml::Value) -> Self {
+        match value {
+            toml::Value::Boolean(b) => Self::Bool(b),
+            toml::Value::Integer(i) => Self::Int(i),
+            toml::Value::Float(f) => Self::Float(f),
+            toml::Value::String(s) => Self::String(s),
+            // These would otherwise come through as a strange private map
+            toml::Value::Datetime(dt) => Self::String(dt.to_string()),
+            toml::Value::Array(arr) => Self::List(arr.into_iter().map(Self::from).collect()),
+            toml::Value::Table(table) => {
+                Self::Map(table.into_iter().map(|(k, v)| (k, Self::from(v))).collect())
+            }
+        }
+    }
+}
+
+impl From<serde_json::Value> for AttributeValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Int(i),
+                // Anything else will fit in a float, possibly with some loss of precision
+                None => Self::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(arr) => Self::List(arr.into_iter().map(Self::from).collect()),
+            serde_json::Value::Object(map) => {
+                Self::Map(map.into_iter().map(|(k, v)| (k, Self::from(v))).collect())
+            }
+        }
+    }
+}
+impl TryFrom<serde_yaml::Value> for AttributeValue {
+    type Error = ();
+
+    /// Converts the given YAML value, failing if it contains a map with a key that isn't a
+    /// scalar (which we have no way of representing).
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        Ok(match value {
+            serde_yaml::Value::Null => Self::Null,
+            serde_yaml::Value::Bool(b) => Self::Bool(b),
+            serde_yaml::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Int(i),
+                None => Self::Float(n.as_f64().ok_or(())?),
+            },
+            serde_yaml::Value::String(s) => Self::String(s),
+            serde_yaml::Value::Sequence(seq) => Self::List(
+                seq.into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            serde_yaml::Value::Mapping(map) => Self::Map(
+                map.into_iter()
+                    .map(|(k, v)| Ok((yaml_key(k).ok_or(())?, Self::try_from(v)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            // Custom tags mean nothing to us, so we just take what they're attached to
+            serde_yaml::Value::Tagged(tagged) => Self::try_from(tagged.value)?,
+        })
+    }
+}
+
+/// Converts the given YAML map key to a string, if it's a scalar.
+fn yaml_key(key: serde_yaml::Value) -> Option<String> {
+    match key {
+        serde_yaml::Value::String(s) => Some(s),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// The styles of frontmatter a Markdown document can have.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrontmatterStyle {
+    /// YAML frontmatter, delimited by `---`.
+    Yaml,
+    /// TOML frontmatter, delimited by `+++` (as used by Hugo and Zola).
+    Toml,
+    /// JSON frontmatter, either delimited by `;;;` or given as a bare object whose opening brace
+    /// is on a line of its own.
+    Json,
+}
+impl FrontmatterStyle {
+    /// Detects the style of the given raw frontmatter, as it appears at the start of a document.
+    pub fn detect(attributes: &str) -> Option<Self> {
+        if attributes.starts_with("---") {
+            Some(Self::Yaml)
+        } else if attributes.starts_with("+++") {
+            Some(Self::Toml)
+        } else if attributes.starts_with(";;;")
+            || attributes
+                .lines()
+                .next()
+                .is_some_and(|line| line.trim_end() == "{")
+        {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+}
+
+/// The keys in frontmatter that are deserialized into [`MarkdownFrontmatter`]'s own fields.
+static EXTRACTED_KEYS: [&str; 2] = ["title", "tags"];
+
+/// The properties Starling extracts from the frontmatter of a Markdown document, in any style.
+#[derive(Deserialize)]
+pub struct MarkdownFrontmatter {
+    pub title: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Every key in the frontmatter (including the title and tags).
+    #[serde(skip)]
+    pub attributes: Attributes,
+}
+impl MarkdownFrontmatter {
+    /// Parses the given raw frontmatter (including its delimiters), returning [`None`] if it
+    /// isn't in a style we recognise.
+    pub fn parse(attributes: &str) -> Option<Result<Self, FrontmatterError>> {
+        let style = FrontmatterStyle::detect(attributes)?;
+        let inner = match style {
+            FrontmatterStyle::Yaml => strip_delimiters(attributes, "---")?.to_string(),
+            FrontmatterStyle::Toml => strip_delimiters(attributes, "+++")?.to_string(),
+            FrontmatterStyle::Json => {
+                let inner = strip_delimiters(attributes, ";;;")
+                    .unwrap_or(attributes)
+                    .trim();
+                // Some tools leave out the braces between `;;;` delimiters
+                if inner.starts_with('{') {
+                    inner.to_string()
+                } else {
+                    format!("{{{inner}}}")
+                }
+            }
+        };
+
+        Some(Self::parse_inner(&inner, style))
+    }
+    /// Parses the given frontmatter, with its delimiters stripped, in the given style.
+    fn parse_inner(inner: &str, style: FrontmatterStyle) -> Result<Self, FrontmatterError> {
+        // Everything is parsed untyped first, and then only the keys we extract are deserialized
+        // strictly, while every other key is converted separately, so one strange value can't
+        // stop us from getting the rest
+        let frontmatter = match style {
+            FrontmatterStyle::Toml => {
+                let table: toml::Table = toml::from_str(inner).map_err(FrontmatterError::Toml)?;
+                let known = table
+                    .iter()
+                    .filter(|(k, _)| EXTRACTED_KEYS.contains(&k.as_str()))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                let mut frontmatter: Self = toml::Value::Table(known)
+                    .try_into()
+                    .map_err(FrontmatterError::Toml)?;
+                frontmatter.attributes = table
+                    .into_iter()
+                    .map(|(k, v)| (k, AttributeValue::from(v)))
+                    .collect();
+                frontmatter
+            }
+            FrontmatterStyle::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_str(inner).map_err(FrontmatterError::Json)?;
+                let serde_json::Value::Object(map) = value else {
+                    return serde_json::from_value(value).map_err(FrontmatterError::Json);
+                };
+                let known = map
+                    .iter()
+                    .filter(|(k, _)| EXTRACTED_KEYS.contains(&k.as_str()))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                let mut frontmatter: Self =
+                    serde_json::from_value(serde_json::Value::Object(known))
+                        .map_err(FrontmatterError::Json)?;
+                frontmatter.attributes = map
+                    .into_iter()
+                    .map(|(k, v)| (k, AttributeValue::from(v)))
+                    .collect();
+                frontmatter
+            }
+            FrontmatterStyle::Yaml => {
+                let value: serde_yaml::Value =
+                    serde_yaml::from_str(inner).map_err(FrontmatterError::Yaml)?;
+                let serde_yaml::Value::Mapping(map) = value else {
+                    return serde_yaml::from_value(value).map_err(FrontmatterError::Yaml);
+                };
+                let known = map
+                    .iter()
+                    .filter(|(k, _)| k.as_str().is_some_and(|k| EXTRACTED_KEYS.contains(&k)))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                let mut frontmatter: Self =
+                    serde_yaml::from_value(serde_yaml::Value::Mapping(known))
+                        .map_err(FrontmatterError::Yaml)?;
+                frontmatter.attributes = map
+                    .into_iter()
+                    .filter_map(|(k, v)| {
+                        let Some(key) = yaml_key(k) else {
+                            warn!("skipping frontmatter key that isn't a scalar");
+                            return None;
+                        };
+                        match AttributeValue::try_from(v) {
+                            Ok(value) => Some((key, value)),
+                            Err(_) => {
+                                warn!("skipping frontmatter key {key:?} with unsupported value");
+                                None
+                            }
+                        }
+                    })
+                    .collect();
+                frontmatter
+            }
+        };
+
+        Ok(frontmatter)
+    }
+}
+
+/// Parses all the `#+KEY: value` lines in the given Org attributes. Keys are lowercased, since Org
+/// treats them case-insensitively, and keys given more than once will have all their values
+/// collected into a list.
+pub fn parse_org_attributes(attributes: &str) -> Attributes {
+    let mut parsed = Attributes::new();
+    for line in attributes.lines() {
+        let Some((key, value)) = line
+            .strip_prefix("#+")
+            .and_then(|line| line.split_once(':'))
+        else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = AttributeValue::String(value.trim().to_string());
+
+        match parsed.remove(&key) {
+            Some(AttributeValue::List(mut values)) => {
+                values.push(value);
+                parsed.insert(key, AttributeValue::List(values));
+            }
+            Some(prev) => {
+                parsed.insert(key, AttributeValue::List(vec![prev, value]));
+            }
+            None => {
+                parsed.insert(key, value);
+            }
+        }
+    }
+
+    parsed
+}
+
+/// Splits any frontmatter that Orgish won't recognise itself (i.e. anything other than YAML)
+/// off the start of the given Markdown document, returning the raw frontmatter (with its
+/// delimiters) and the rest of the document. The raw frontmatter should be written back
+/// verbatim, so the original style is preserved.
+///
+/// This will return [`None`] if there's no such frontmatter, in which case the document should
+/// be parsed as-is.
+pub fn split_frontmatter(contents: &str) -> Option<(&str, &str)> {
+    let end = match FrontmatterStyle::detect(contents)? {
+        FrontmatterStyle::Yaml => return None,
+        FrontmatterStyle::Toml => delimited_end(contents, "+++")?,
+        FrontmatterStyle::Json if contents.starts_with(";;;") => delimited_end(contents, ";;;")?,
+        // A bare object ends wherever the object does, but plenty of Markdown starts with a brace
+        // (e.g. Hugo shortcodes), so it's only frontmatter if it really is an object, and it's set
+        // apart from the rest of the document by a blank line (or the root properties we put
+        // straight after frontmatter ourselves)
+        FrontmatterStyle::Json => {
+            let mut stream =
+                serde_json::Deserializer::from_str(contents).into_iter::<serde_json::Value>();
+            if !stream.next()?.ok()?.is_object() {
+                return None;
+            }
+            let end = stream.byte_offset();
+            let mut following = contents[end..].split_inclusive('\n');
+            let closed = following.next().is_none_or(|line| line.trim().is_empty());
+            let separated = following
+                .next()
+                .is_none_or(|line| line.trim().is_empty() || line.trim_end() == "<!--PROPERTIES");
+            if !(closed && separated) {
+                return None;
+            }
+            end
+        }
+    };
+
+    let (attributes, rest) = contents.split_at(end);
+    // Orgish would consume the newline after the frontmatter, so we do too
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+        .unwrap_or(rest);
+    Some((attributes, rest))
+}
+
+/// Gets the byte index of the end of the closing delimiter of frontmatter that opens with the
+/// given delimiter on its own line.
+fn delimited_end(contents: &str, delimiter: &str) -> Option<usize> {
+    let mut offset = 0;
+    for (i, line) in contents.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_end();
+        if i == 0 && trimmed != delimiter {
+            return None;
+        } else if i > 0 && trimmed == delimiter {
+            return Some(offset + delimiter.len());
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Strips the given delimiter from the start and end of the given raw frontmatter, returning
+/// [`None`] if it isn't delimited on both sides.
+fn strip_delimiters<'a>(attributes: &'a str, delimiter: &str) -> Option<&'a str> {
+    attributes
+        .trim_end()
+        .strip_prefix(delimiter)?
+        .strip_suffix(delimiter)
+}
@@ -1,10 +1,15 @@
 use crate::error::ConfigParseError;
 use directories::ProjectDirs;
+use orgish::Format;
 use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
 #[cfg(test)]
 use std::sync::atomic::AtomicBool;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
 
 /// The global configutation for a Starling instance. This starts as uninstantiated.
 pub static STARLING_CONFIG: GlobalConfig = GlobalConfig::new();
@@ -82,6 +87,139 @@ fn default_port() -> u16 {
 fn default_debounce_duration() -> u64 {
     300
 }
+fn default_backup_retention() -> usize {
+    10
+}
+fn default_journal_retention() -> usize {
+    1000
+}
+fn default_respect_gitignore() -> bool {
+    true
+}
+fn default_title_fallbacks() -> Vec<TitleFallback> {
+    vec![TitleFallback::Heading, TitleFallback::Filename]
+}
+fn default_extensions() -> HashMap<String, FileFormat> {
+    [
+        ("org", FileFormat::Org),
+        ("md", FileFormat::Markdown),
+        ("markdown", FileFormat::Markdown),
+    ]
+    .into_iter()
+    .map(|(ext, format)| (ext.to_string(), format))
+    .collect()
+}
+
+/// A format a file tracked by Starling can be parsed in. This is a configurable mirror of
+/// [`Format`].
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Org,
+    Markdown,
+}
+/// The kind of ID given to new nodes.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// Random (version 4) UUIDs.
+    #[default]
+    UuidV4,
+    /// Time-ordered (version 7) UUIDs, which sort in the order they were created, and record when
+    /// that was.
+    UuidV7,
+    /// ULIDs, which are time-ordered like version 7 UUIDs, but written in 26 characters of
+    /// Crockford's base 32. Nodes whose IDs are already written as UUIDs will keep them.
+    Ulid,
+}
+impl IdScheme {
+    /// Generates a new ID in this scheme.
+    pub fn generate(self) -> Uuid {
+        match self {
+            Self::UuidV4 => Uuid::new_v4(),
+            Self::UuidV7 => Uuid::now_v7(),
+            Self::Ulid => crate::ulid::generate(),
+        }
+    }
+}
+/// A way of inferring the title of a document which doesn't have one set explicitly.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleFallback {
+    /// Use the title of the first top-level heading.
+    Heading,
+    /// Use the filename, without its extension.
+    Filename,
+}
+impl std::fmt::Display for TitleFallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Heading => write!(f, "the first heading"),
+            Self::Filename => write!(f, "the filename"),
+        }
+    }
+}
+
+/// Restrictions on the nodes links of a particular type can connect.
+#[derive(Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct LinkConstraint {
+    /// Tags the node making the link must have one of. If this is empty, any node can make the
+    /// link.
+    #[serde(default)]
+    pub source_tags: Vec<String>,
+    /// Tags the node being linked to must have one of. If this is empty, any node can be linked
+    /// to.
+    #[serde(default)]
+    pub target_tags: Vec<String>,
+    /// Whether or not both ends of the link must be action items (i.e. have a keyword).
+    #[serde(default)]
+    pub action_items: bool,
+    /// Whether or not links of this type are forbidden from forming cycles.
+    #[serde(default)]
+    pub acyclic: bool,
+}
+
+/// The type of a node property, which its raw string value should be parsed as.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+    /// Any string (this is useful for making a property required without constraining it).
+    String,
+    /// An integer.
+    Int,
+    /// A floating-point number.
+    Float,
+    /// `true` or `false`.
+    Bool,
+    /// A date, either as `YYYY-MM-DD` or as an Org timestamp (e.g. `<2024-01-01 Mon>`).
+    Date,
+    /// A duration, either as `H:MM` (as in Org effort estimates) or as a series of amounts with
+    /// units (e.g. `1h 30min`).
+    Duration,
+    /// A comma-separated list of strings.
+    List,
+    /// One of the given strings.
+    Enum(Vec<String>),
+}
+/// The properties nodes with a particular tag should have.
+#[derive(Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct TagSchema {
+    /// Properties these nodes must have.
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// The types of properties on these nodes. These take precedence over `property_types`.
+    #[serde(default)]
+    pub types: HashMap<String, PropertyType>,
+}
+
+impl From<FileFormat> for Format {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Org => Format::Org,
+            FileFormat::Markdown => Format::Markdown,
+        }
+    }
+}
 
 /// The user's configuration of Starling. This is instantiated at the very start as a global
 /// variable, and is used to manage many components of the overall system.
@@ -104,9 +242,76 @@ pub struct Config {
     /// The default type of link. This *must* be contained in `link_types`.
     #[serde(default = "default_default_link_type")]
     pub default_link_type: String,
+    /// The inverses of link types, which backlinks will be reported with. For example, mapping
+    /// `supports` to `supported-by` means that, if A links to B with `supports`, B will see a
+    /// backlink from A of type `supported-by`. Types mapped to themselves are symmetric, and types
+    /// without an inverse will be reported with the same type in both directions.
+    ///
+    /// Every key here must be in `link_types`. Inverses don't have to be, but, if one is, its own
+    /// inverse will be the type it was declared as the inverse of.
+    #[serde(default = "HashMap::new")]
+    pub inverse_link_types: HashMap<String, String>,
+    /// Rules restricting the nodes links of particular types can connect, keyed by link type
+    /// (which must be in `link_types`). Links that break these are still valid connections, but
+    /// they'll be reported as constraint violations.
+    #[serde(default = "HashMap::new")]
+    pub link_constraints: HashMap<String, LinkConstraint>,
+    /// The types of properties, wherever they appear. Properties with types will have their
+    /// values parsed, and nodes with values that don't parse will have property errors.
+    #[serde(default = "HashMap::new")]
+    pub property_types: HashMap<String, PropertyType>,
+    /// Schemas for the properties of nodes with particular tags, keyed by tag.
+    #[serde(default = "HashMap::new")]
+    pub tag_schemas: HashMap<String, TagSchema>,
     /// All the tags that vertices are allowed to have. This global listing prevents typos.
+    ///
+    /// Tags can be hierarchical, with levels separated by `/`: a node tagged `cs/algorithms` is
+    /// implicitly tagged `cs` too, and `cs` is allowed wherever `cs/algorithms` is.
     #[serde(default = "default_tags")]
     pub tags: Vec<String>,
+    /// Alternative names for tags, mapped to the tag they stand for (which must be allowed by
+    /// `tags`, and can't be another alias). Aliases are allowed anywhere their tags are, and are
+    /// treated as their tags when matching.
+    #[serde(default = "HashMap::new")]
+    pub tag_aliases: HashMap<String, String>,
+    /// Whether or not tag aliases on headings should be rewritten to the tags they stand for.
+    /// Tags in the document's attributes (e.g. Markdown frontmatter or `#+filetags`) are written
+    /// back verbatim, so they won't be rewritten.
+    #[serde(default)]
+    pub normalize_tag_aliases: bool,
+    /// Whether or not problems with individual nodes (unknown tags and duplicate IDs) should be
+    /// reported as diagnostics on those nodes, rather than stopping their whole path from being
+    /// parsed. In lenient mode, duplicate IDs will be replaced with new ones.
+    #[serde(default)]
+    pub lenient: bool,
+    /// Whether or not IDs should only be written to headings that need them. In this mode,
+    /// headings without IDs get IDs derived from their paths and positions in them, which are
+    /// stable as long as the headings don't move, and a heading's ID is only written to the disk
+    /// once something links to it. Otherwise, every heading gets a random ID, which is written
+    /// back as soon as it's seen.
+    #[serde(default)]
+    pub lazy_ids: bool,
+    /// Directories (relative to the root directory) in which `lazy_ids` applies even if it's off
+    /// everywhere else, like a directory of daily notes full of small headings.
+    #[serde(default)]
+    pub lazy_id_directories: Vec<PathBuf>,
+    /// The kind of ID to give nodes which don't have one yet (unless `lazy_ids` is on, in which
+    /// case they'll be derived from their positions until they're needed). This won't change any
+    /// IDs which are already on the disk, and links can be written with IDs of any kind.
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+    /// The number of characters at the start of a node's ID which can be used to link to it
+    /// instead of the whole ID (e.g. `[title](5d93b936)` with a length of 8), as long as no other
+    /// node's ID starts the same way. If this is zero, only whole IDs can be used. Time-ordered
+    /// IDs created around the same time will start the same way, so they'll need longer prefixes.
+    #[serde(default)]
+    pub id_prefix_length: usize,
+    /// Whether or not unknown tags found in lenient mode should be collected in a list of tags
+    /// pending approval, which can be approved through the API to stop warning about them. This
+    /// doesn't change the configuration file, so approved tags are only kept until the next
+    /// restart (they should be added to `tags` to keep them).
+    #[serde(default)]
+    pub collect_unknown_tags: bool,
     /// A number of milliseconds to debounce events over. Essentially, updates from the filesystem
     /// need to be watched by Starling to reload the in-memory representation, but sometimes
     /// they'll come very rapidly, so we'll wait until there are no events for this long, and then
@@ -114,19 +319,82 @@ pub struct Config {
     /// long values may lead to poor responsiveness.
     #[serde(default = "default_debounce_duration")]
     pub debounce_duration: u64,
+    /// Whether or not, when a write Starling wants to make conflicts with changes on the disk and
+    /// can't be merged with them, the contents it wanted to write should be saved next to the
+    /// original file as `<file>.starling-conflict.<ext>`. Deleting that file will throw away
+    /// Starling's changes, as will resolving the conflict through the API.
+    #[serde(default)]
+    pub conflict_sidecars: bool,
     /// The directory to write rolling daily log files to. Because retrieving the default for this
     /// can fail, this will start as `None` in the default and be set to the default log directory
     /// when instantiated properly.
     pub log_directory: Option<PathBuf>,
+    /// Whether or not Starling should avoid writing to anything in the directory. Writes it would
+    /// otherwise make (e.g. to update link titles or add IDs) will instead be held until they're
+    /// applied through the API. This can also be turned on with the `--dry-run` flag.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Whether or not the previous contents of files should be backed up before Starling writes
+    /// to them.
+    #[serde(default)]
+    pub backups: bool,
+    /// The directory to keep backups in, if they're enabled. Each file gets its own directory of
+    /// timestamped backups in here, at its path relative to the root directory, so this shouldn't
+    /// be shared with other root directories. If this isn't provided, a directory for this root
+    /// directory in the same place as the default logging directory will be used.
+    pub backup_directory: Option<PathBuf>,
+    /// The number of backups to keep for each file, after which the oldest will be removed. If
+    /// this is zero, all backups will be kept.
+    #[serde(default = "default_backup_retention")]
+    pub backup_retention: usize,
+    /// Whether or not the journal of every write Starling makes should be kept on the disk, so
+    /// writes can still be listed and undone after a restart. Otherwise, it's only kept in memory.
+    #[serde(default)]
+    pub persist_journal: bool,
+    /// The directory to keep the journal in, if it's being persisted, along with the contents of
+    /// each file before and after each write. This shouldn't be shared with other root
+    /// directories. If this isn't provided, a directory for this root directory in the same place
+    /// as the default logging directory will be used.
+    pub journal_directory: Option<PathBuf>,
+    /// The number of most recent writes to keep the contents of files around, so they can be
+    /// undone. Older writes will still be listed in the journal, but can't be undone. If this is
+    /// zero, the contents around every write will be kept.
+    #[serde(default = "default_journal_retention")]
+    pub journal_retention: usize,
     /// The host to serve the Starling server on.
     #[serde(default = "default_host")]
     pub host: String,
     /// The port to serve the Starling server on.
     #[serde(default = "default_port")]
     pub port: u16,
-    /// Any paths to exclude, expressed relative to the root directory.
+    /// Any paths to exclude, expressed as gitignore-style patterns relative to the root directory
+    /// (e.g. `archive/`, `*.draft.md`, or `!keep.md`). These are combined with any
+    /// `.starlingignore` files in the directory, and take precedence over them.
     #[serde(default = "Vec::new")]
     pub exclude_paths: Vec<String>,
+    /// Whether or not to also exclude paths ignored by `.gitignore` files in the directory.
+    #[serde(default = "default_respect_gitignore")]
+    pub respect_gitignore: bool,
+    /// The extensions of files that should be tracked (without a leading `.`), mapped to the
+    /// format they should be parsed in. Files with any other extension will be ignored.
+    #[serde(default = "default_extensions")]
+    pub extensions: HashMap<String, FileFormat>,
+    /// Default formats for directories (relative to the root directory), which files in them
+    /// without any extension will be tracked and parsed in (files with extensions are only ever
+    /// tracked through `extensions`, and hidden files never are). Where these are nested, the
+    /// deepest directory containing a file decides its format.
+    #[serde(default = "HashMap::new")]
+    pub directory_formats: HashMap<PathBuf, FileFormat>,
+    /// The ways to infer the title of a document without one in its frontmatter or `#+title`,
+    /// tried in order. If none of these work, the document will fail to parse; otherwise, it'll be
+    /// parsed with a warning.
+    #[serde(default = "default_title_fallbacks")]
+    pub title_fallbacks: Vec<TitleFallback>,
+    /// Whether or not links to nodes by their titles or aliases (e.g. `[[link:Some Title]]`)
+    /// should be rewritten to link to the IDs of those nodes once they're resolved. This makes
+    /// links robust to later changes in titles, at the cost of readability in the source files.
+    #[serde(default)]
+    pub rewrite_name_links: bool,
 }
 
 impl Default for Config {
@@ -135,12 +403,37 @@ impl Default for Config {
             action_keywords: default_action_keywords(),
             link_types: default_link_types(),
             default_link_type: default_default_link_type(),
+            inverse_link_types: HashMap::new(),
+            link_constraints: HashMap::new(),
+            property_types: HashMap::new(),
+            tag_schemas: HashMap::new(),
             tags: default_tags(),
+            tag_aliases: HashMap::new(),
+            normalize_tag_aliases: false,
+            lenient: false,
+            lazy_ids: false,
+            lazy_id_directories: Vec::new(),
+            id_scheme: IdScheme::default(),
+            id_prefix_length: 0,
+            collect_unknown_tags: false,
             debounce_duration: default_debounce_duration(),
+            conflict_sidecars: false,
             host: default_host(),
             port: default_port(),
             log_directory: None,
+            read_only: false,
+            backups: false,
+            backup_directory: None,
+            backup_retention: default_backup_retention(),
+            persist_journal: false,
+            journal_directory: None,
+            journal_retention: default_journal_retention(),
             exclude_paths: Vec::new(),
+            respect_gitignore: default_respect_gitignore(),
+            extensions: default_extensions(),
+            directory_formats: HashMap::new(),
+            title_fallbacks: default_title_fallbacks(),
+            rewrite_name_links: false,
         }
     }
 }
@@ -175,6 +468,7 @@ impl Config {
 
         // Validate the config
         if let Ok(mut config) = config_res {
+            config.default_vault_dirs(dir);
             config.validate()?;
 
             Ok(config)
@@ -183,9 +477,103 @@ impl Config {
             config_res
         }
     }
+    /// Fills in the default locations of anything Starling keeps outside the given root directory
+    /// that hasn't been set manually. These are kept in a separate directory for each root
+    /// directory, in the same place as the default logging directory, so that different
+    /// instances can't get their files mixed up.
+    fn default_vault_dirs(&mut self, dir: &Path) {
+        let Some(proj_dirs) = ProjectDirs::from("org", "starling", "starling") else {
+            // Validation will complain about anything we needed a default for
+            return;
+        };
+        let vault_dir = proj_dirs.data_dir().join("vaults").join(vault_name(dir));
+        if self.backup_directory.is_none() {
+            self.backup_directory = Some(vault_dir.join("backups"));
+        }
+        if self.persist_journal && self.journal_directory.is_none() {
+            self.journal_directory = Some(vault_dir.join("journal"));
+        }
+    }
+    /// Gets the directory the journal should be persisted in, if it's being persisted.
+    pub fn journal_dir(&self) -> Option<&Path> {
+        self.journal_directory
+            .as_deref()
+            .filter(|_| self.persist_journal)
+    }
+    /// Returns whether or not IDs should be written lazily in the file at the given (relative)
+    /// path (see `lazy_ids`).
+    pub fn lazy_ids_for(&self, path: &Path) -> bool {
+        self.lazy_ids
+            || self
+                .lazy_id_directories
+                .iter()
+                .any(|dir| path.starts_with(dir))
+    }
+    /// Resolves the format the file at the given (relative) path should be parsed in. This will
+    /// return [`None`] if the path doesn't have one of the tracked extensions, unless it has no
+    /// extension and is in a directory with a default format.
+    ///
+    /// This is the *only* place formats should be determined from paths.
+    pub fn format_for(&self, path: &Path) -> Option<Format> {
+        let format = match path.extension() {
+            Some(ext) => self.extensions.get(ext.to_str()?)?,
+            // Hidden files (like `.starlingignore`) don't have extensions either
+            None if path
+                .file_name()
+                .is_some_and(|name| !name.to_string_lossy().starts_with('.')) =>
+            {
+                path.ancestors()
+                    .skip(1)
+                    .find_map(|dir| self.directory_formats.get(dir))?
+            }
+            None => return None,
+        };
+        Some((*format).into())
+    }
+    /// Determines whether or not the given tag is allowed: it must be in `tags`, an ancestor of
+    /// one of those, or an alias.
+    pub fn is_valid_tag(&self, tag: &str) -> bool {
+        self.tag_aliases.contains_key(tag)
+            || self.tags.iter().any(|t| {
+                t == tag
+                    || t.strip_prefix(tag)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+    /// Gets the tag the given tag stands for, if it's an alias, or the tag itself otherwise.
+    pub fn canonical_tag<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.tag_aliases
+            .get(tag)
+            .map(|canonical| canonical.as_str())
+            .unwrap_or(tag)
+    }
+    /// Expands the given tags into the full set of tags they imply, by resolving aliases and
+    /// adding the ancestors of hierarchical tags (e.g. `cs/algorithms` becomes `cs/algorithms`
+    /// and `cs`).
+    pub fn expand_tags<'a>(&self, tags: impl IntoIterator<Item = &'a String>) -> HashSet<String> {
+        let mut expanded = HashSet::new();
+        for tag in tags {
+            let tag = self.canonical_tag(tag);
+            expanded.extend(
+                tag.match_indices('/')
+                    .map(|(idx, _)| tag[..idx].to_string()),
+            );
+            expanded.insert(tag.to_string());
+        }
+
+        expanded
+    }
+    /// Gets the type a backlink should be reported with, given the type of the connection it
+    /// comes from. See `inverse_link_types`.
+    pub fn inverse_link_type<'a>(&'a self, ty: &'a str) -> &'a str {
+        self.inverse_link_types
+            .get(ty)
+            .map(|inverse| inverse.as_str())
+            .unwrap_or(ty)
+    }
     /// Validates this configuration, returning an error if it finds an invalid part. This will
     /// also create expensive defaults if needed.
-    fn validate(&mut self) -> Result<(), ConfigParseError> {
+    pub(crate) fn validate(&mut self) -> Result<(), ConfigParseError> {
         if self.link_types.contains(&"".to_string()) {
             return Err(ConfigParseError::EmptyLinkType);
         }
@@ -196,6 +584,60 @@ impl Config {
             self.link_types.push(self.default_link_type.clone());
         }
 
+        // Inverses have to be of real types, and consistent with each other
+        for (ty, inverse) in &self.inverse_link_types {
+            if !self.link_types.contains(ty) {
+                return Err(ConfigParseError::UnknownInverseLinkType { ty: ty.clone() });
+            }
+            if inverse.is_empty() {
+                return Err(ConfigParseError::EmptyLinkType);
+            }
+            if let Some(other) = self.inverse_link_types.get(inverse) {
+                if other != ty {
+                    return Err(ConfigParseError::InconsistentInverseLinkType {
+                        ty: ty.clone(),
+                        inverse: inverse.clone(),
+                        other: other.clone(),
+                    });
+                }
+            }
+        }
+        if let Some(ty) = self
+            .link_constraints
+            .keys()
+            .find(|ty| !self.link_types.contains(ty))
+        {
+            return Err(ConfigParseError::UnknownConstrainedLinkType { ty: ty.clone() });
+        }
+        if let Some(tag) = self.tag_schemas.keys().find(|tag| !self.is_valid_tag(tag)) {
+            return Err(ConfigParseError::UnknownSchemaTag { tag: tag.clone() });
+        }
+        // Aliases can stand for ancestors of tags too, but not for other aliases (they're only
+        // resolved once)
+        if let Some(canonical) = self.tag_aliases.values().find(|canonical| {
+            self.tag_aliases.contains_key(*canonical) || !self.is_valid_tag(canonical)
+        }) {
+            return Err(ConfigParseError::UnknownAliasedTag {
+                tag: canonical.clone(),
+            });
+        }
+        // Links of an inverse type should have the original type on their backlinks
+        let implied = self
+            .inverse_link_types
+            .iter()
+            .filter(|(_, inverse)| {
+                self.link_types.contains(inverse) && !self.inverse_link_types.contains_key(*inverse)
+            })
+            .map(|(ty, inverse)| (inverse.clone(), ty.clone()))
+            .collect::<Vec<_>>();
+        self.inverse_link_types.extend(implied);
+
+        // Be lenient with extensions written like `.md` (we'd never match those otherwise)
+        self.extensions = std::mem::take(&mut self.extensions)
+            .into_iter()
+            .map(|(ext, format)| (ext.trim_start_matches('.').to_string(), format))
+            .collect();
+
         // Validate the logging directory, or set one up if a custom one wasn't provided
         if let Some(log_dir) = &self.log_directory {
             if !log_dir.is_dir() {
@@ -226,6 +668,52 @@ impl Config {
         }
         // By now, `self.log_directory` is guaranteed to be `Some(valid_dir)`
 
+        // If we're making backups, make sure we've got somewhere to put them
+        if self.backups {
+            let backup_dir = self
+                .backup_directory
+                .as_ref()
+                .ok_or(ConfigParseError::NoDefaultBackupDir)?;
+            std::fs::create_dir_all(backup_dir).map_err(|err| {
+                ConfigParseError::CreateBackupDirFailed {
+                    path: backup_dir.clone(),
+                    err,
+                }
+            })?;
+        }
+
+        // If we're persisting the journal, make sure we've got somewhere to put it
+        if self.persist_journal {
+            let journal_dir = self
+                .journal_directory
+                .as_ref()
+                .ok_or(ConfigParseError::NoDefaultJournalDir)?;
+            std::fs::create_dir_all(journal_dir).map_err(|err| {
+                ConfigParseError::CreateJournalDirFailed {
+                    path: journal_dir.clone(),
+                    err,
+                }
+            })?;
+        }
+
         Ok(())
     }
 }
+
+/// Gets the name of the directory Starling keeps things for the given root directory in outside
+/// it, which is the name of the root directory followed by a hash of its canonical path (with
+/// 64-bit FNV-1a, which, unlike the standard library's hasher, is stable across versions).
+pub(crate) fn vault_name(dir: &Path) -> String {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let hash = dir
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".to_string());
+    format!("{name}-{hash:016x}")
+}
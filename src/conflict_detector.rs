@@ -1,4 +1,5 @@
 use crate::debouncer::{DebouncedEvents, Event};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
@@ -52,7 +53,9 @@ impl ConflictDetector {
     /// When the provided writes attempt to write to a file that has been deleted, the write is
     /// dropped. When they try to write to a file that has been renamed, they are adjusted to write
     /// to that file. When they try to write to a file that has been modified (including one that
-    /// was renamed and then the renamed path was modified), a conflict is produced.
+    /// was renamed and then the renamed path was modified), a conflict is produced, unless the
+    /// write came from filesystem processing and doesn't have a base to be merged against, in
+    /// which case it's dropped (the modification will be processed and produce its own writes).
     ///
     /// This will decrement the "reference count" on the patch with the given index internally,
     /// meaning once this is called for every update that depended on that patch, its information
@@ -143,16 +146,14 @@ impl ConflictDetector {
                             PathRename::None => {
                                 break match event {
                                     // Path has been modified, we have a conflict (but for
-                                    // filesystem updates, they're not strictly necessary, we can
-                                    // just drop them)
+                                    // filesystem updates, they're not strictly necessary, so we
+                                    // can just drop them if they couldn't be merged anyway)
                                     Some(Event::Create(_)) | Some(Event::Modify(_)) => {
                                         match write.source {
-                                            WriteSource::Filesystem => None,
-                                            WriteSource::Other => Some(Write {
-                                                path: write.path,
-                                                contents: write.contents,
-                                                source: write.source,
+                                            WriteSource::Filesystem if write.base.is_none() => None,
+                                            _ => Some(Write {
                                                 conflict: Conflict::Simple,
+                                                ..write
                                             }),
                                         }
                                     }
@@ -170,10 +171,8 @@ impl ConflictDetector {
                             // Instant conflict
                             PathRename::Many(paths) => {
                                 break Some(Write {
-                                    path: write.path,
-                                    contents: write.contents,
-                                    source: write.source,
                                     conflict: Conflict::Multi(paths.clone()),
+                                    ..write
                                 })
                             }
                         }
@@ -337,9 +336,16 @@ pub struct Write {
     pub source: WriteSource,
     /// The type of conflict on this write, if any.
     pub conflict: Conflict,
+    /// The contents of the path this write was computed from, if they're known. If the path is
+    /// modified before the write is made, the write can be merged with those modifications
+    /// against these.
+    pub base: Option<String>,
+    /// Why this write is being made, which will be recorded in the journal.
+    pub reasons: Vec<WriteReason>,
 }
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
 pub enum WriteSource {
     /// This write came after processing a patch from the filesystem. These writes are entirely
     /// secondary, and only contain minor changes to things like link titles. If they conflict with
@@ -357,6 +363,26 @@ pub enum WriteSource {
     Other,
 }
 
+/// Reasons Starling might write to a file.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteReason {
+    /// New nodes were found in the file, which might have needed IDs added to them.
+    NewNodes,
+    /// The titles of links to nodes whose titles have changed were updated.
+    LinkTitles,
+    /// Tag aliases were replaced with the tags they stand for.
+    TagAliases,
+    /// A node whose ID wasn't on the disk yet was linked to, so its ID was added.
+    LinkTargets,
+    /// The user resolved a conflict.
+    ConflictResolution,
+    /// The user undid an earlier write.
+    Undo,
+    /// The user applied a write held back in read-only mode.
+    PendingWrite,
+}
+
 /// Types of conflicts that can occur on a write.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum Conflict {
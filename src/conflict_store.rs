@@ -0,0 +1,221 @@
+use crate::{
+    conflict_detector::{Conflict, Write, WriteReason, WriteSource},
+    diff::unified_diff,
+    error::ApiError,
+};
+use crossbeam_queue::SegQueue;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// The marker inserted before the extension of a file to get the name of its conflict sidecar.
+static SIDECAR_MARKER: &str = "starling-conflict";
+
+/// A store of the writes Starling wanted to make, but couldn't because they conflicted with
+/// changes on the disk. These are held until the user decides what to do with each of them, and
+/// the writes they decide should go ahead are handed back to the [`crate::fs_engine::FsEngine`].
+pub struct ConflictStore {
+    /// All the conflicts awaiting resolution, in the order they occurred.
+    conflicts: Mutex<Vec<StoredConflict>>,
+    /// Writes which the user has chosen to make in resolving conflicts, which haven't yet been
+    /// made.
+    resolved: SegQueue<Write>,
+    /// Sidecar files for conflicts which have been resolved, which haven't yet been removed.
+    stale_sidecars: SegQueue<PathBuf>,
+}
+impl ConflictStore {
+    /// Creates a new, empty [`ConflictStore`].
+    pub fn new() -> Self {
+        Self {
+            conflicts: Mutex::new(Vec::new()),
+            resolved: SegQueue::new(),
+            stale_sidecars: SegQueue::new(),
+        }
+    }
+    /// Records the given conflicted write, along with the contents of the path it was going to be
+    /// written to as they are now (if it still exists) and the sidecar file its contents were
+    /// saved to (if they were), returning the ID of the new conflict.
+    ///
+    /// Any conflicts already stored for the same path will be dropped, because the new write will
+    /// have been computed from more recent contents.
+    pub fn record(
+        &self,
+        write: Write,
+        disk_contents: Option<String>,
+        sidecar: Option<PathBuf>,
+    ) -> Uuid {
+        let mut candidates = match write.conflict {
+            Conflict::Multi(paths) => paths.into_iter().collect(),
+            Conflict::Simple | Conflict::None => Vec::new(),
+        };
+        candidates.sort();
+        let conflict = StoredConflict {
+            id: Uuid::new_v4(),
+            path: write.path,
+            contents: write.contents,
+            disk_contents,
+            candidates,
+            sidecar,
+        };
+        let id = conflict.id;
+
+        let mut conflicts = self.conflicts.lock();
+        conflicts.retain(|c| {
+            let replaced = c.path == conflict.path;
+            // The new conflict's sidecar would have overwritten the old one
+            if replaced && c.sidecar.is_some() && c.sidecar != conflict.sidecar {
+                self.stale_sidecars.push(c.sidecar.clone().unwrap());
+            }
+            !replaced
+        });
+        conflicts.push(conflict);
+        id
+    }
+    /// Gets all the conflicts awaiting resolution, in the order they occurred.
+    pub fn list(&self) -> Vec<StoredConflict> {
+        self.conflicts.lock().clone()
+    }
+    /// Gets the conflict with the given ID, if it's still awaiting resolution.
+    pub fn get(&self, id: Uuid) -> Option<StoredConflict> {
+        self.conflicts.lock().iter().find(|c| c.id == id).cloned()
+    }
+    /// Resolves the conflict with the given ID in the given way. If this means a write should be
+    /// made, it will be made by the filesystem engine the next time the filesystem is quiet,
+    /// regardless of any changes to the disk made in the meantime.
+    pub fn resolve(&self, id: Uuid, resolution: Resolution) -> Result<(), ApiError> {
+        let mut conflicts = self.conflicts.lock();
+        let idx = conflicts
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or(ApiError::ConflictNotFound { id })?;
+        let conflict = &conflicts[idx];
+        let path = match resolution {
+            Resolution::KeepDisk => None,
+            Resolution::ForceMine => Some(conflict.path.clone()),
+            Resolution::Target { .. } if conflict.candidates.is_empty() => {
+                return Err(ApiError::InvalidResolution {
+                    id,
+                    reason: "only conflicts with multiple candidate paths can have a target"
+                        .to_string(),
+                })
+            }
+            Resolution::Target { path } if !conflict.candidates.contains(&path) => {
+                return Err(ApiError::InvalidResolution {
+                    id,
+                    reason: format!("{path:?} is not one of the candidate paths"),
+                })
+            }
+            Resolution::Target { path } => Some(path),
+        };
+
+        let conflict = conflicts.remove(idx);
+        if let Some(sidecar) = conflict.sidecar {
+            self.stale_sidecars.push(sidecar);
+        }
+        if let Some(path) = path {
+            self.resolved.push(Write {
+                path,
+                contents: conflict.contents,
+                source: WriteSource::Other,
+                conflict: Conflict::None,
+                base: None,
+                reasons: vec![WriteReason::ConflictResolution],
+            });
+        }
+        Ok(())
+    }
+    /// Drops the conflict whose contents were saved to the given sidecar file, because the user
+    /// has deleted it. This is the same as resolving it by keeping what's on the disk. This
+    /// returns the ID of the conflict, if there was one.
+    pub fn discard_sidecar(&self, sidecar: &Path) -> Option<Uuid> {
+        let mut conflicts = self.conflicts.lock();
+        let idx = conflicts
+            .iter()
+            .position(|c| c.sidecar.as_deref() == Some(sidecar))?;
+        Some(conflicts.remove(idx).id)
+    }
+    /// Takes all the sidecar files of resolved conflicts which should be removed since this was
+    /// last called.
+    pub fn take_stale_sidecars(&self) -> Vec<PathBuf> {
+        let mut sidecars = Vec::new();
+        while let Some(sidecar) = self.stale_sidecars.pop() {
+            sidecars.push(sidecar);
+        }
+        sidecars
+    }
+    /// Takes all the writes the user has chosen to make in resolving conflicts since this was
+    /// last called.
+    pub fn take_resolved(&self) -> Vec<Write> {
+        let mut writes = Vec::new();
+        while let Some(write) = self.resolved.pop() {
+            writes.push(write);
+        }
+        writes
+    }
+}
+
+/// A write which couldn't be made because it conflicted with changes on the disk.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StoredConflict {
+    /// A unique identifier for this conflict, used to resolve it.
+    pub id: Uuid,
+    /// The (relative) path the write was going to.
+    pub path: PathBuf,
+    /// The contents Starling wanted to write.
+    pub contents: String,
+    /// The contents of the path on the disk when the conflict occurred, or `None` if it no longer
+    /// existed.
+    pub disk_contents: Option<String>,
+    /// If the path was moved to several places and we couldn't tell which one the write should go
+    /// to, all those places. Otherwise, this will be empty.
+    pub candidates: Vec<PathBuf>,
+    /// The (relative) path of the sidecar file the contents Starling wanted to write were saved
+    /// to, if they were.
+    pub sidecar: Option<PathBuf>,
+}
+impl StoredConflict {
+    /// Gets a unified diff from the contents on the disk to the contents Starling wanted to write.
+    pub fn diff(&self) -> String {
+        let name = self.path.to_string_lossy();
+        unified_diff(
+            self.disk_contents.as_deref().unwrap_or(""),
+            &self.contents,
+            &format!("disk/{name}"),
+            &format!("starling/{name}"),
+        )
+    }
+}
+
+/// The ways the user can resolve a conflict.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Resolution {
+    /// Leave the disk as it is, throwing away Starling's write.
+    KeepDisk,
+    /// Make Starling's write to the original path, overwriting whatever's there.
+    ForceMine,
+    /// Make Starling's write to the given path, which must be one of the candidates of a conflict
+    /// over where the write should go.
+    Target { path: PathBuf },
+}
+
+/// Gets the path of the sidecar file for conflicts on the given path, which will be next to it
+/// (e.g. `notes/foo.starling-conflict.md` for `notes/foo.md`).
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{SIDECAR_MARKER}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{SIDECAR_MARKER}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Returns whether or not the given path is a conflict sidecar file.
+pub fn is_sidecar(path: &Path) -> bool {
+    let marker = format!(".{SIDECAR_MARKER}");
+    path.extension().is_some_and(|ext| ext == SIDECAR_MARKER)
+        || path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().ends_with(&marker))
+}
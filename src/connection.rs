@@ -1,12 +1,84 @@
 use crate::{
-    config::STARLING_CONFIG,
+    attributes::{AttributeValue, Attributes},
+    config::{IdScheme, STARLING_CONFIG},
+    constraints::ConstraintViolation,
     graph::IndexCriteria,
     path_node::{StarlingDocument, StarlingNode},
 };
+use directories::BaseDirs;
 use orgish::Format;
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::{Component, Path, PathBuf},
+};
 use uuid::Uuid;
 
+/// Gets the key a link by name (i.e. to a title or alias, rather than to an ID) is recorded under
+/// in connection maps and the graph. Names are compared case-insensitively, and with runs of
+/// whitespace collapsed.
+///
+/// These keys are version 8 UUIDs, so they'll never collide with the version 4 or 7 UUIDs nodes
+/// are given (and ULIDs are no more likely to collide with them than with each other), but
+/// they're only stable within a single run of Starling, and should never be written to disk.
+pub fn name_key(name: &str) -> Uuid {
+    let normalized = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    hash_key("name", &normalized)
+}
+
+/// Gets the key a link by the short form of an ID (see [`short_id`]) is recorded under. This works
+/// like [`name_key`], but the two are hashed separately, so a node whose title happens to look
+/// like the start of an ID can't make links by that short ID ambiguous.
+pub fn short_id_key(short_id: &str) -> Uuid {
+    hash_key("short_id", &short_id.to_lowercase())
+}
+
+/// Hashes the given value into a version 8 UUID, separating different kinds of keys by the given
+/// domain.
+fn hash_key(domain: &str, value: &str) -> Uuid {
+    let mut bytes = [0; 16];
+    for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = DefaultHasher::new();
+        (i, domain, value).hash(&mut hasher);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+
+    Uuid::new_v8(bytes)
+}
+
+/// Gets the short form of the given ID that it can be linked to by, if short IDs are enabled (see
+/// [`crate::config::Config::id_prefix_length`]). This is the start of the ID as it's written, so
+/// the start of its ULID if it's written as one. This is recorded like a name of the node (under
+/// its [`short_id_key`]), so links to it will only resolve if no other node's ID starts the same
+/// way.
+pub fn short_id(id: Uuid, ulid: bool) -> Option<String> {
+    let length = STARLING_CONFIG.get().id_prefix_length;
+    let id = if ulid {
+        crate::ulid::encode(id)
+    } else {
+        id.to_string()
+    };
+    (length > 0 && length < id.len()).then(|| id[..length].to_string())
+}
+
+/// Returns whether or not the given link target is the short form of an ID with the given prefix
+/// length (see [`short_id`]), written as either a UUID or a ULID.
+pub(crate) fn is_short_id(target: &str, length: usize) -> bool {
+    let uuid = || {
+        target.chars().enumerate().all(|(idx, c)| match idx {
+            // Where the hyphens go in a UUID
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+    };
+    length > 0 && target.len() == length && (uuid() || crate::ulid::is_prefix(target))
+}
+
 /// A connection from one node to another, by the unique ID of the node being connected to.
 /// Connections can have *types* to encode metadata, and all have a title, which will be updated to
 /// ensure it's valid.
@@ -26,63 +98,69 @@ struct Connection {
     ///
     /// This will be used for reconstructing the link, whatever it may be.
     title: String,
+    /// The syntax the link was originally written in, which will be preserved when it's written
+    /// back.
+    style: LinkStyle,
+    /// The title or alias this link targeted, if it was a link by name rather than by ID. This
+    /// will be written back in place of the ID of the node it resolved to, unless the user has
+    /// asked for links by name to be rewritten (in which case this will be cleared once the link
+    /// is resolved).
+    name: Option<String>,
+    /// Whether or not the ID this link targeted was written as a ULID, in which case it'll be
+    /// written back as one.
+    ulid: bool,
+}
+/// The syntax a connection was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkStyle {
+    /// The standard link syntax of the format: `[title](type:key)` in Markdown, or
+    /// `[[type:key][title]]` in Org.
+    Standard,
+    /// A Markdown wiki link, like `[[type:key|title]]`, as used by Obsidian and Foam. Unlike
+    /// standard links, these are written back exactly as they were: a missing type won't be
+    /// qualified (since other tools would see it as part of the target), and a missing title won't
+    /// be added.
+    Wiki { typed: bool, titled: bool },
+    /// An Org link with no description, like `[[type:key]]`. These are written back without one.
+    Bare { typed: bool },
+    /// An Org link to a heading or custom ID, like `[[*Heading][title]]` or `[[#custom-id]]`.
+    /// Org only understands these without a type, so they're written back exactly as they were
+    /// (as long as they're still written with their name).
+    Search { typed: bool, titled: bool },
 }
 impl Connection {
-    /// Parses a single connection from a string of the form `[title](type:key)` in Markdown, or
-    /// `[[type:key][title]]` in Org mode. In these formats, `type` will be one of the types the
-    /// user has specified in their configuration, and `key` will be the unique identifier of
-    /// another node in the graph. This will return both the ID, as well as the metadata properties
-    /// of the title and type.
+    /// Parses a single connection from a string of the form `[title](type:key)` or
+    /// `[[type:key|title]]` in Markdown, or `[[type:key][title]]` or `[[type:key]]` in Org mode.
+    /// In these formats, `type` will be one of the types the user has specified in their
+    /// configuration, and `key` will be the unique identifier of another node in the graph. This
+    /// will return both the ID, as well as the metadata properties of the title and type.
+    ///
+    /// The key can also be the title or alias of another node, as long as the link is typed or a
+    /// wiki link (otherwise we'd pick up every link to a relative path), or the short form of
+    /// another node's ID, if those are enabled (see [`short_id`]). In this case, the
+    /// returned ID will be the [`name_key`] (or [`short_id_key`]) of that name, which the graph
+    /// will try to resolve. Anything that looks like a short ID is always treated as one, so a
+    /// node titled like that can only be linked to by its aliases.
+    /// In Org, links to headings (`*Heading`) and custom IDs (`#custom-id`) are names too, which
+    /// will be resolved within the same document first (see [`ConnectedNode::from_node`]).
     ///
     /// This function will return `None` if it is provided either a string which is not a link, or
     /// a link which does not conform to the expected format.
     fn from_str(link: &str, format: Format) -> Option<(Uuid, Self)> {
-        let link = link.trim();
-
-        // Regardless of the format, this will get the title and get the parts of the link
-        // around the `:` (if one, a generic link; if two, a typed link)
-        let (title, link_parts) = match format {
-            // Format: [Title](ty:link)
-            Format::Markdown => {
-                if !link.starts_with('[') || !link.ends_with(')') || !link.contains("](") {
-                    // This indicates something that doesn't even qualify as a link
-                    return None;
-                }
-
-                // Parse the link into its parts
-                let mut link_parts = link.splitn(2, "](");
-                let title = link_parts.next().unwrap();
-                let title = title.strip_prefix('[').unwrap();
-                let link = link_parts.next().unwrap();
-                let link = link.strip_suffix(')').unwrap();
-
-                (title, link.splitn(2, ':').collect::<Vec<_>>())
-            }
-            // Format: [[ty:link][Title]]
-            Format::Org => {
-                if !link.starts_with("[[") || !link.ends_with("]]") || !link.contains("][") {
-                    return None;
-                }
-
-                // Parse the link into its parts (ignoring the title)
-                let mut link_parts = link.splitn(2, "][");
-                let link = link_parts.next().unwrap();
-                let link = link.strip_prefix("[[").unwrap();
-                let title = link_parts.next()?;
-                let title = title.strip_suffix("]]").unwrap();
-
-                (title, link.splitn(2, ':').collect::<Vec<_>>())
-            }
-        };
+        // This will get the title and the parts of the link around the `:` (if one, a generic
+        // link; if two, a typed link)
+        let (title, target, mut style) = parse_link(link, format)?;
+        let link_parts = target.splitn(2, ':').collect::<Vec<_>>();
 
         let config = STARLING_CONFIG.get();
-        let (target_str, ty) = if link_parts.len() == 2 {
+        let typed = link_parts.len() == 2;
+        let (target_str, ty) = if typed {
             // We have two parts, parse the first one as a link type
             if config.link_types.iter().any(|t| t == link_parts[0]) {
                 (link_parts[1], link_parts[0])
             } else {
-                // This is not a valid link type
-                // TODO: URL links trigger this path, what should we do with resources?
+                // This is not a valid link type (URLs will end up here, and they'll be picked up
+                // as resources instead)
                 return None;
             }
         } else {
@@ -90,31 +168,289 @@ impl Connection {
             (link_parts[0], config.default_link_type.as_str())
         };
 
-        // Try to parse the target as a UUID, if we can, then it's an attempt to link to another
-        // vertex; otherwise, it's not a link as far as we're concerned
-        let id = Uuid::try_parse(target_str).ok()?;
+        // Org searches for headings and custom IDs are always names, even without a type
+        let search = format == Format::Org
+            && (target_str.starts_with('*') || target_str.starts_with('#'))
+            && target_str.len() > 1;
+        if search {
+            style = LinkStyle::Search {
+                typed,
+                titled: style == LinkStyle::Standard,
+            };
+        }
+
+        // Try to parse the target as a UUID or a ULID, if we can, then it's an attempt to link to
+        // another vertex directly; otherwise, it might be a link to another vertex by name
+        let parsed = match Uuid::try_parse(target_str) {
+            Ok(id) => Some((id, false)),
+            Err(_) => crate::ulid::decode(target_str).map(|id| (id, true)),
+        };
+        let (id, name, ulid) = match parsed {
+            Some((id, ulid)) => (id, None, ulid),
+            None if is_short_id(target_str, config.id_prefix_length) => (
+                short_id_key(target_str),
+                Some(target_str.to_string()),
+                false,
+            ),
+            None if search => (name_key(target_str), Some(target_str.to_string()), false),
+            None if (typed || matches!(style, LinkStyle::Wiki { .. }))
+                && !target_str.trim().is_empty()
+                && ResourceKind::detect(target_str, false).is_none() =>
+            {
+                (name_key(target_str), Some(target_str.to_string()), false)
+            }
+            // Not a link as far as we're concerned
+            None => return None,
+        };
 
         Some((
             id,
             Self {
                 ty: ty.to_string(),
                 title: title.to_string(),
+                style,
+                name,
+                ulid,
             },
         ))
     }
     /// Converts this connection into a string in the given [`Format`]. This will use whatever the
     /// registered title is for the connection, and will fully-qualify the link type (e.g. the
-    /// default will not be elided).
+    /// default will not be elided), unless this was a wiki link, which will be written back in the
+    /// same style it was written in (if the format is Markdown).
     ///
     /// As [`Connection`] does not include the ID of the node it points to, the ID must be provided
-    /// separately.
+    /// separately. Links by name will be written with their name.
     fn to_string(&self, id: Uuid, format: Format) -> String {
-        match format {
-            Format::Markdown => {
-                format!("[{}]({}:{})", self.title, self.ty, id)
+        let target = match &self.name {
+            Some(name) => name.clone(),
+            None if self.ulid => crate::ulid::encode(id),
+            None => id.to_string(),
+        };
+        match (format, self.style) {
+            (Format::Markdown, LinkStyle::Wiki { typed, titled }) => {
+                let mut link = String::from("[[");
+                if typed {
+                    link.push_str(&self.ty);
+                    link.push(':');
+                }
+                link.push_str(&target);
+                if titled {
+                    link.push('|');
+                    link.push_str(&self.title);
+                }
+                link.push_str("]]");
+                link
+            }
+            (Format::Markdown, _) => {
+                format!("[{}]({}:{})", self.title, self.ty, target)
+            }
+            (Format::Org, LinkStyle::Bare { typed: true }) => {
+                format!("[[{}:{}]]", self.ty, target)
+            }
+            (Format::Org, LinkStyle::Bare { typed: false }) => format!("[[{}]]", target),
+            (Format::Org, LinkStyle::Search { typed, titled }) if self.name.is_some() => {
+                let mut link = String::from("[[");
+                if typed {
+                    link.push_str(&self.ty);
+                    link.push(':');
+                }
+                link.push_str(&target);
+                if titled {
+                    link.push_str("][");
+                    link.push_str(&self.title);
+                }
+                link.push_str("]]");
+                link
+            }
+            // Once a search has been rewritten to an ID, it's just a regular link
+            (Format::Org, LinkStyle::Search { titled: false, .. }) => {
+                format!("[[{}:{}]]", self.ty, target)
+            }
+            (Format::Org, _) => format!("[[{}:{}][{}]]", self.ty, target, self.title),
+        }
+    }
+}
+
+/// Splits a link of any of the forms a [`Connection`] can take into its title, its target
+/// (including any type), and the syntax it was written in. This doesn't check that the target
+/// is anything in particular.
+fn parse_link(link: &str, format: Format) -> Option<(&str, &str, LinkStyle)> {
+    let link = link.trim();
+    let parts = match format {
+        // Format: [[ty:link|Title]] (both the type and title are optional)
+        Format::Markdown if link.starts_with("[[") && link.ends_with("]]") => {
+            let link = link.strip_prefix("[[").unwrap().strip_suffix("]]").unwrap();
+            let (link, title) = match link.split_once('|') {
+                Some((link, title)) => (link, Some(title)),
+                None => (link, None),
+            };
+            let style = LinkStyle::Wiki {
+                typed: link.contains(':'),
+                titled: title.is_some(),
+            };
+
+            (title.unwrap_or_default(), link, style)
+        }
+        // Format: [Title](ty:link)
+        Format::Markdown => {
+            if !link.starts_with('[') || !link.ends_with(')') || !link.contains("](") {
+                // This indicates something that doesn't even qualify as a link
+                return None;
+            }
+
+            // Parse the link into its parts
+            let mut link_parts = link.splitn(2, "](");
+            let title = link_parts.next().unwrap();
+            let title = title.strip_prefix('[').unwrap();
+            let link = link_parts.next().unwrap();
+            let link = link.strip_suffix(')').unwrap();
+
+            (title, link, LinkStyle::Standard)
+        }
+        // Format: [[ty:link]]
+        Format::Org if link.starts_with("[[") && link.ends_with("]]") && !link.contains("][") => {
+            let link = link.strip_prefix("[[").unwrap().strip_suffix("]]").unwrap();
+            let style = LinkStyle::Bare {
+                typed: link.contains(':'),
+            };
+
+            ("", link, style)
+        }
+        // Format: [[ty:link][Title]]
+        Format::Org => {
+            if !link.starts_with("[[") || !link.ends_with("]]") || !link.contains("][") {
+                return None;
+            }
+
+            // Parse the link into its parts
+            let mut link_parts = link.splitn(2, "][");
+            let link = link_parts.next().unwrap();
+            let link = link.strip_prefix("[[").unwrap();
+            let title = link_parts.next()?;
+            let title = title.strip_suffix("]]").unwrap();
+
+            (title, link, LinkStyle::Standard)
+        }
+    };
+
+    Some(parts)
+}
+
+/// A reference from a node to something outside the graph, like a web page or a local file.
+/// Unlike connections, these are never rewritten, they're just recorded.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Resource {
+    /// What kind of resource this is.
+    pub kind: ResourceKind,
+    /// The type of the link to the resource, which comes from the same list as the types of
+    /// connections.
+    pub ty: String,
+    /// The target of the link, as it was written (without any type or `file:` prefix). Relative
+    /// file paths are relative to the directory of the path containing the link.
+    pub target: String,
+}
+/// The kinds of [`Resource`]s nodes can link to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    /// A web page, linked to with an `http://` or `https://` URL.
+    Url,
+    /// A local file, linked to with a `file:` link or a relative path.
+    File,
+}
+impl ResourceKind {
+    /// Detects what kind of resource the given link target (without a type) points to, if any,
+    /// returning the target without any `file:` prefix.
+    ///
+    /// Paths like `./image.png` will always be detected as files, but paths like `image.png` will
+    /// only be if `bare_paths` is set (because those could easily be names).
+    fn detect(target: &str, bare_paths: bool) -> Option<(Self, &str)> {
+        let target = target.trim();
+        if target.starts_with("http://") || target.starts_with("https://") {
+            Some((Self::Url, target))
+        } else if let Some(path) = target.strip_prefix("file:") {
+            Some((Self::File, path))
+        } else if ["./", "../", "/", "~/"]
+            .iter()
+            .any(|prefix| target.starts_with(prefix))
+            // Other URL schemes and anchors within the same document aren't files
+            || (bare_paths && !target.is_empty() && !target.contains(':') && !target.starts_with('#'))
+        {
+            Some((Self::File, target))
+        } else {
+            None
+        }
+    }
+}
+impl Resource {
+    /// Parses a link in any of the forms a [`Connection`] can take as a link to a resource,
+    /// returning [`None`] if it isn't one. Links that are connections should never be passed to
+    /// this.
+    fn from_str(link: &str, format: Format) -> Option<Self> {
+        let (_, target, style) = parse_link(link, format)?;
+        // Wiki links are always to other notes
+        if matches!(style, LinkStyle::Wiki { .. }) {
+            return None;
+        }
+
+        let config = STARLING_CONFIG.get();
+        let (ty, target) = match target.split_once(':') {
+            Some((ty, rest)) if config.link_types.iter().any(|t| t == ty) => (ty, rest),
+            _ => (config.default_link_type.as_str(), target),
+        };
+        // In Org, anything without a scheme is a search for a heading
+        let bare_paths = format == Format::Markdown && style == LinkStyle::Standard;
+        let (kind, target) = ResourceKind::detect(target, bare_paths)?;
+
+        Some(Self {
+            kind,
+            ty: ty.to_string(),
+            target: target.to_string(),
+        })
+    }
+    /// Gets the key this resource is recorded under in the graph, given the path containing the
+    /// link to it. For URLs, this is the URL itself, and, for files, this is the path to the file
+    /// relative to the root directory (unless it's outside it, in which case it'll be absolute),
+    /// without any search options or anchors.
+    pub fn key(&self, path: &Path) -> String {
+        match self.kind {
+            ResourceKind::Url => self.target.clone(),
+            ResourceKind::File => self.file_path(path).to_string_lossy().to_string(),
+        }
+    }
+    /// Gets the path to the file this resource points to, relative to the root directory,
+    /// if it's a file. See [`Self::key`].
+    pub fn file_path(&self, path: &Path) -> PathBuf {
+        // Strip Org search options (`file.org::*Heading`) and Markdown anchors (`file.md#heading`)
+        let target = self.target.split("::").next().unwrap();
+        let target = target.split(['#', '?']).next().unwrap();
+
+        let full_path = if let Some(rest) = target.strip_prefix("~/") {
+            BaseDirs::new()
+                .map(|dirs| dirs.home_dir().join(rest))
+                .unwrap_or_else(|| PathBuf::from(target))
+        } else {
+            path.parent().unwrap_or(Path::new("")).join(target)
+        };
+        // Resolve `..` and `.` lexically (the file might not exist)
+        let mut normalized = PathBuf::new();
+        for component in full_path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir
+                    if matches!(
+                        normalized.components().next_back(),
+                        Some(Component::Normal(_))
+                    ) =>
+                {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
             }
-            Format::Org => format!("[[{}:{}][{}]]", self.ty, id, self.title),
         }
+
+        normalized
     }
 }
 
@@ -141,36 +477,72 @@ pub struct ParallelConnections {
     /// titles shouldn't be blatantly overriden. If the connection is valid, however, these will
     /// all be updated to match the target node's title.
     variants: Vec<Connection>,
+    /// If these are connections by name, the ID of the node that name was last resolved to. This
+    /// is kept when the connections are invalidated, so that links whose names have been
+    /// rewritten can still be written back.
+    resolved: Option<Uuid>,
+    /// The constraints these connections break, if they're valid. These are worked out whenever
+    /// the connections are validated.
+    violations: Vec<ConstraintViolation>,
 }
 impl ParallelConnections {
     /// Returns whether or not this set of connections is valid.
     pub fn valid(&self) -> bool {
         self.valid
     }
-    pub fn types(&self) -> impl Iterator<Item = &str> {
-        self.variants.iter().map(|conn_data| conn_data.ty.as_str())
+    /// Returns the ID of the node these connections by name were last resolved to, if they're
+    /// connections by name and they've ever been resolved.
+    pub fn resolved(&self) -> Option<Uuid> {
+        self.resolved
+    }
+    /// Returns the constraints these connections broke when they were last validated.
+    pub fn violations(&self) -> &[ConstraintViolation] {
+        &self.violations
+    }
+    /// Gets the set of types across all these connections.
+    pub fn types(&self) -> HashSet<&str> {
+        self.variants.iter().map(|conn| conn.ty.as_str()).collect()
     }
 }
 
 pub struct ConnectionRef<'a> {
     id: Uuid,
     valid: bool,
+    resolved: Option<Uuid>,
+    violations: &'a [ConstraintViolation],
     variants: &'a Vec<Connection>,
 }
 impl<'a> ConnectionRef<'a> {
+    /// Gets the ID this connection is keyed by, which will be a [`name_key`] for connections by
+    /// name.
     pub fn id(&self) -> Uuid {
         self.id
     }
+    /// Gets the ID of the node this connection points to. For connections by name, this is only
+    /// meaningful if the connection is valid.
+    pub fn target(&self) -> Uuid {
+        self.resolved.unwrap_or(self.id)
+    }
     pub fn is_valid(&self) -> bool {
         self.valid
     }
-    pub fn types(&self) -> impl Iterator<Item = &str> {
+    /// Gets the title or alias this connection was written with, if it's a connection by name (or
+    /// by short ID) that hasn't been rewritten.
+    pub fn name(&self) -> Option<&'a str> {
+        self.variants.iter().find_map(|conn| conn.name.as_deref())
+    }
+    pub fn types(&self) -> impl Iterator<Item = &'a str> {
         self.variants.iter().map(|conn_data| conn_data.ty.as_str())
     }
+    pub fn violations(&self) -> &'a [ConstraintViolation] {
+        self.violations
+    }
 }
 pub struct ConnectionMut<'a> {
     id: Uuid,
     valid: &'a mut bool,
+    resolved: &'a mut Option<Uuid>,
+    violations: &'a mut Vec<ConstraintViolation>,
     variants: &'a mut Vec<Connection>, // NOTE: Keep, will modify in queries
 }
 impl<'a> ConnectionMut<'a> {
@@ -180,6 +552,16 @@ impl<'a> ConnectionMut<'a> {
     pub fn set_valid(&mut self, valid: bool) {
         *self.valid = valid;
     }
+    pub fn set_resolved(&mut self, resolved: Option<Uuid>) {
+        *self.resolved = resolved;
+    }
+    pub fn set_violations(&mut self, violations: Vec<ConstraintViolation>) {
+        *self.violations = violations;
+    }
+    /// Gets the set of types across all these connections.
+    pub fn types(&self) -> HashSet<&str> {
+        self.variants.iter().map(|conn| conn.ty.as_str()).collect()
+    }
 }
 
 /// A map of the IDs of nodes being connected to to the details of the connections to those nodes.
@@ -195,9 +577,11 @@ struct ConnectedString {
     inner: Vec<ConnectionToken>,
 }
 impl ConnectedString {
-    /// Parses the provided string into one with connections.
-    fn from_str(target: &str, format: Format) -> (Self, ConnectionMap) {
+    /// Parses the provided string into one with connections. This also returns any links to
+    /// resources found in the string, which are left as regular strings.
+    fn from_str(target: &str, format: Format) -> (Self, ConnectionMap, Vec<Resource>) {
         let mut connections = HashMap::new();
+        let mut resources = Vec::new();
         // Go through the string contents manually to find links (format-specific)
         let mut tokens = Vec::new();
         let mut chars = target.chars().peekable();
@@ -212,7 +596,15 @@ impl ConnectedString {
                 ConnectionLoc::None => {
                     match format {
                         Format::Markdown => {
-                            if c == '[' {
+                            if c == '[' && chars.peek().is_some_and(|next_c| *next_c == '[') {
+                                // We have the start of a wiki link (probably)
+                                conn_loc = ConnectionLoc::Wiki;
+                                curr_match.push(c);
+                                curr_match.push(chars.next().unwrap());
+
+                                tokens.push(ConnectionToken::String(curr_str));
+                                curr_str = String::new();
+                            } else if c == '[' {
                                 // We have the start of some kind of link
                                 conn_loc = ConnectionLoc::Title;
                                 curr_match.push(c);
@@ -240,6 +632,29 @@ impl ConnectedString {
                         }
                     }
                 }
+                // Inside a wiki link, we'll wait for the end, unless this turns out to be a
+                // regular link with a title starting with `[`
+                ConnectionLoc::Wiki => {
+                    if c == ']' && chars.peek().is_some_and(|next_c| *next_c == ']') {
+                        conn_loc = ConnectionLoc::None;
+                        curr_match.push(c);
+                        curr_match.push(chars.next().unwrap());
+
+                        Self::push_connection(
+                            std::mem::take(&mut curr_match),
+                            format,
+                            &mut tokens,
+                            &mut connections,
+                            &mut resources,
+                        );
+                    } else if c == ']' && chars.peek().is_some_and(|next_c| *next_c == '(') {
+                        conn_loc = ConnectionLoc::Target;
+                        curr_match.push(c);
+                        curr_match.push(chars.next().unwrap());
+                    } else {
+                        curr_match.push(c);
+                    }
+                }
                 // Inside a title, we'll look only for the delimiter before the link target
                 // (but we'll store the title itself so we have it for later string
                 // replacement if needed)
@@ -256,6 +671,22 @@ impl ConnectedString {
                         // Push both delimiters to get straight onto the target
                         curr_match.push(c);
                         curr_match.push(chars.next().unwrap());
+                    } else if format == Format::Org
+                        && c == ']'
+                        && chars.peek().is_some_and(|next_c| *next_c == ']')
+                    {
+                        // An Org link without a description ends early
+                        conn_loc = ConnectionLoc::None;
+                        curr_match.push(c);
+                        curr_match.push(chars.next().unwrap());
+
+                        Self::push_connection(
+                            std::mem::take(&mut curr_match),
+                            format,
+                            &mut tokens,
+                            &mut connections,
+                            &mut resources,
+                        );
                     } else {
                         curr_match.push(c);
                     }
@@ -276,24 +707,13 @@ impl ConnectedString {
                         }
 
                         // We have a full connection, parse it
-                        if let Some((id, conn)) = Connection::from_str(&curr_match, format) {
-                            let variants = &mut connections
-                                .entry(id)
-                                .or_insert(ParallelConnections {
-                                    valid: false,
-                                    variants: Vec::new(),
-                                })
-                                .variants;
-                            variants.push(conn);
-                            tokens.push(ConnectionToken::Connection {
-                                id,
-                                idx: variants.len() - 1,
-                            });
-                        } else {
-                            // This isn't actually a connection, add it as a string
-                            tokens.push(ConnectionToken::String(curr_match));
-                        }
-                        curr_match = String::new();
+                        Self::push_connection(
+                            std::mem::take(&mut curr_match),
+                            format,
+                            &mut tokens,
+                            &mut connections,
+                            &mut resources,
+                        );
                     } else {
                         curr_match.push(c);
                     }
@@ -312,7 +732,39 @@ impl ConnectedString {
             tokens.push(ConnectionToken::String(curr_match));
         }
 
-        (Self { inner: tokens }, connections)
+        (Self { inner: tokens }, connections, resources)
+    }
+    /// Parses the given full match as a connection, adding it to the given tokens and map if it is
+    /// one, or adding it as a string if not (recording it as a resource if it's a link to one).
+    fn push_connection(
+        curr_match: String,
+        format: Format,
+        tokens: &mut Vec<ConnectionToken>,
+        connections: &mut ConnectionMap,
+        resources: &mut Vec<Resource>,
+    ) {
+        if let Some((id, conn)) = Connection::from_str(&curr_match, format) {
+            let variants = &mut connections
+                .entry(id)
+                .or_insert(ParallelConnections {
+                    valid: false,
+                    variants: Vec::new(),
+                    resolved: None,
+                    violations: Vec::new(),
+                })
+                .variants;
+            variants.push(conn);
+            tokens.push(ConnectionToken::Connection {
+                id,
+                idx: variants.len() - 1,
+            });
+        } else {
+            // This isn't actually a connection, add it as a string
+            if let Some(resource) = Resource::from_str(&curr_match, format) {
+                resources.push(resource);
+            }
+            tokens.push(ConnectionToken::String(curr_match));
+        }
     }
     /// Converts [`Self`] back into a regular string by stringifying all the connections in it.
     /// This takes in a map for reference.
@@ -323,7 +775,9 @@ impl ConnectedString {
                 // This takes a reference anyway, so no real cost to making this take `&self`
                 ConnectionToken::String(s) => string.push_str(s),
                 ConnectionToken::Connection { id, idx } => {
-                    string.push_str(&connections[id].variants[*idx].to_string(*id, format));
+                    let conns = &connections[id];
+                    let target = conns.resolved.unwrap_or(*id);
+                    string.push_str(&conns.variants[*idx].to_string(target, format));
                 }
             }
         }
@@ -334,6 +788,8 @@ impl ConnectedString {
 /// The parser's position while parsing a connection.
 #[derive(PartialEq, Eq)]
 enum ConnectionLoc {
+    /// Inside a Markdown wiki link (which has no separate title and target delimiters).
+    Wiki,
     Title,
     Target,
     None,
@@ -364,23 +820,31 @@ pub struct SingleConnectedNode {
     /// The names of the indices in which this node is present. We need to keep track of this so
     /// modifications can be made to the overall graph without locking index maps unnecessarily.
     indices: HashSet<String>,
+    /// The keys (from [`name_key`]) of the names other nodes can link to this node by: its title,
+    /// any aliases, its custom ID, and the short form of its ID (from [`short_id_key`]).
+    names: HashSet<Uuid>,
+    /// The resources linked to in the title and body of this node.
+    resources: HashSet<Resource>,
 }
 impl SingleConnectedNode {
     /// Creates a new [`SingleConnectedNode`] from the given strings for a title and body. This
     /// will start with no backlinks.
     ///
-    /// This takes the indices which this node is known to be a part of, simply because this is a
-    /// good structure to record them on.
+    /// This takes the indices which this node is known to be a part of, and the names it can be
+    /// linked to by, simply because this is a good structure to record them on.
     fn new(
         title_str: String,
         body_str: Option<String>,
         position: Vec<usize>,
         indices: HashSet<String>,
+        names: HashSet<Uuid>,
         format: Format,
     ) -> Self {
-        let (title, mut title_map) = ConnectedString::from_str(&title_str, format);
+        let (title, mut title_map, title_resources) = ConnectedString::from_str(&title_str, format);
+        let mut resources = title_resources.into_iter().collect::<HashSet<_>>();
         if let Some(body_str) = body_str {
-            let (mut body, body_map) = ConnectedString::from_str(&body_str, format);
+            let (mut body, body_map, body_resources) = ConnectedString::from_str(&body_str, format);
+            resources.extend(body_resources);
             // We're going to put all entries in the body map into the title map, and where there
             // are overlaps, the variants in the body will be *appended* to those from the title,
             // meaning the variant indices among the body tokens should be incremented by however
@@ -404,6 +868,8 @@ impl SingleConnectedNode {
                     .or_insert_with(|| ParallelConnections {
                         valid: conns.valid,
                         variants: Vec::new(),
+                        resolved: conns.resolved,
+                        violations: Vec::new(),
                     })
                     .variants
                     .extend(conns.variants);
@@ -416,6 +882,8 @@ impl SingleConnectedNode {
                 position,
                 backlinks: HashSet::new(),
                 indices,
+                names,
+                resources,
             }
         } else {
             // Simple case, no map combination needed
@@ -426,6 +894,50 @@ impl SingleConnectedNode {
                 position,
                 backlinks: HashSet::new(),
                 indices,
+                names,
+                resources,
+            }
+        }
+    }
+
+    /// Moves any connections recorded under one of the given keys (which should be name keys) to
+    /// be recorded under the ID of the node the key maps to. This is used to resolve names within
+    /// a single document, after which they're no different to connections by ID (though they'll
+    /// still be written back with their names).
+    fn resolve_local(&mut self, targets: &HashMap<Uuid, Uuid>) {
+        let keys = self
+            .connections
+            .keys()
+            .filter(|key| targets.contains_key(key))
+            .copied()
+            .collect::<Vec<_>>();
+        for key in keys {
+            let target = targets[&key];
+            let conns = self.connections.remove(&key).unwrap();
+            let entry = self
+                .connections
+                .entry(target)
+                .or_insert_with(|| ParallelConnections {
+                    valid: false,
+                    variants: Vec::new(),
+                    resolved: None,
+                    violations: Vec::new(),
+                });
+            // Same as when combining the title and body maps, the variants get appended
+            let increment = entry.variants.len();
+            entry.variants.extend(conns.variants);
+            for token in self
+                .title
+                .inner
+                .iter_mut()
+                .chain(self.body.iter_mut().flat_map(|body| body.inner.iter_mut()))
+            {
+                if let ConnectionToken::Connection { id, idx } = token {
+                    if *id == key {
+                        *id = target;
+                        *idx += increment;
+                    }
+                }
             }
         }
     }
@@ -435,15 +947,25 @@ impl SingleConnectedNode {
         self.connections.iter().map(|(id, conn)| ConnectionRef {
             id: *id,
             valid: conn.valid,
+            resolved: conn.resolved,
+            violations: &conn.violations,
             variants: &conn.variants,
         })
     }
+    /// Gets an iterator of all the valid connections in the title and body of this node that point
+    /// to the node with the given ID, whether directly or by name.
+    pub fn connections_to(&self, target: Uuid) -> impl Iterator<Item = ConnectionRef<'_>> {
+        self.connections()
+            .filter(move |conn| conn.is_valid() && conn.target() == target)
+    }
     /// Gets an iterator of mutable references to all the connections in the title and body of this
     /// node.
     pub fn connections_mut(&mut self) -> impl Iterator<Item = ConnectionMut<'_>> {
         self.connections.iter_mut().map(|(id, conn)| ConnectionMut {
             id: *id,
             valid: &mut conn.valid,
+            resolved: &mut conn.resolved,
+            violations: &mut conn.violations,
             variants: &mut conn.variants,
         })
     }
@@ -451,19 +973,48 @@ impl SingleConnectedNode {
     pub fn invalidate_connection(&mut self, to: Uuid) {
         if let Some(conn) = self.connections.get_mut(&to) {
             conn.valid = false;
+            conn.violations.clear();
         }
     }
-    /// Renders all connections to the node with the given ID valid, and sets their titles to the
-    /// provided string.
-    pub fn validate_connection(&mut self, to: Uuid, to_title: String) {
+    /// Renders all connections with the given ID valid, and sets their titles to the provided
+    /// string. The target is the ID of the node they point to, which will only be different to
+    /// the ID they're recorded under if they're connections by name.
+    ///
+    /// If the user has asked for links by name to be rewritten, they'll be written back with the
+    /// ID of their target from here on (as a ULID, if that's the configured scheme).
+    pub fn validate_connection(&mut self, to: Uuid, target: Uuid, to_title: String) {
         if let Some(conn) = self.connections.get_mut(&to) {
             conn.valid = true;
+            if target != to {
+                conn.resolved = Some(target);
+            }
+            let (rewrite_names, ulids) = {
+                let config = STARLING_CONFIG.get();
+                (
+                    config.rewrite_name_links,
+                    config.id_scheme == IdScheme::Ulid,
+                )
+            };
             // The connection is valid, homogeneise all the titles to the correct one
             for variant in conn.variants.iter_mut() {
                 variant.title = to_title.clone();
+                if rewrite_names && variant.name.take().is_some() {
+                    variant.ulid = ulids;
+                }
             }
         }
     }
+    /// Records the constraints the connections with the given ID break.
+    pub fn set_violations(&mut self, to: Uuid, violations: Vec<ConstraintViolation>) {
+        if let Some(conn) = self.connections.get_mut(&to) {
+            conn.violations = violations;
+        }
+    }
+    /// Returns whether or not this node has any connections with the given ID (regardless of
+    /// whether or not they're valid).
+    pub fn has_connection(&self, to: &Uuid) -> bool {
+        self.connections.contains_key(to)
+    }
     /// Gets an iterator of the IDs of the nodes which link *to* this node.
     pub fn backlinks(&self) -> impl Iterator<Item = &Uuid> {
         self.backlinks.iter()
@@ -492,6 +1043,12 @@ impl SingleConnectedNode {
     pub fn indices(&self) -> &HashSet<String> {
         &self.indices
     }
+    pub fn names(&self) -> &HashSet<Uuid> {
+        &self.names
+    }
+    pub fn resources(&self) -> &HashSet<Resource> {
+        &self.resources
+    }
 }
 
 /// A [`StarlingNode`] which contains parsed connections in its title and/or body.
@@ -507,19 +1064,29 @@ pub struct ConnectedNode {
 }
 impl ConnectedNode {
     /// Parses the provided node into a connected node by tokenising its title and body (if
-    /// present).
+    /// present). This takes any aliases declared for the root node outside its properties (i.e.
+    /// in the document's attributes), and the IDs which are written as ULIDs.
+    ///
+    /// In Org, links to headings and custom IDs in the same document will be resolved here, and
+    /// recorded as connections by ID to the nodes they point to.
     fn from_node(
         mut node: StarlingNode,
+        root_aliases: Vec<String>,
+        ulid_ids: &HashSet<Uuid>,
         index_checkers: &Vec<(IndexCriteria, String)>,
         format: Format,
     ) -> Self {
         // Parse through all the nodes recursively (recording the positions for later indexing)
+        #[allow(clippy::too_many_arguments)]
         fn tokenise_tree(
             node: &mut StarlingNode,
             format: Format,
             nodes: &mut HashMap<Uuid, SingleConnectedNode>,
             position: Vec<usize>,
+            mut aliases: Vec<String>,
+            ulid_ids: &HashSet<Uuid>,
             index_checkers: &Vec<(IndexCriteria, String)>,
+            searches: &mut HashMap<Uuid, Vec<Uuid>>,
         ) {
             // Check which indices this node is a part of (before we extract its title and body)
             let indices = index_checkers
@@ -532,6 +1099,30 @@ impl ConnectedNode {
                     }
                 })
                 .collect();
+            // Work out what this node can be linked to by (aliases in the properties are
+            // comma-separated)
+            if let Some(property) = node.properties.get("ALIASES") {
+                aliases.extend(property.split(',').map(|alias| alias.to_string()));
+            }
+            // Custom IDs can be linked to from anywhere, but headings only from the same document
+            let custom_id = node
+                .properties
+                .get("CUSTOM_ID")
+                .map(|custom_id| format!("#{}", custom_id.trim()));
+            let id = *node.properties.id;
+            let short_id = short_id(id, ulid_ids.contains(&id));
+            let names = std::iter::once(node.title.as_str())
+                .chain(aliases.iter().map(|alias| alias.as_str()))
+                .chain(custom_id.as_deref())
+                .filter(|name| !name.trim().is_empty())
+                .map(name_key)
+                .chain(short_id.as_deref().map(short_id_key))
+                .collect();
+            // The root isn't a heading, so it can't be searched for by its title
+            let heading = (!position.is_empty()).then(|| format!("*{}", node.title));
+            for search in heading.into_iter().chain(custom_id) {
+                searches.entry(name_key(&search)).or_default().push(id);
+            }
             // Parse the title and body as connected strings, scrubbing them out of the original
             // `node`
             let connected_node = SingleConnectedNode::new(
@@ -539,20 +1130,53 @@ impl ConnectedNode {
                 node.body.take(),
                 position.clone(),
                 indices,
+                names,
                 format,
             );
-            let id = *node.properties.id;
             nodes.insert(id, connected_node);
 
             // Perfectly safe, we aren't modifying the levels of any children
             for (idx, child) in node.unchecked_mut_children().iter_mut().enumerate() {
                 let mut child_pos = position.clone();
                 child_pos.push(idx);
-                tokenise_tree(child, format, nodes, child_pos, index_checkers);
+                tokenise_tree(
+                    child,
+                    format,
+                    nodes,
+                    child_pos,
+                    Vec::new(),
+                    ulid_ids,
+                    index_checkers,
+                    searches,
+                );
             }
         }
         let mut map = HashMap::new();
-        tokenise_tree(&mut node, format, &mut map, Vec::new(), index_checkers);
+        let mut searches = HashMap::new();
+        tokenise_tree(
+            &mut node,
+            format,
+            &mut map,
+            Vec::new(),
+            root_aliases,
+            ulid_ids,
+            index_checkers,
+            &mut searches,
+        );
+        // Org searches resolve within the document if they can (ambiguous ones are left for the
+        // graph, where custom IDs might still resolve)
+        if format == Format::Org {
+            let targets = searches
+                .into_iter()
+                .filter_map(|(key, ids)| match ids.as_slice() {
+                    [id] => Some((key, *id)),
+                    _ => None,
+                })
+                .collect::<HashMap<_, _>>();
+            for connected_node in map.values_mut() {
+                connected_node.resolve_local(&targets);
+            }
+        }
 
         Self { node, map }
     }
@@ -604,11 +1228,16 @@ impl ConnectedNode {
     pub fn node_mut(&mut self, uuid: &Uuid) -> Option<&mut SingleConnectedNode> {
         self.map.get_mut(uuid)
     }
-    // /// Returns the raw [`StarlingNode`] with the given ID, which will *not* have a title or body.
-    // /// This should be used to access node properties only.
-    // pub fn raw_node(&self, uuid: &Uuid) -> Option<&StarlingNode> {
-    //
-    // }
+    /// Returns the raw [`StarlingNode`] with the given ID, which will *not* have a title or body.
+    /// This should be used to access node properties only.
+    pub fn raw_node(&self, uuid: &Uuid) -> Option<&StarlingNode> {
+        let mut node = &self.node;
+        for idx in self.map.get(uuid)?.position() {
+            node = &node.children()[*idx];
+        }
+
+        Some(node)
+    }
     // /// Returns the stringified title of the node with the given UUID in this [`ConnectedNode`]'s
     // /// tree. This returns [`None`] if there is no node with the given ID in this tree.
     // ///
@@ -669,18 +1298,47 @@ pub struct ConnectedDocument {
     pub root: ConnectedNode,
     /// The raw attributes from the original document
     pub attributes: String,
+    /// The attributes parsed from the raw attributes. These are only used for reading, so they
+    /// don't need to be kept in sync with the raw attributes.
+    pub parsed_attributes: Attributes,
 }
 impl ConnectedDocument {
     /// Parses the provided document into a connected document by tokenising its title and body (if
-    /// present).
+    /// present). This also takes the attributes already parsed from the document, which can
+    /// declare aliases for the root node under an `aliases` key, and the IDs in the document
+    /// which are written as ULIDs (so they can be linked to by the start of those).
     pub fn from_document(
         document: StarlingDocument,
+        parsed_attributes: Attributes,
+        ulid_ids: &HashSet<Uuid>,
         index_checkers: &Vec<(IndexCriteria, String)>,
         format: Format,
     ) -> Self {
+        let root_aliases = match parsed_attributes.get("aliases") {
+            Some(AttributeValue::List(aliases)) => aliases
+                .iter()
+                .filter_map(|alias| match alias {
+                    AttributeValue::String(alias) => Some(alias.clone()),
+                    _ => None,
+                })
+                .collect(),
+            // Org attributes given once will be plain strings, which we treat like properties
+            Some(AttributeValue::String(aliases)) => {
+                aliases.split(',').map(|alias| alias.to_string()).collect()
+            }
+            _ => Vec::new(),
+        };
+
         Self {
-            root: ConnectedNode::from_node(document.root, index_checkers, format),
+            root: ConnectedNode::from_node(
+                document.root,
+                root_aliases,
+                ulid_ids,
+                index_checkers,
+                format,
+            ),
             attributes: document.attributes,
+            parsed_attributes,
         }
     }
     /// Converts [`Self`] back into a regular document by stringifying all the connections in it.
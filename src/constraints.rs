@@ -0,0 +1,115 @@
+use crate::{config::STARLING_CONFIG, graph::node_has_tag, path_node::StarlingNode};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// The links between nodes whose types can't form cycles, mapped from the node making each link,
+/// to the node being linked to, to the types of the links between them. This only records valid
+/// connections.
+pub type AcyclicLinkMap = HashMap<Uuid, HashMap<Uuid, HashSet<String>>>;
+
+/// A valid connection which breaks the constraints on one of its types. These don't stop the
+/// connection from working, they're just reported to the user.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    /// The node making the connection.
+    pub from: Uuid,
+    /// The node being connected to (which, for connections by name, is the node they resolved
+    /// to).
+    pub to: Uuid,
+    /// The type whose constraints were broken.
+    pub ty: String,
+    /// The constraint that was broken.
+    pub kind: ViolationKind,
+}
+/// The constraints a connection can break.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// The node making the connection doesn't have any of the required tags.
+    SourceTags,
+    /// The node being connected to doesn't have any of the required tags.
+    TargetTags,
+    /// One of the nodes isn't an action item.
+    NotActionItem,
+    /// The connection is part of a cycle of connections of the same type.
+    Cycle,
+}
+
+/// Checks a connection of the given types between the given nodes against the constraints in the
+/// global configuration, returning all the constraints it breaks. Cycles can't be detected from
+/// the two nodes alone, so they're checked separately (see [`in_cycle`]).
+pub fn check_connection<'a>(
+    types: impl Iterator<Item = &'a str>,
+    from: (Uuid, &StarlingNode),
+    to: (Uuid, &StarlingNode),
+) -> Vec<ConstraintViolation> {
+    let config = STARLING_CONFIG.get();
+    // A connection can have the same type more than once
+    let types = types.collect::<HashSet<_>>();
+    let has_any_tag = |node: &StarlingNode, tags: &[String]| {
+        tags.is_empty() || tags.iter().any(|tag| node_has_tag(node, tag))
+    };
+
+    let mut violations = Vec::new();
+    for ty in types {
+        let Some(constraint) = config.link_constraints.get(ty) else {
+            continue;
+        };
+        let mut violation = |kind| {
+            violations.push(ConstraintViolation {
+                from: from.0,
+                to: to.0,
+                ty: ty.to_string(),
+                kind,
+            })
+        };
+
+        if !has_any_tag(from.1, &constraint.source_tags) {
+            violation(ViolationKind::SourceTags);
+        }
+        if !has_any_tag(to.1, &constraint.target_tags) {
+            violation(ViolationKind::TargetTags);
+        }
+        if constraint.action_items && (from.1.keyword.is_none() || to.1.keyword.is_none()) {
+            violation(ViolationKind::NotActionItem);
+        }
+    }
+
+    violations
+}
+
+/// Gets the types of a connection which can't form cycles, according to the global
+/// configuration.
+pub fn acyclic_types<'a>(types: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    let config = STARLING_CONFIG.get();
+    types
+        .filter(|ty| config.link_constraints.get(*ty).is_some_and(|c| c.acyclic))
+        .map(|ty| ty.to_string())
+        .collect()
+}
+
+/// Determines whether or not the link of the given type from one node to another is part of a
+/// cycle of links of that type, by searching for a way back from the node being linked to.
+pub fn in_cycle(links: &AcyclicLinkMap, ty: &str, from: Uuid, to: Uuid) -> bool {
+    let mut stack = vec![to];
+    let mut seen = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if id == from {
+            return true;
+        }
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Some(targets) = links.get(&id) {
+            stack.extend(
+                targets
+                    .iter()
+                    .filter(|(_, types)| types.contains(ty))
+                    .map(|(target, _)| *target),
+            );
+        }
+    }
+
+    false
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-use crate::config::STARLING_CONFIG;
+use crate::exclude::ExcludeMatcher;
 
 /// Some kind of filesystem update to a single path.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -128,19 +128,19 @@ impl DebouncedEvents {
         debounced
     }
     /// Creates a [`DebouncedEvents`] object of creation events from all the readable paths in a
-    /// directory. This will skip paths which cannot be read, as well as those in the configured
-    /// exclusion list.
-    pub fn start_from_dir(dir: &Path) -> Self {
-        let exclude = STARLING_CONFIG
-            .get()
-            .exclude_paths
-            .iter()
-            .map(PathBuf::from)
-            .collect::<Vec<_>>();
-
+    /// directory. This will skip paths which cannot be read, as well as those excluded by the
+    /// given matcher (excluded directories won't be descended into at all).
+    pub fn start_from_dir(dir: &Path, exclude: &ExcludeMatcher) -> Self {
         Self {
             inner: WalkDir::new(dir)
                 .into_iter()
+                // Filter according to the *relative* path, so the matcher works the same way it
+                // does for live events
+                .filter_entry(|entry| {
+                    let path = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+                    path.as_os_str().is_empty()
+                        || !exclude.is_excluded(path, entry.file_type().is_dir())
+                })
                 .filter_map(|entry| entry.ok())
                 .map(|entry| {
                     // Be careful to decanonicalize so the paths we use in the graph are correct
@@ -150,8 +150,6 @@ impl DebouncedEvents {
                     event.decanonicalize(dir);
                     (event.path().to_path_buf(), (None, Some(event)))
                 })
-                // Filter according to the *relative* exclusion list after decanonicalization
-                .filter(|(path, _)| !exclude.iter().any(|ex| path.starts_with(ex)))
                 .collect(),
         }
     }
@@ -0,0 +1,353 @@
+/// The number of unchanged lines shown around each change in a unified diff.
+const CONTEXT: usize = 3;
+
+/// A single step in turning one list of lines into another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffOp {
+    /// The line at the first index in the old list is the same as the line at the second index in
+    /// the new list.
+    Equal(usize, usize),
+    /// The line at this index in the old list was removed.
+    Delete(usize),
+    /// The line at this index in the new list was added.
+    Insert(usize),
+}
+
+/// Works out the steps to turn the old lines into the new ones, keeping as many lines unchanged
+/// as possible.
+///
+/// This uses the linear-space variant of Myers' algorithm (as `diff` and `git diff` do), so it
+/// takes time proportional to the size of the inputs multiplied by the number of differences
+/// between them, which is fast for the small changes Starling usually makes, and never needs more
+/// than linear memory, however large the files are.
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let max_d = (old.len() + new.len()).div_ceil(2);
+    let mut forward = Diagonals::new(max_d);
+    let mut backward = Diagonals::new(max_d);
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+    diff_box(
+        old,
+        new,
+        Area {
+            left: 0,
+            top: 0,
+            right: old.len(),
+            bottom: new.len(),
+        },
+        &mut forward,
+        &mut backward,
+        &mut ops,
+    );
+
+    ops
+}
+
+/// A rectangle in the edit graph between the old lines (horizontally) and the new lines
+/// (vertically), going from the old and new lines at its top-left to just before the ones at its
+/// bottom-right.
+#[derive(Clone, Copy)]
+struct Area {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+/// The furthest point reached on each diagonal of the edit graph by one of the searches in
+/// Myers' algorithm, indexed by diagonal (which can be negative).
+struct Diagonals {
+    offset: isize,
+    furthest: Vec<isize>,
+}
+impl Diagonals {
+    /// Creates storage for searches of up to the given number of differences.
+    fn new(max_d: usize) -> Self {
+        Self {
+            offset: max_d as isize + 1,
+            furthest: vec![0; 2 * max_d + 3],
+        }
+    }
+}
+impl std::ops::Index<isize> for Diagonals {
+    type Output = isize;
+
+    fn index(&self, k: isize) -> &isize {
+        &self.furthest[(k + self.offset) as usize]
+    }
+}
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut isize {
+        &mut self.furthest[(k + self.offset) as usize]
+    }
+}
+
+/// Adds the steps to turn the old lines in the given box into the new ones in it to the given
+/// list, by splitting it around a difference in the middle of the shortest path through it, and
+/// working on either side of that recursively.
+fn diff_box(
+    old: &[&str],
+    new: &[&str],
+    mut area: Area,
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+    ops: &mut Vec<DiffOp>,
+) {
+    // Lines in common at the start and end can be dealt with without searching
+    while area.left < area.right && area.top < area.bottom && old[area.left] == new[area.top] {
+        ops.push(DiffOp::Equal(area.left, area.top));
+        area.left += 1;
+        area.top += 1;
+    }
+    let mut suffix = 0;
+    while area.right > area.left
+        && area.bottom > area.top
+        && old[area.right - 1] == new[area.bottom - 1]
+    {
+        area.right -= 1;
+        area.bottom -= 1;
+        suffix += 1;
+    }
+
+    if area.left == area.right {
+        ops.extend((area.top..area.bottom).map(DiffOp::Insert));
+    } else if area.top == area.bottom {
+        ops.extend((area.left..area.right).map(DiffOp::Delete));
+    } else {
+        // There are at least two differences left (otherwise one side would be empty now), so
+        // each of these parts is smaller than the whole
+        let (start, end) = middle_snake(old, new, area, forward, backward);
+        for part in [
+            Area {
+                right: start.0,
+                bottom: start.1,
+                ..area
+            },
+            Area {
+                left: start.0,
+                top: start.1,
+                right: end.0,
+                bottom: end.1,
+            },
+            Area {
+                left: end.0,
+                top: end.1,
+                ..area
+            },
+        ] {
+            diff_box(old, new, part, forward, backward, ops);
+        }
+    }
+
+    ops.extend((0..suffix).map(|i| DiffOp::Equal(area.right + i, area.bottom + i)));
+}
+
+/// Finds a single difference (and the unchanged lines before or after it) in the middle of the
+/// shortest path through the given box, by searching forward from its top-left and backward from
+/// its bottom-right at the same time until the searches overlap. This returns the points at the
+/// start and end of that part of the path.
+///
+/// The forward search records the furthest `x` on each diagonal `k = x - y`, and the backward
+/// search records the furthest `y` on each diagonal `c = k - delta`, where `delta` is the
+/// diagonal the bottom-right corner is on.
+fn middle_snake(
+    old: &[&str],
+    new: &[&str],
+    area: Area,
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+) -> ((usize, usize), (usize, usize)) {
+    let (left, top, right, bottom) = (
+        area.left as isize,
+        area.top as isize,
+        area.right as isize,
+        area.bottom as isize,
+    );
+    let delta = (right - left) - (bottom - top);
+    let max_d = ((right - left) + (bottom - top) + 1) / 2;
+    let point = |x: isize, y: isize| (x as usize, y as usize);
+
+    forward[1] = left;
+    backward[1] = bottom;
+    for d in 0..=max_d {
+        for k in (-d..=d).rev().step_by(2) {
+            let (px, mut x) = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                (forward[k + 1], forward[k + 1])
+            } else {
+                (forward[k - 1], forward[k - 1] + 1)
+            };
+            let mut y = top + (x - left) - k;
+            let py = if d == 0 || x != px { y } else { y - 1 };
+            while x < right && y < bottom && y >= top && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[k] = x;
+
+            let c = k - delta;
+            if delta % 2 != 0 && (-(d - 1)..=d - 1).contains(&c) && y >= backward[c] {
+                return (point(px, py), point(x, y));
+            }
+        }
+        for c in (-d..=d).rev().step_by(2) {
+            let (py, mut y) = if c == -d || (c != d && backward[c - 1] > backward[c + 1]) {
+                (backward[c + 1], backward[c + 1])
+            } else {
+                (backward[c - 1], backward[c - 1] - 1)
+            };
+            let k = c + delta;
+            let mut x = left + (y - top) + k;
+            let px = if d == 0 || y != py { x } else { x + 1 };
+            while x > left && y > top && x <= right && old[x as usize - 1] == new[y as usize - 1] {
+                x -= 1;
+                y -= 1;
+            }
+            backward[c] = y;
+
+            if delta % 2 == 0 && (-d..=d).contains(&k) && x <= forward[k] {
+                return (point(x, y), point(px, py));
+            }
+        }
+    }
+
+    // The searches always meet by the time they've each covered half the differences
+    unreachable!("forward and backward searches in diff didn't overlap")
+}
+
+/// Creates a unified diff (as `diff -u` or `git diff` would show) from the old contents to the new
+/// contents, labelling them with the given names. If there are no differences, this will be
+/// empty.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    // The number of old and new lines before each step, so we know where each hunk starts
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            DiffOp::Equal(..) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            DiffOp::Delete(_) => old_pos += 1,
+            DiffOp::Insert(_) => new_pos += 1,
+        }
+    }
+
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(..)))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {old_name}\n+++ {new_name}\n");
+    let mut change_idx = 0;
+    while change_idx < changes.len() {
+        // Changes close enough together that their context would overlap go in the same hunk
+        let first = changes[change_idx];
+        let mut last = first;
+        change_idx += 1;
+        while change_idx < changes.len() && changes[change_idx] - last <= 2 * CONTEXT + 1 {
+            last = changes[change_idx];
+            change_idx += 1;
+        }
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let old_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Delete(_)))
+            .count();
+        // Empty ranges are given by the line before them
+        let (old_start, new_start) = positions[start];
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + (old_count > 0) as usize,
+            old_count,
+            new_start + (new_count > 0) as usize,
+            new_count
+        ));
+        for op in hunk {
+            let (marker, line) = match op {
+                DiffOp::Equal(i, _) => (' ', old_lines[*i]),
+                DiffOp::Delete(i) => ('-', old_lines[*i]),
+                DiffOp::Insert(j) => ('+', new_lines[*j]),
+            };
+            diff.push(marker);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    diff
+}
+
+/// Merges the changes made to the base contents in two different versions of them, line by line,
+/// as `diff3` or `git merge-file` would. If both versions changed the same part of the base in
+/// different ways, they conflict, and this will return [`None`].
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> Option<String> {
+    let base_lines = base.split_inclusive('\n').collect::<Vec<_>>();
+    let our_lines = ours.split_inclusive('\n').collect::<Vec<_>>();
+    let their_lines = theirs.split_inclusive('\n').collect::<Vec<_>>();
+    let our_matches = base_matches(&base_lines, &our_lines);
+    let their_matches = base_matches(&base_lines, &their_lines);
+
+    // Go through the base lines that both versions left alone, and between each of them take
+    // whichever version changed the lines in between (if only one did)
+    let mut merged = String::new();
+    let (mut base_idx, mut our_idx, mut their_idx) = (0, 0, 0);
+    loop {
+        let stable = (base_idx..base_lines.len())
+            .find_map(|idx| Some((idx, our_matches[idx]?, their_matches[idx]?)));
+        let (base_end, our_end, their_end) =
+            stable.unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+
+        let base_chunk = &base_lines[base_idx..base_end];
+        let our_chunk = &our_lines[our_idx..our_end];
+        let their_chunk = &their_lines[their_idx..their_end];
+        let chunk = if our_chunk == base_chunk {
+            their_chunk
+        } else if their_chunk == base_chunk || their_chunk == our_chunk {
+            our_chunk
+        } else {
+            return None;
+        };
+        merged.extend(chunk.iter().copied());
+
+        match stable {
+            Some(_) => {
+                merged.push_str(base_lines[base_end]);
+                base_idx = base_end + 1;
+                our_idx = our_end + 1;
+                their_idx = their_end + 1;
+            }
+            None => break,
+        }
+    }
+
+    Some(merged)
+}
+
+/// Works out which line in the new lines each of the old lines was kept as, if any.
+fn base_matches(old: &[&str], new: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; old.len()];
+    for op in diff_lines(old, new) {
+        if let DiffOp::Equal(i, j) = op {
+            matches[i] = Some(j);
+        }
+    }
+    matches
+}
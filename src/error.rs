@@ -1,3 +1,6 @@
+use crate::config::TitleFallback;
+use fmterr::fmterr;
+use serde::Serialize;
 use std::path::PathBuf;
 use thiserror::Error;
 use uuid::Uuid;
@@ -13,6 +16,8 @@ pub enum Error {
     Notify(#[from] notify::Error),
     #[error("please provide a directory for Starling to track")]
     NoDir,
+    #[error("unknown flag '{flag}' (the only flag is `--dry-run`)")]
+    UnknownFlag { flag: String },
     #[error("failed to bind listener on {host}:{port}")]
     ListenFailed {
         host: String,
@@ -60,6 +65,22 @@ pub enum ConfigParseError {
     },
     #[error("cannot have the empty string as a valid link type (this will be handled as the default case automatically)")]
     EmptyLinkType,
+    #[error("cannot declare an inverse for link type '{ty}', which isn't in `link_types`")]
+    UnknownInverseLinkType { ty: String },
+    #[error("cannot declare constraints for link type '{ty}', which isn't in `link_types`")]
+    UnknownConstrainedLinkType { ty: String },
+    #[error("cannot declare a property schema for tag '{tag}', which isn't in `tags`")]
+    UnknownSchemaTag { tag: String },
+    #[error("cannot declare an alias for tag '{tag}', which isn't allowed by `tags`")]
+    UnknownAliasedTag { tag: String },
+    #[error(
+        "link type '{ty}' has inverse '{inverse}', but the inverse of '{inverse}' is '{other}'"
+    )]
+    InconsistentInverseLinkType {
+        ty: String,
+        inverse: String,
+        other: String,
+    },
     #[error("{path:?} is not a valid directory (please create it)")]
     InvalidLogDir { path: PathBuf },
     #[error("could not retrieve default logging path from operating system, please set `log_directory` manually")]
@@ -70,6 +91,28 @@ pub enum ConfigParseError {
         #[source]
         err: std::io::Error,
     },
+    #[error("could not retrieve default backup path from operating system, please set `backup_directory` manually")]
+    NoDefaultBackupDir,
+    #[error("failed to create backup directory {path:?}")]
+    CreateBackupDirFailed {
+        path: PathBuf,
+        #[source]
+        err: std::io::Error,
+    },
+    #[error("could not retrieve default journal path from operating system, please set `journal_directory` manually")]
+    NoDefaultJournalDir,
+    #[error("failed to create journal directory {path:?}")]
+    CreateJournalDirFailed {
+        path: PathBuf,
+        #[source]
+        err: std::io::Error,
+    },
+    #[error("invalid exclusion pattern '{pattern}'")]
+    InvalidExcludePattern {
+        pattern: String,
+        #[source]
+        err: ignore::Error,
+    },
 }
 
 /// Errors that can occur while parsing a single vertex in isolation.
@@ -85,21 +128,310 @@ pub enum PathParseError {
     DocumentParseFailed {
         path: PathBuf,
         format: orgish::Format,
+        /// The first part of the document that couldn't be parsed, if it could be narrowed down.
+        span: Option<Span>,
         #[source]
         err: orgish::error::ParseError,
     },
-    #[error("found markdown vertex at {path:?} with non-yaml frontmatter (not yet supported!)")]
-    FrontmatterNotYaml { path: PathBuf },
-    #[error("failed to parse frontmatter for markdown vertex at {path:?}: expected yaml with string `title` and array of strings `tags`")]
+    #[error("found markdown vertex at {path:?} with frontmatter in an unsupported style (expected `---` yaml, `+++` toml, or `;;;`/`{{}}` json)")]
+    UnknownFrontmatter { path: PathBuf, span: Option<Span> },
+    #[error("failed to parse frontmatter for markdown vertex at {path:?}: expected optional string `title` and array of strings `tags`")]
     InvalidFrontmatter {
         path: PathBuf,
+        span: Option<Span>,
+        // Boxed to keep this error small
         #[source]
-        err: serde_yaml::Error,
+        err: Box<FrontmatterError>,
     },
-    #[error("failed to parse attributes for org vertex at {path:?}: no title found")]
-    OrgNoTitle { path: PathBuf },
+    #[error("no title found for vertex at {path:?}, and none could be inferred")]
+    NoTitle { path: PathBuf },
     #[error("found unknown tag '{tag}' in {path:?}, all tags must be specified in global config")]
-    InvalidTag { path: PathBuf, tag: String },
+    InvalidTag {
+        path: PathBuf,
+        tag: String,
+        span: Option<Span>,
+    },
     #[error("the unique id '{id}' appears more than once in {path:?}")]
-    InternalDuplicateId { path: PathBuf, id: Uuid },
+    InternalDuplicateId {
+        path: PathBuf,
+        id: Uuid,
+        span: Option<Span>,
+    },
+}
+impl PathParseError {
+    /// Gets the part of the path this error is about, if it's about a particular part and that
+    /// could be worked out.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::DocumentParseFailed { span, .. }
+            | Self::UnknownFrontmatter { span, .. }
+            | Self::InvalidFrontmatter { span, .. }
+            | Self::InvalidTag { span, .. }
+            | Self::InternalDuplicateId { span, .. } => *span,
+            Self::ReadFailed { .. } | Self::NoTitle { .. } => None,
+        }
+    }
+    /// Converts this error into a [`Diagnostic`] which can be sent to clients.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            id: None,
+            severity: Severity::Error,
+            message: fmterr(self),
+            span: self.span(),
+        }
+    }
+    /// Gets a stable code for the kind of this error, which clients can branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ReadFailed { .. } => "read_failed",
+            Self::DocumentParseFailed { .. } => "document_parse_failed",
+            Self::UnknownFrontmatter { .. } => "unknown_frontmatter",
+            Self::InvalidFrontmatter { .. } => "invalid_frontmatter",
+            Self::NoTitle { .. } => "no_title",
+            Self::InvalidTag { .. } => "invalid_tag",
+            Self::InternalDuplicateId { .. } => "internal_duplicate_id",
+        }
+    }
+    /// Converts this error into an [`ErrorReport`] which can be sent to clients.
+    pub fn report(&self) -> ErrorReport {
+        let path = match self {
+            Self::ReadFailed { path, .. }
+            | Self::DocumentParseFailed { path, .. }
+            | Self::UnknownFrontmatter { path, .. }
+            | Self::InvalidFrontmatter { path, .. }
+            | Self::NoTitle { path }
+            | Self::InvalidTag { path, .. }
+            | Self::InternalDuplicateId { path, .. } => path.clone(),
+        };
+        ErrorReport {
+            path: Some(path),
+            tag: match self {
+                Self::InvalidTag { tag, .. } => Some(tag.clone()),
+                _ => None,
+            },
+            id: match self {
+                Self::InternalDuplicateId { id, .. } => Some(*id),
+                _ => None,
+            },
+            span: self.span(),
+            ..ErrorReport::new(self.code(), self)
+        }
+    }
+}
+
+/// Errors that can be sent back from the server in response to a request.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("no node with id '{id}' is in the graph")]
+    NodeNotFound { id: Uuid },
+    #[error("'{id}' isn't the id of a node, or the start of exactly one node's id")]
+    UnresolvedNodeId { id: String },
+    #[error("no path {path:?} is tracked")]
+    PathNotFound { path: PathBuf },
+    #[error("invalid request: {message}")]
+    BadOptions { message: String },
+    #[error("no conflict with id '{id}' is awaiting resolution")]
+    ConflictNotFound { id: Uuid },
+    #[error("conflict '{id}' can't be resolved that way: {reason}")]
+    InvalidResolution { id: Uuid, reason: String },
+    #[error("no write with id '{id}' is pending")]
+    PendingWriteNotFound { id: Uuid },
+    #[error("no write with id '{id}' is in the journal")]
+    JournalEntryNotFound { id: Uuid },
+    #[error("write '{id}' can't be undone: {reason}")]
+    CannotUndo { id: Uuid, reason: String },
+    #[error("tag '{tag}' isn't pending approval")]
+    TagNotPending { tag: String },
+}
+impl ApiError {
+    /// Gets a stable code for the kind of this error, which clients can branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NodeNotFound { .. } | Self::UnresolvedNodeId { .. } => "node_not_found",
+            Self::PathNotFound { .. } => "path_not_found",
+            Self::BadOptions { .. } => "bad_options",
+            Self::ConflictNotFound { .. } => "conflict_not_found",
+            Self::InvalidResolution { .. } => "invalid_resolution",
+            Self::PendingWriteNotFound { .. } => "pending_write_not_found",
+            Self::JournalEntryNotFound { .. } => "journal_entry_not_found",
+            Self::CannotUndo { .. } => "cannot_undo",
+            Self::TagNotPending { .. } => "tag_not_pending",
+        }
+    }
+    /// Converts this error into an [`ErrorReport`] which can be sent to clients.
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            path: match self {
+                Self::PathNotFound { path } => Some(path.clone()),
+                _ => None,
+            },
+            id: match self {
+                Self::NodeNotFound { id }
+                | Self::ConflictNotFound { id }
+                | Self::InvalidResolution { id, .. }
+                | Self::PendingWriteNotFound { id }
+                | Self::JournalEntryNotFound { id }
+                | Self::CannotUndo { id, .. } => Some(*id),
+                _ => None,
+            },
+            tag: match self {
+                Self::TagNotPending { tag } => Some(tag.clone()),
+                _ => None,
+            },
+            ..ErrorReport::new(self.code(), self)
+        }
+    }
+}
+
+/// A machine-readable description of an error, which can be sent to clients.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    /// A stable code for the kind of error, which clients can branch on.
+    pub code: &'static str,
+    /// A description of the error itself (not including what caused it).
+    pub message: String,
+    /// The path the error is about, if any.
+    pub path: Option<PathBuf>,
+    /// The tag the error is about, if any.
+    pub tag: Option<String>,
+    /// The ID the error is about, if any.
+    pub id: Option<Uuid>,
+    /// The part of the path the error is about, if that could be worked out.
+    pub span: Option<Span>,
+    /// Descriptions of the errors that caused this one, starting with the most immediate cause.
+    pub sources: Vec<String>,
+}
+impl ErrorReport {
+    /// Creates a report with the given code for the given error and its causes, without any
+    /// details about what it's about.
+    fn new(code: &'static str, err: &dyn std::error::Error) -> Self {
+        let mut sources = Vec::new();
+        let mut source = err.source();
+        while let Some(err) = source {
+            sources.push(err.to_string());
+            source = err.source();
+        }
+
+        Self {
+            code,
+            message: err.to_string(),
+            path: None,
+            tag: None,
+            id: None,
+            span: None,
+            sources,
+        }
+    }
+}
+
+/// Non-fatal problems that can occur while parsing a single vertex. Unlike errors, these don't stop
+/// the vertex from being added to the graph.
+#[derive(Error, Debug, Clone)]
+pub enum PathParseWarning {
+    #[error("no title found for vertex at {path:?}, inferred '{title}' from {fallback}")]
+    TitleInferred {
+        path: PathBuf,
+        title: String,
+        fallback: TitleFallback,
+    },
+    #[error("found unknown tag '{tag}' on node {id} in {path:?}, all tags must be specified in global config")]
+    UnknownTag {
+        path: PathBuf,
+        id: Uuid,
+        tag: String,
+        span: Option<Span>,
+    },
+    #[error("the unique id '{id}' appears more than once in {path:?}, so the later node was given the new id '{new_id}'")]
+    DuplicateId {
+        path: PathBuf,
+        id: Uuid,
+        new_id: Uuid,
+        span: Option<Span>,
+    },
+    #[error("failed to parse part of vertex at {path:?} (lines {}-{}), so it was left out of the graph and the vertex won't be written to until it's fixed", span.start.line, span.end.line)]
+    SubtreeSkipped { path: PathBuf, span: Span },
+}
+impl PathParseWarning {
+    /// Gets how serious this warning is. Anything that would have been an error outside lenient
+    /// mode is treated as one here too.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::TitleInferred { .. } => Severity::Warning,
+            Self::UnknownTag { .. } | Self::DuplicateId { .. } | Self::SubtreeSkipped { .. } => {
+                Severity::Error
+            }
+        }
+    }
+    /// Gets the ID of the node this warning is about, if it's about a particular node.
+    pub fn node(&self) -> Option<Uuid> {
+        match self {
+            Self::TitleInferred { .. } | Self::SubtreeSkipped { .. } => None,
+            Self::UnknownTag { id, .. } => Some(*id),
+            Self::DuplicateId { new_id, .. } => Some(*new_id),
+        }
+    }
+    /// Gets the part of the path this warning is about, if it's about a particular part and that
+    /// could be worked out.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::TitleInferred { .. } => None,
+            Self::UnknownTag { span, .. } | Self::DuplicateId { span, .. } => *span,
+            Self::SubtreeSkipped { span, .. } => Some(*span),
+        }
+    }
+    /// Converts this warning into a [`Diagnostic`] which can be sent to clients.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            id: self.node(),
+            severity: self.severity(),
+            message: self.to_string(),
+            span: self.span(),
+        }
+    }
+}
+
+/// How serious a diagnostic about a path is.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Something the user might not have intended, but which Starling can handle fine.
+    Warning,
+    /// Something which is definitely wrong. Unless this is the error that stopped the path from
+    /// being parsed, it didn't stop the path from being part of the graph.
+    Error,
+}
+/// A problem found while parsing a path, stringified for clients.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The node the problem is with, if it's with a particular node.
+    pub id: Option<Uuid>,
+    pub severity: Severity,
+    pub message: String,
+    /// The part of the path the problem is in, if that could be worked out.
+    pub span: Option<Span>,
+}
+
+/// A position in a file, by line and by character within that line (both starting from 1).
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+/// A range in a file, from the start position up to (but not including) the end position.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Errors that can occur while deserializing the frontmatter of a Markdown vertex, in any of the
+/// supported styles.
+#[derive(Error, Debug)]
+pub enum FrontmatterError {
+    #[error("invalid yaml frontmatter")]
+    Yaml(#[source] serde_yaml::Error),
+    #[error("invalid toml frontmatter")]
+    Toml(#[source] toml::de::Error),
+    #[error("invalid json frontmatter")]
+    Json(#[source] serde_json::Error),
 }
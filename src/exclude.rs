@@ -0,0 +1,171 @@
+use crate::{conflict_store::is_sidecar, error::ConfigParseError, writer::is_temp_file};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use parking_lot::RwLock;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+/// The name of the Starling-specific ignore file, which can be placed in any directory.
+static STARLING_IGNORE_FILE: &str = ".starlingignore";
+/// The name of Git's ignore file, which can be placed in any directory.
+static GIT_IGNORE_FILE: &str = ".gitignore";
+
+/// A matcher for paths that Starling should never track. This combines the gitignore-style
+/// patterns in the user's configuration with any `.starlingignore` files (and, if configured,
+/// `.gitignore` files) found anywhere in the tracked directory.
+///
+/// All paths given to this should be relative to the root directory, as they are in the graph.
+/// This is shared between the initial scan, the construction of path patches, and the handling of
+/// live filesystem events, so ignored paths never enter the graph, no matter how they got there.
+pub struct ExcludeMatcher {
+    /// The root directory being tracked, which *must* be canonicalized.
+    dir: PathBuf,
+    /// Rules from the exclusion patterns in the user's configuration, which are rooted at the
+    /// tracked directory.
+    config_rules: Gitignore,
+    /// Rules from ignore files anywhere in the tracked directory, keyed by the relative path of
+    /// the ignore file they came from. Each of these is rooted at the directory its file is in.
+    ///
+    /// These can be reloaded whenever an ignore file changes.
+    file_rules: RwLock<HashMap<PathBuf, Gitignore>>,
+    /// Whether or not `.gitignore` files should be respected.
+    respect_gitignore: bool,
+}
+impl ExcludeMatcher {
+    /// Creates a new matcher for the given directory (which *must* be canonicalized) from the
+    /// given gitignore-style patterns. This will scan the directory for ignore files to load.
+    pub fn new(
+        dir: &Path,
+        patterns: &[String],
+        respect_gitignore: bool,
+    ) -> Result<Self, ConfigParseError> {
+        let mut builder = GitignoreBuilder::new(dir);
+        for pattern in patterns {
+            builder.add_line(None, pattern).map_err(|err| {
+                ConfigParseError::InvalidExcludePattern {
+                    pattern: pattern.clone(),
+                    err,
+                }
+            })?;
+        }
+        let config_rules =
+            builder
+                .build()
+                .map_err(|err| ConfigParseError::InvalidExcludePattern {
+                    pattern: patterns.join(", "),
+                    err,
+                })?;
+
+        let this = Self {
+            dir: dir.to_path_buf(),
+            config_rules,
+            file_rules: RwLock::new(HashMap::new()),
+            respect_gitignore,
+        };
+
+        // Find all the ignore files in the directory, being careful not to descend into excluded
+        // directories (loading parent ignore files first, so those apply as we go)
+        let mut walker = WalkDir::new(dir).sort_by_file_name().into_iter();
+        while let Some(entry) = walker.next() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let path = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            if entry.file_type().is_dir() {
+                if !path.as_os_str().is_empty() && this.is_excluded(path, true) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+                // Load this directory's ignore files before we look at anything in it
+                for name in [GIT_IGNORE_FILE, STARLING_IGNORE_FILE] {
+                    let ignore_path = path.join(name);
+                    if this.is_ignore_file(&ignore_path) && dir.join(&ignore_path).is_file() {
+                        this.reload(&ignore_path);
+                    }
+                }
+            }
+        }
+
+        Ok(this)
+    }
+    /// Returns whether or not the given path (relative to the root directory) should be excluded
+    /// from the graph. Deeper ignore files take precedence over shallower ones, as in Git, and the
+    /// patterns in the user's configuration take precedence over everything.
+    ///
+    /// Git's own directory, conflict sidecar files, and the temporary files writes are made to are
+    /// always excluded. Paths outside the root
+    /// directory are never excluded (they can't be matched against anything).
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if path.is_absolute() {
+            return false;
+        }
+        if path.components().any(|c| c.as_os_str() == ".git")
+            || is_sidecar(path)
+            || is_temp_file(path)
+        {
+            return true;
+        }
+
+        let full_path = self.dir.join(path);
+        match self
+            .config_rules
+            .matched_path_or_any_parents(&full_path, is_dir)
+        {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+
+        let file_rules = self.file_rules.read();
+        for ancestor in path.ancestors().skip(1) {
+            // `.starlingignore` files take precedence over `.gitignore` files in the same
+            // directory
+            for name in [STARLING_IGNORE_FILE, GIT_IGNORE_FILE] {
+                if let Some(rules) = file_rules.get(&ancestor.join(name)) {
+                    match rules.matched_path_or_any_parents(&full_path, is_dir) {
+                        Match::Ignore(_) => return true,
+                        Match::Whitelist(_) => return false,
+                        Match::None => {}
+                    }
+                }
+            }
+        }
+
+        false
+    }
+    /// Returns whether or not the given path is an ignore file this matcher should load rules
+    /// from.
+    pub fn is_ignore_file(&self, path: &Path) -> bool {
+        match path.file_name() {
+            Some(name) if name == STARLING_IGNORE_FILE => true,
+            Some(name) if name == GIT_IGNORE_FILE => self.respect_gitignore,
+            _ => false,
+        }
+    }
+    /// Reloads the rules from the ignore file at the given path (relative to the root directory).
+    /// If the file no longer exists, its rules will be removed. Invalid lines in ignore files are
+    /// skipped with a warning, as Git does.
+    ///
+    /// This only affects which paths are excluded from here on; paths already in the graph will
+    /// stay there until they're next changed.
+    pub fn reload(&self, path: &Path) {
+        let full_path = self.dir.join(path);
+        let mut file_rules = self.file_rules.write();
+        if full_path.is_file() {
+            let (rules, err) = Gitignore::new(&full_path);
+            if let Some(err) = err {
+                warn!("failed to parse some rules in ignore file {path:?}: {err}");
+            }
+            file_rules.insert(path.to_path_buf(), rules);
+            info!("loaded ignore rules from {path:?}");
+        } else if file_rules.remove(path).is_some() {
+            info!("removed ignore rules from {path:?}");
+        }
+    }
+}
@@ -1,20 +1,32 @@
 use crate::{
     config::STARLING_CONFIG,
-    conflict_detector::{Conflict, ConflictDetector, Write},
+    conflict_detector::{Conflict, ConflictDetector, Write, WriteReason, WriteSource},
+    conflict_store::{is_sidecar, sidecar_path},
     debouncer::{DebouncedEvents, Event},
+    diff::merge3,
+    exclude::ExcludeMatcher,
     graph::Graph,
     patch::GraphPatch,
+    writer,
 };
 use crossbeam_queue::SegQueue;
 use futures::{future::join_all, Future};
 use notify::{
-    event::{CreateKind, ModifyKind},
+    event::{CreateKind, ModifyKind, RenameMode},
     EventKind as NotifyEvent, RecommendedWatcher, RecursiveMode, Watcher,
 };
-use std::{collections::HashSet, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{select, sync::mpsc};
 use tracing::{debug, error, info, span, warn, Level};
 
+/// How long to wait for the event from one of our own writes before assuming we've missed it.
+const SELF_WRITE_EXPIRY: Duration = Duration::from_secs(10);
+
 /// The engine that powers Starling's filesystem interactions. This is responsible for monitoring
 /// and debouncing filesystem changes, developing them into patches, and actioning them within the
 /// main [`Graph`]. This is also responsible for handling writes, be they from API-triggered
@@ -34,12 +46,16 @@ pub struct FsEngine {
     /// A number of millseconds after which, if there have been no filesystem events, the evnets
     /// received will be actioned.
     debounce_duration: u64,
+    /// The matcher for paths which should never enter the graph. This is shared with the patch
+    /// tasks, and reloaded whenever an ignore file changes.
+    exclude: Arc<ExcludeMatcher>,
     watcher: Option<RecommendedWatcher>,
 }
 impl FsEngine {
     /// Create a new filesystem engine to handle the given graph, which should already have been
-    /// instantiated. This also takes some initial corrective writes.
-    pub fn new(graph: Arc<Graph>, writes: Vec<Write>) -> Self {
+    /// instantiated. This also takes some initial corrective writes, and the matcher for excluded
+    /// paths the graph was created with.
+    pub fn new(graph: Arc<Graph>, writes: Vec<Write>, exclude: Arc<ExcludeMatcher>) -> Self {
         // Create our conflict detector and register the initial writes as an update (even though
         // the probability of conflicts is near zero at application start)
         let mut conflict_detector = ConflictDetector::new();
@@ -52,6 +68,7 @@ impl FsEngine {
             debounce_duration: STARLING_CONFIG.get().debounce_duration,
             conflict_detector,
             writes_queue: Arc::new(writes_queue),
+            exclude,
             watcher: None,
         }
     }
@@ -111,7 +128,27 @@ impl FsEngine {
                                     ev.paths[1].clone(),
                                 )))
                             }
-                            // Rename event with only one path, ignore
+                            // Some backends (like FSEvents on macOS) report each end of a
+                            // rename separately without saying which end it is, including when
+                            // we rename our own writes into place, so we work out whether the
+                            // path was moved here or away
+                            ModifyKind::Name(RenameMode::Any) => {
+                                if ev.paths[0].exists() {
+                                    debug!(
+                                        "sent creation event for {:?} (renamed here)",
+                                        ev.paths[0]
+                                    );
+                                    tx.send(Some(Event::Create(ev.paths[0].clone())))
+                                } else {
+                                    debug!(
+                                        "sent deletion event for {:?} (renamed away)",
+                                        ev.paths[0]
+                                    );
+                                    tx.send(Some(Event::Delete(ev.paths[0].clone())))
+                                }
+                            }
+                            // Otherwise, this is one end of a rename we'll get both ends of
+                            // together in a moment, ignore
                             ModifyKind::Name(_) => {
                                 debug!("received single-path rename event, ignoring");
                                 Ok(())
@@ -133,21 +170,6 @@ impl FsEngine {
         // If watching the directory fails, we'll error before the future so the user can handle
         // this immediately
         watcher.watch(&cwd, RecursiveMode::Recursive)?;
-        // Now unwatch all the exclusion paths
-        for path in &STARLING_CONFIG.get().exclude_paths {
-            // It's fine for the exclusion path not to exist
-            if !cwd.join(path).exists() {
-                continue;
-            }
-            match watcher.unwatch(&cwd.join(path)) {
-                Ok(_) => {}
-                Err(err) => match err.kind {
-                    // Sometimes we get here with single-file exclusions...
-                    notify::ErrorKind::WatchNotFound => {}
-                    _ => return Err(err),
-                },
-            };
-        }
 
         Ok(async move {
             self.watcher = Some(watcher);
@@ -156,8 +178,8 @@ impl FsEngine {
             // send to the graph
             let mut patch_task = None;
             // Set of paths we've just written to do make sure we don't detect our own
-            // modifications to them (infinite loops)
-            let mut self_writes = HashSet::new();
+            // modifications to them (infinite loops), with when we wrote to them
+            let mut self_writes = HashMap::new();
 
             let mut debounced_events = DebouncedEvents::new();
             loop {
@@ -187,8 +209,9 @@ impl FsEngine {
                             let graph = self.graph.clone();
                             let writes_queue = self.writes_queue.clone();
                             let dir = cwd.clone();
+                            let exclude = self.exclude.clone();
                             patch_task = Some(tokio::spawn(async move {
-                                let patch = GraphPatch::from_events(debounced_events_clone, &dir).await;
+                                let patch = GraphPatch::from_events(debounced_events_clone, &dir, &exclude).await;
 
                                 // Hand off the graph processing to another task (it's *not*
                                 // cancel-safe, and there's no need to cancel it, many of these can
@@ -215,42 +238,214 @@ impl FsEngine {
                         // we'll have all the events that have occurred up until *now* and we'll
                         // write these in a moment, so there won't be any more conflicts we can do
                         // anything about.
-                        let mut write_futs = Vec::new();
-                        let mut local_self_writes = HashSet::new();
+                        let mut to_write = Vec::new();
+                        let mut to_merge = Vec::new();
+                        let mut conflicted = Vec::new();
+                        // Undos go through the conflict detector like any other write, but if the
+                        // path has changed since the write being undone, we'll need to merge the
+                        // undo with those changes
+                        for undo in self.graph.journal.take_undos() {
+                            let disk_contents = tokio::fs::read_to_string(cwd.join(&undo.path)).await.ok();
+                            if disk_contents == undo.base {
+                                let patch_idx = self.conflict_detector.register_update();
+                                self.writes_queue.push((vec![undo], patch_idx));
+                            } else {
+                                to_merge.push(Write {
+                                    conflict: Conflict::Simple,
+                                    ..undo
+                                });
+                            }
+                        }
+                        // The same goes for pending writes the user has chosen to apply, which might
+                        // have been computed long before the disk last changed
+                        for pending in self.graph.pending_writes.take_approved() {
+                            let disk_contents = tokio::fs::read_to_string(cwd.join(&pending.path)).await.ok();
+                            let mut reasons = pending.reasons;
+                            reasons.push(WriteReason::PendingWrite);
+                            let write = Write {
+                                path: pending.path,
+                                contents: pending.contents,
+                                source: WriteSource::Other,
+                                conflict: Conflict::None,
+                                base: pending.base,
+                                reasons,
+                            };
+                            if disk_contents == write.base {
+                                let patch_idx = self.conflict_detector.register_update();
+                                self.writes_queue.push((vec![write], patch_idx));
+                            } else {
+                                to_merge.push(Write {
+                                    conflict: Conflict::Simple,
+                                    ..write
+                                });
+                            }
+                        }
                         while let Some((writes, patch_idx)) = self.writes_queue.pop() {
                             let updated_writes =
                                 self.conflict_detector.detect_conflicts(patch_idx, writes);
                             for write in updated_writes {
-                                match write.conflict {
-                                    Conflict::None => {
-                                        let full_path = cwd.join(&write.path);
-                                        write_futs.push(
-                                            tokio::fs::write(full_path.clone(), write.contents)
-                                        );
-                                        info!("wrote to '{:?}'", full_path);
-                                        // Prepare to record that we soon will have written to this
-                                        // path (using the decanonicalized version)
-                                        local_self_writes.insert(write.path);
-                                    },
+                                match &write.conflict {
+                                    Conflict::None => to_write.push(write),
+                                    // The modification in `write.contents` conflicts with the
+                                    // state on the disk, but if we know what it was computed from,
+                                    // we might be able to merge the two
+                                    Conflict::Simple if write.base.is_some() => to_merge.push(write),
                                     Conflict::Simple => {
-                                        // The modification in `write.contents` conflicts with the
-                                        // state on the disk
-                                        error!("conflict in {:?}", write.path);
+                                        warn!("conflict in {:?}, storing for resolution", write.path);
+                                        conflicted.push(write);
                                     }
                                     Conflict::Multi(paths) => {
                                         // The path we want to write to was renamed, recreated, and
                                         // renamed to somewhere else at least once, meaning we
                                         // don't know where to send our modification
-                                        error!("conflict with write to '{:?}', could go to any of {:?}", write.path, paths);
+                                        warn!("conflict with write to '{:?}', could go to any of {:?}, storing for resolution", write.path, paths);
+                                        conflicted.push(write);
                                     }
                                 }
                             }
                         }
+                        // Merge what's on the disk now and what we wanted to write with respect
+                        // to what we computed that from, and send the result back through the
+                        // conflict detector (the disk could change again before it's written)
+                        for write in to_merge {
+                            let disk_contents = tokio::fs::read_to_string(cwd.join(&write.path)).await.ok();
+                            // Merging large files could hold up the loop
+                            let (merged, write, disk_contents) = tokio::task::spawn_blocking(move || {
+                                let merged = disk_contents.as_deref().zip(write.base.as_deref()).and_then(
+                                    |(disk, base)| merge3(base, disk, &write.contents)
+                                );
+                                (merged, write, disk_contents)
+                            })
+                            .await
+                            .unwrap();
+                            match merged {
+                                Some(merged) => {
+                                    info!("merged write to {:?} with changes on disk", write.path);
+                                    let patch_idx = self.conflict_detector.register_update();
+                                    let write = Write {
+                                        contents: merged,
+                                        conflict: Conflict::None,
+                                        base: disk_contents,
+                                        ..write
+                                    };
+                                    self.writes_queue.push((vec![write], patch_idx));
+                                }
+                                // Processing the changes on the disk will produce any writes we
+                                // still need
+                                None if write.source == WriteSource::Filesystem => {
+                                    debug!("couldn't merge write to {:?}, dropping", write.path);
+                                }
+                                None => {
+                                    warn!("conflict in {:?} couldn't be merged, storing for resolution", write.path);
+                                    conflicted.push(write);
+                                }
+                            }
+                        }
+                        // In read-only mode, hold onto everything we would have written until the
+                        // user applies it (except what they've already applied)
+                        let read_only = STARLING_CONFIG.get().read_only;
+                        if read_only {
+                            let (approved, held) = std::mem::take(&mut to_write)
+                                .into_iter()
+                                .partition(|write: &Write| write.reasons.contains(&WriteReason::PendingWrite));
+                            to_write = approved;
+                            for write in held {
+                                let path = write.path.clone();
+                                // Diffing large files could hold up the loop
+                                let graph = self.graph.clone();
+                                let id = tokio::task::spawn_blocking(move || {
+                                    graph.pending_writes.record(write)
+                                })
+                                .await
+                                .unwrap();
+                                info!("holding write to {:?} as pending write {} in read-only mode", path, id);
+                            }
+                        }
+                        // The user has explicitly chosen to make these writes in resolving
+                        // conflicts, so they go ahead regardless of what's on the disk now
+                        to_write.extend(self.graph.conflicts.take_resolved());
+                        // Hold onto the conflicted writes, along with what they conflicted with,
+                        // until the user decides what to do with them (none of them will be
+                        // written below, so the disk contents are what they conflicted with)
+                        // Clear away the sidecars of resolved conflicts first, because new
+                        // conflicts might be about to reuse them
+                        for sidecar in self.graph.conflicts.take_stale_sidecars() {
+                            match tokio::fs::remove_file(cwd.join(&sidecar)).await {
+                                Ok(()) => info!("removed conflict sidecar {:?}", sidecar),
+                                // The user might have deleted it already
+                                Err(err) => debug!("couldn't remove conflict sidecar {:?}: {}", sidecar, err),
+                            }
+                        }
+                        let mut sidecar_futs = Vec::new();
+                        let sidecars = STARLING_CONFIG.get().conflict_sidecars && !read_only;
+                        for write in conflicted {
+                            let disk_contents = tokio::fs::read_to_string(cwd.join(&write.path)).await.ok();
+                            // Users without API-aware editors can see what we wanted to write next
+                            // to the original (we can't do this if we don't know where it should
+                            // go)
+                            let sidecar = (sidecars && write.conflict == Conflict::Simple)
+                                .then(|| sidecar_path(&write.path));
+                            if let Some(sidecar) = sidecar.clone() {
+                                let contents = write.contents.clone();
+                                let cwd = &cwd;
+                                sidecar_futs.push(async move {
+                                    writer::write_file(cwd, &sidecar, contents).await
+                                });
+                            }
+                            let path = write.path.clone();
+                            let id = self.graph.conflicts.record(write, disk_contents, sidecar);
+                            info!("stored conflict {} for write to {:?}", id, path);
+                        }
+
+                        let mut write_futs = Vec::new();
+                        let mut local_self_writes = HashSet::new();
+                        for write in to_write {
+                            // Prepare to record that we soon will have written to this path (using
+                            // the decanonicalized version)
+                            local_self_writes.insert(write.path.clone());
+                            let cwd = &cwd;
+                            write_futs.push(async move {
+                                let res = writer::write_file(cwd, &write.path, write.contents.clone()).await;
+                                (write, res)
+                            });
+                        }
                         // Action all those writes (yes, a conflict could occur during this, but
-                        // there's nothing we can possibly do about that)
-                        join_all(write_futs).await;
+                        // there's nothing we can possibly do about that), and journal the ones
+                        // that went through so they can be undone
+                        for (write, res) in join_all(write_futs).await {
+                            match res {
+                                Ok(previous) => {
+                                    let path = write.path.clone();
+                                    // Persisting the journal touches the disk
+                                    let graph = self.graph.clone();
+                                    let id = tokio::task::spawn_blocking(move || {
+                                        graph.journal.record(
+                                            &write.path,
+                                            previous.as_deref(),
+                                            &write.contents,
+                                            write.source,
+                                            write.reasons,
+                                        )
+                                    })
+                                    .await
+                                    .unwrap();
+                                    info!("wrote to {:?} (journal entry {})", path, id);
+                                }
+                                Err(err) => {
+                                    error!("failed to write to {:?}: {}", write.path, err);
+                                    // We won't see any events for this
+                                    local_self_writes.remove(&write.path);
+                                }
+                            }
+                        }
+                        for res in join_all(sidecar_futs).await {
+                            if let Err(err) = res {
+                                error!("failed to write conflict sidecar: {}", err);
+                            }
+                        }
                         // *Now* record that we've written to all those paths
-                        self_writes.extend(local_self_writes);
+                        let now = Instant::now();
+                        self_writes.extend(local_self_writes.into_iter().map(|path| (path, now)));
                     },
                     res = rx.recv() => {
                         // Receiving an event means any partly or fully developed I/O patches have
@@ -278,16 +473,36 @@ impl FsEngine {
                                 // graph have to be relative, so decanonicalize with respect to our
                                 // directory
                                 event.decanonicalize(&cwd);
+                                // Deleting a conflict sidecar is how users without API-aware
+                                // editors tell us to throw away what we wanted to write
+                                if let Event::Delete(path) | Event::Rename(path, _) = &event {
+                                    if is_sidecar(path) {
+                                        if let Some(id) = self.graph.conflicts.discard_sidecar(path) {
+                                            info!("conflict sidecar {:?} deleted, discarded conflict {}", path, id);
+                                        }
+                                    }
+                                }
+                                // Excluded paths are watched like any other (they can't be
+                                // reliably unwatched), so filter them out here, after picking up
+                                // any changes to the rules themselves
+                                let Some(event) = self.filter_excluded(event, &cwd) else {
+                                    continue;
+                                };
                                 // Debounce in real time because it's fast and ensures we have a
                                 // map of paths to events. Be sure *not* to record this if this was
                                 // a path we just wrote to though, to prevent infinite loops.
-                                if self_writes.remove(event.path()) {
-                                    // On modification (what we expect), block the event; otherwise
-                                    // allow the event through (but we really should have seen a
-                                    // modification first, so a bit weird)
+                                // If we haven't seen an event for a write by now, the backend
+                                // must have reported it in a way we couldn't recognise, so stop
+                                // waiting for it rather than swallowing a real change later
+                                self_writes.retain(|_, written| written.elapsed() < SELF_WRITE_EXPIRY);
+                                if self_writes.remove(event.path()).is_some() {
+                                    // On modification or creation (what we expect, because writes
+                                    // are renamed into place from excluded temporary files),
+                                    // block the event; otherwise allow the event through (but we
+                                    // really should have seen one of those first, so a bit weird)
                                     match event {
-                                        Event::Modify(_) => {
-                                            info!("saw self-write modification on {:?}, skipping", event.path());
+                                        Event::Modify(_) | Event::Create(_) => {
+                                            info!("saw self-write on {:?}, skipping", event.path());
                                             continue;
                                         },
                                         _ => warn!(
@@ -313,4 +528,43 @@ impl FsEngine {
             }
         })
     }
+    /// Filters the given (decanonicalized) event according to the exclusion rules, reloading those
+    /// rules first if the event touches an ignore file. This will return [`None`] if the event
+    /// should be skipped entirely.
+    ///
+    /// Renames across the boundary of excluded paths become creations or deletions, and deletions
+    /// are always let through, because a path might have been tracked before the rules changed to
+    /// exclude it (deleting an untracked path is a no-op for the graph).
+    fn filter_excluded(&self, event: Event, cwd: &Path) -> Option<Event> {
+        let is_excluded = |path: &Path| self.exclude.is_excluded(path, cwd.join(path).is_dir());
+        let paths = match &event {
+            Event::Rename(from, to) => vec![from, to],
+            Event::Create(p) | Event::Delete(p) | Event::Modify(p) => vec![p],
+        };
+        for path in paths {
+            if self.exclude.is_ignore_file(path) {
+                self.exclude.reload(path);
+            }
+        }
+
+        match event {
+            Event::Create(path) | Event::Modify(path) if is_excluded(&path) => {
+                debug!("skipping event on excluded path {:?}", path);
+                None
+            }
+            Event::Rename(from, to) => match (is_excluded(&from), is_excluded(&to)) {
+                (true, true) => {
+                    debug!(
+                        "skipping rename between excluded paths {:?} -> {:?}",
+                        from, to
+                    );
+                    None
+                }
+                (false, true) => Some(Event::Delete(from)),
+                (true, false) => Some(Event::Create(to)),
+                (false, false) => Some(Event::Rename(from, to)),
+            },
+            event => Some(event),
+        }
+    }
 }
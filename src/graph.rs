@@ -1,17 +1,29 @@
-use crate::conflict_detector::{Conflict, Write, WriteSource};
+use crate::config::STARLING_CONFIG;
+use crate::conflict_detector::{Conflict, Write, WriteReason, WriteSource};
+use crate::conflict_store::ConflictStore;
+use crate::connection::{is_short_id, short_id_key, ResourceKind};
+use crate::constraints::{AcyclicLinkMap, ConstraintViolation};
+use crate::error::{ApiError, Diagnostic, ErrorReport};
+use crate::journal::Journal;
 use crate::node::{Node, NodeOptions};
-use crate::path_node::StarlingNode;
-use crate::{debouncer::DebouncedEvents, patch::GraphPatch, path_node::PathNode};
+use crate::path_node::{strip_ids, write_ulids, StarlingNode};
+use crate::pending_writes::PendingWrites;
+use crate::schema::PropertyError;
+use crate::{
+    debouncer::DebouncedEvents, exclude::ExcludeMatcher, patch::GraphPatch, path_node::PathNode,
+};
 use fmterr::fmterr;
-use futures::future::join;
 use futures::future::join_all;
 use futures::future::OptionFuture;
-use orgish::Format;
+use parking_lot::Mutex;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
 use std::sync::Arc;
 use std::{
     collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tokio::sync::{RwLock, RwLockWriteGuard};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -46,6 +58,20 @@ pub enum GraphUpdate {
     },
     /// The node with the given ID should be removed from the index with the given name.
     RemoveNodeFromIndex { id: Uuid, index: String },
+    /// The node with the given ID should be able to be linked to by the name with the given key
+    /// (see [`crate::connection::name_key`]). As this could resolve connections to that name that
+    /// were previously unresolved (or make ones that were resolved ambiguous), this will create
+    /// [`GraphUpdate::CheckConnection`] instructions for every connection to the name.
+    AddName { id: Uuid, name: Uuid },
+    /// The node with the given ID should no longer be able to be linked to by the name with the
+    /// given key. Like [`GraphUpdate::AddName`], this will recheck every connection to the name.
+    RemoveName { id: Uuid, name: Uuid },
+    /// The node with the given ID should be recorded as citing the resource with the given key
+    /// (see [`crate::connection::Resource::key`]).
+    AddResource { id: Uuid, resource: String },
+    /// The node with the given ID should no longer be recorded as citing the resource with the
+    /// given key.
+    RemoveResource { id: Uuid, resource: String },
     /// We should remove the backlink on the node with the given ID from the node with the given
     /// ID. This will be because either there is no longer a connection to it, or because the
     /// source vertex has been removed.
@@ -66,9 +92,15 @@ pub enum GraphUpdate {
     /// (in which case we know this will succeed). It is also used to invalidate known-bad
     /// connections.
     ///
+    /// If there's no node with the ID being connected to, it will be treated as a connection by
+    /// name, and will be valid if exactly one node has that name.
+    ///
     /// If the connection is found to be valid, the path which made the connection will be written
     /// to disk with any updated connection titles.
     CheckConnection { from: Uuid, to: Uuid },
+    /// The path containing the node with the given ID should be written back to the disk, because
+    /// its document was changed during parsing (e.g. tag aliases were normalized).
+    RewritePath { id: Uuid, path: PathBuf },
 }
 impl std::fmt::Debug for GraphUpdate {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -86,6 +118,14 @@ impl std::fmt::Debug for GraphUpdate {
             GraphUpdate::RemoveNodeFromIndex { id, index } => {
                 write!(f, "RemoveNodeFromIndex({:?}, {:?})", id, index)
             }
+            GraphUpdate::AddName { id, name } => write!(f, "AddName({:?}, {:?})", id, name),
+            GraphUpdate::RemoveName { id, name } => write!(f, "RemoveName({:?}, {:?})", id, name),
+            GraphUpdate::AddResource { id, resource } => {
+                write!(f, "AddResource({:?}, {:?})", id, resource)
+            }
+            GraphUpdate::RemoveResource { id, resource } => {
+                write!(f, "RemoveResource({:?}, {:?})", id, resource)
+            }
             GraphUpdate::RemoveBacklink { on, from } => {
                 write!(f, "RemoveBacklink({:?}, {:?})", on, from)
             }
@@ -95,13 +135,76 @@ impl std::fmt::Debug for GraphUpdate {
             GraphUpdate::CheckConnection { from, to } => {
                 write!(f, "CheckConnection({:?}, {:?})", from, to)
             }
+            GraphUpdate::RewritePath { id, path } => {
+                write!(f, "RewritePath({:?}, {:?})", id, path)
+            }
         }
     }
 }
 
 type NodeMap = HashMap<Uuid, PathBuf>;
 type PathMap = HashMap<PathBuf, RwLock<PathNode>>;
-type InvalidConnectionsMap = HashMap<Uuid, HashSet<Uuid>>;
+type InvalidConnectionsMap = HashMap<Uuid, InvalidConnectionEntry>;
+type NameMap = HashMap<Uuid, NameEntry>;
+type ResourceMap = HashMap<String, HashSet<Uuid>>;
+
+/// The nodes with a single name (i.e. title or alias), and the connections that resolved to one
+/// of them.
+#[derive(Default)]
+pub(crate) struct NameEntry {
+    /// The nodes with this name. Connections to the name can only be resolved if there's exactly
+    /// one of these.
+    nodes: HashSet<Uuid>,
+    /// The nodes with valid connections to this name, mapped to the node their connections
+    /// resolved to. Invalid connections to this name are recorded in the invalid connections map
+    /// instead.
+    bindings: HashMap<Uuid, Uuid>,
+}
+
+/// The nodes with invalid connections to a single ID or name key.
+#[derive(Default)]
+pub(crate) struct InvalidConnectionEntry {
+    /// The nodes which made such a connection, by their IDs.
+    referrers: HashSet<Uuid>,
+    /// If these are connections by name (or short ID), the text they were written with, so they
+    /// can be reported by that rather than their (opaque) name key. Where different referrers
+    /// wrote the name differently, this is the first way it was written.
+    name: Option<String>,
+}
+
+/// A connection which couldn't be resolved, as reported by [`Graph::errors`].
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InvalidConnection {
+    /// The ID the connection pointed to, if it was a connection by ID.
+    pub id: Option<Uuid>,
+    /// The title, alias, or short ID the connection pointed to, as it was written, if it was a
+    /// connection by name.
+    pub name: Option<String>,
+    /// Why the connection couldn't be resolved.
+    pub reason: InvalidConnectionReason,
+    /// The nodes an ambiguous name could refer to, sorted by their IDs. This is empty for
+    /// connections that didn't resolve to anything.
+    pub candidates: Vec<Uuid>,
+}
+/// The reason a connection couldn't be resolved.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidConnectionReason {
+    /// There's no node with the ID or name the connection pointed to.
+    NotFound,
+    /// There's more than one node with the name the connection pointed to.
+    Ambiguous,
+}
+impl NameEntry {
+    /// Gets the node connections to this name should resolve to, if there's exactly one.
+    fn resolve(&self) -> Option<Uuid> {
+        if self.nodes.len() == 1 {
+            self.nodes.iter().next().copied()
+        } else {
+            None
+        }
+    }
+}
 
 pub(crate) struct IndexMap {
     /// An alphabetically-ordered map of the actual index data.
@@ -205,6 +308,16 @@ pub(crate) struct Index {
 }
 pub type IndexCriteria = Arc<dyn Fn(&StarlingNode) -> bool + Send + Sync>;
 
+/// Determines whether or not the given node has the given tag (or an alias of it) directly, or
+/// has a more specific tag under it (e.g. `cs/algorithms` for `cs`). This is intended for use in
+/// [`IndexCriteria`], which only see the node itself, so inherited tags aren't considered.
+pub fn node_has_tag(node: &StarlingNode, tag: &str) -> bool {
+    let config = STARLING_CONFIG.get();
+    config
+        .expand_tags(node.tags.iter())
+        .contains(config.canonical_tag(tag))
+}
+
 /// A graph of many nodes derived from Org/Markdown files ([`PathNode`]s), which are connected
 /// together.
 pub struct Graph {
@@ -228,11 +341,51 @@ pub struct Graph {
     /// If maps are to be locked, this must always be locked third. If individual paths are to be
     /// locked, they should be locked sorted in path order to prevent deadlocks.
     pub(crate) paths: RwLock<PathMap>,
-    /// A list of invalid connections, indexed by the invalid ID they connected to, and listing in
-    /// each entry the set of nodes which made such a connection, by their IDs.
+    /// A lookup table of the names nodes can be linked to by (their titles and aliases), keyed by
+    /// [`crate::connection::name_key`].
     ///
     /// If maps are to be locked, this must always be locked fourth.
+    pub(crate) names: RwLock<NameMap>,
+    /// A list of invalid connections, indexed by the invalid ID they connected to, and listing in
+    /// each entry the set of nodes which made such a connection, by their IDs. Connections by name
+    /// which are unresolved or ambiguous are recorded here under their name keys, along with the
+    /// name they were written with.
+    ///
+    /// If maps are to be locked, this must always be locked fifth.
     pub(crate) invalid_connections: RwLock<InvalidConnectionsMap>,
+    /// A map of the resources cited anywhere in the graph (keyed by
+    /// [`crate::connection::Resource::key`]) to the nodes which cite them.
+    ///
+    /// If maps are to be locked, this must always be locked sixth.
+    pub(crate) resources: RwLock<ResourceMap>,
+    /// The valid connections whose types mustn't form cycles, used to find the cycles they do
+    /// form.
+    ///
+    /// If maps are to be locked, this must always be locked seventh.
+    pub(crate) acyclic_links: RwLock<AcyclicLinkMap>,
+    /// The writes which couldn't be made because they conflicted with changes on the disk. These
+    /// are recorded by the filesystem engine and resolved through the server, which is why they
+    /// live here.
+    ///
+    /// This is independent of all the other maps, and can be accessed at any time.
+    pub(crate) conflicts: ConflictStore,
+    /// The writes which weren't made because Starling is in read-only mode. Like the conflicts,
+    /// these are recorded by the filesystem engine and applied through the server.
+    ///
+    /// This is independent of all the other maps, and can be accessed at any time.
+    pub(crate) pending_writes: PendingWrites,
+    /// The record of every write the filesystem engine has made, through which those writes can
+    /// be undone from the server.
+    ///
+    /// This is independent of all the other maps, and can be accessed at any time.
+    pub(crate) journal: Journal,
+    /// The unknown tags the user has approved through the server, which won't be warned about
+    /// again. These are only kept for as long as Starling is running: to keep them after that,
+    /// they should be added to `tags` in the configuration file.
+    ///
+    /// This is independent of all the other maps, but it's only modified with the paths map
+    /// locked, so paths created while a tag is being approved don't miss it.
+    approved_tags: Mutex<HashSet<String>>,
 }
 impl Graph {
     /// Creates a new, completely empty graph. Typically, [`Self::from_dir`] would be used to
@@ -243,23 +396,33 @@ impl Graph {
             nodes: RwLock::new(HashMap::new()),
             indices: IndexMap::new(indices),
             paths: RwLock::new(HashMap::new()),
+            names: RwLock::new(HashMap::new()),
             invalid_connections: RwLock::new(HashMap::new()),
+            resources: RwLock::new(HashMap::new()),
+            acyclic_links: RwLock::new(HashMap::new()),
+            conflicts: ConflictStore::new(),
+            pending_writes: PendingWrites::new(),
+            journal: Journal::new(),
+            approved_tags: Mutex::new(HashSet::new()),
         }
     }
     /// Returns any errors associated with the given path. The return type here is a little
-    /// strange: if the path couldn't be parsed, you'll get an `Err(PathParseError)` (stringified),
-    /// but if it could be, you'll get an `Ok(_)` with a list of the IDs of all invalid connections
-    /// made in the path. If the path doesn't exist at all, you'll get `None`. Since any path parse
+    /// strange: if the path couldn't be parsed, you'll get an `Err(_)` with a report of the
+    /// [`crate::error::PathParseError`], but if it could be, you'll get an `Ok(_)` with a list of
+    /// all the invalid connections made in the path. Connections by name are reported by the name
+    /// they were written with, along with the nodes it could refer to if it's ambiguous. If the path doesn't exist at all, you'll get `None`. Since any path parse
     /// error will be erased once it no longer occurs in the latest version, even if there are
     /// invalid connections present in an earlier, parseable version, if the latest version is not
     /// parseable, that error will be returned preferentially.
     #[tracing::instrument(skip(self))]
-    pub async fn errors(&self, path: &Path) -> Option<Result<Vec<Uuid>, String>> {
+    pub async fn errors(&self, path: &Path) -> Option<Result<Vec<InvalidConnection>, ErrorReport>> {
         let paths = self.paths.read().await;
+        let names = self.names.read().await;
+        let invalid_connections = self.invalid_connections.read().await;
         let path_node = paths.get(path)?.read().await;
 
         Some(if let Some(err) = &path_node.error {
-            Err(fmterr(err))
+            Err(err.report())
         } else {
             // If there's no error, a document is guaranteed
             Ok(path_node
@@ -269,10 +432,202 @@ impl Graph {
                 .root
                 .connections()
                 .filter(|conn| !conn.is_valid())
-                .map(|conn| conn.id())
+                .map(|conn| {
+                    let key = conn.id();
+                    let name = invalid_connections
+                        .get(&key)
+                        .and_then(|entry| entry.name.clone())
+                        .or_else(|| conn.name().map(|name| name.to_string()));
+                    let Some(name) = name else {
+                        // Connections by name which have been rewritten will be written back
+                        // with the ID they last resolved to
+                        return InvalidConnection {
+                            id: Some(conn.target()),
+                            name: None,
+                            reason: InvalidConnectionReason::NotFound,
+                            candidates: Vec::new(),
+                        };
+                    };
+                    let mut candidates = names
+                        .get(&key)
+                        .filter(|entry| entry.nodes.len() > 1)
+                        .map(|entry| entry.nodes.iter().copied().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    candidates.sort();
+
+                    InvalidConnection {
+                        id: None,
+                        name: Some(name),
+                        reason: if candidates.is_empty() {
+                            InvalidConnectionReason::NotFound
+                        } else {
+                            InvalidConnectionReason::Ambiguous
+                        },
+                        candidates,
+                    }
+                })
                 .collect())
         })
     }
+    /// Returns any warnings associated with the given path from the last time it was successfully
+    /// parsed (stringified). These don't prevent the path from being part of the graph, but they
+    /// might indicate something the user didn't intend. If the path doesn't exist, you'll get
+    /// `None`.
+    #[tracing::instrument(skip(self))]
+    pub async fn warnings(&self, path: &Path) -> Option<Vec<String>> {
+        let paths = self.paths.read().await;
+        let path_node = paths.get(path)?.read().await;
+
+        Some(path_node.warnings.iter().map(|w| fmterr(w)).collect())
+    }
+    /// Returns diagnostics for any problems with the given path, with the nodes they're about,
+    /// where they are in the file, and how serious they are (most serious first). If the latest
+    /// version of the path couldn't be parsed, the error will come first, followed by any
+    /// problems from the last time it was successfully parsed. These include warnings, parts of
+    /// the file that had to be left out to parse the rest, and, in lenient mode, problems with
+    /// individual nodes that would otherwise have stopped the path from being parsed. If the
+    /// path doesn't exist, you'll get `None`.
+    #[tracing::instrument(skip(self))]
+    pub async fn diagnostics(&self, path: &Path) -> Option<Vec<Diagnostic>> {
+        let paths = self.paths.read().await;
+        let path_node = paths.get(path)?.read().await;
+
+        let mut diagnostics = path_node
+            .error
+            .as_ref()
+            .map(|err| err.to_diagnostic())
+            .into_iter()
+            .collect::<Vec<_>>();
+        diagnostics.extend(path_node.diagnostics());
+
+        Some(diagnostics)
+    }
+    /// Returns all the unknown tags found in lenient mode which are pending approval, if they're
+    /// being collected (see [`crate::config::Config::collect_unknown_tags`]).
+    #[tracing::instrument(skip(self))]
+    pub async fn pending_tags(&self) -> Vec<String> {
+        if !STARLING_CONFIG.get().collect_unknown_tags {
+            return Vec::new();
+        }
+
+        let paths = self.paths.read().await;
+        let mut tags = HashSet::new();
+        for path_node in paths.values() {
+            let path_node = path_node.read().await;
+            tags.extend(path_node.unknown_tags().cloned());
+        }
+        let mut tags = tags.into_iter().collect::<Vec<_>>();
+        tags.sort_unstable();
+
+        tags
+    }
+    /// Approves the given tag which is pending approval, clearing any diagnostics about it being
+    /// unknown, now and whenever it's used in future. This fails if the tag wasn't pending.
+    ///
+    /// Approved tags aren't added to the configuration, so they'll be unknown again after a
+    /// restart unless the user adds them to `tags` in their configuration file.
+    #[tracing::instrument(skip(self))]
+    pub async fn approve_tag(&self, tag: &str) -> Result<(), ApiError> {
+        let not_pending = || ApiError::TagNotPending {
+            tag: tag.to_string(),
+        };
+        if !STARLING_CONFIG.get().collect_unknown_tags {
+            return Err(not_pending());
+        }
+
+        let paths = self.paths.read().await;
+        // Work out which paths use the tag first, so we only need to lock those for writing
+        let mut affected = Vec::new();
+        for (path, path_node) in paths.iter() {
+            if path_node.read().await.unknown_tags().any(|t| t == tag) {
+                affected.push(path);
+            }
+        }
+        if affected.is_empty() {
+            return Err(not_pending());
+        }
+        self.approved_tags.lock().insert(tag.to_string());
+
+        affected.sort_unstable();
+        for path in affected {
+            paths[path].write().await.approve_tag(tag);
+        }
+
+        Ok(())
+    }
+    /// Returns the targets of any links to local files in the given path that don't exist, as
+    /// they were written, given the root directory of the graph. These are checked every time
+    /// this is called (since attachments aren't tracked). If the path doesn't exist, you'll get
+    /// `None`.
+    #[tracing::instrument(skip(self))]
+    pub async fn dangling_resources(&self, path: &Path, dir: &Path) -> Option<Vec<String>> {
+        let paths = self.paths.read().await;
+        let path_node = paths.get(path)?.read().await;
+
+        let mut dangling = path_node
+            .resources()
+            .filter(|(_, resource)| resource.kind == ResourceKind::File)
+            .filter(|(_, resource)| !dir.join(resource.file_path(path)).exists())
+            .map(|(_, resource)| resource.target.clone())
+            .collect::<Vec<_>>();
+        dangling.sort_unstable();
+        dangling.dedup();
+
+        Some(dangling)
+    }
+    /// Returns all the constraints on link types broken by valid connections in the given path,
+    /// or [`None`] if the path doesn't exist. Like invalid connections, these don't stop the path
+    /// from being parsed.
+    #[tracing::instrument(skip(self))]
+    pub async fn constraint_violations(&self, path: &Path) -> Option<Vec<ConstraintViolation>> {
+        let paths = self.paths.read().await;
+        // This has to be locked before any individual paths
+        let acyclic_links = self.acyclic_links.read().await;
+        let path_node = paths.get(path)?.read().await;
+
+        let mut violations = path_node.violations(&acyclic_links);
+        violations.sort_by(|a, b| (a.from, a.to, &a.ty).cmp(&(b.from, b.to, &b.ty)));
+        Some(violations)
+    }
+    /// Returns all the ways in which the properties of nodes in the given path don't match the
+    /// schemas that apply to them, or [`None`] if the path doesn't exist.
+    #[tracing::instrument(skip(self))]
+    pub async fn property_errors(&self, path: &Path) -> Option<Vec<PropertyError>> {
+        let paths = self.paths.read().await;
+        let path_node = paths.get(path)?.read().await;
+
+        let mut errors = path_node.property_errors();
+        // Errors for each node are already sorted
+        errors.sort_by_key(|err| err.id);
+        Some(errors)
+    }
+    /// Returns the IDs of all the nodes which cite the given resource, which should be a URL or
+    /// the path to a file relative to the root directory.
+    #[tracing::instrument(skip(self))]
+    pub async fn citations(&self, resource: &str) -> Vec<Uuid> {
+        let resources = self.resources.read().await;
+        let mut citers = resources
+            .get(resource)
+            .map(|citers| citers.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        citers.sort_unstable();
+
+        citers
+    }
+    /// Resolves an ID written in any of the forms nodes can be linked to by (as a UUID, as a ULID,
+    /// or as the short form of either, if those are enabled) to the ID of a node. Short IDs only
+    /// resolve if exactly one node's ID starts that way, but whole IDs aren't checked to exist.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve_id(&self, id: &str) -> Option<Uuid> {
+        if let Some(id) = crate::ulid::parse_id(id) {
+            return Some(id);
+        }
+        if !is_short_id(id, STARLING_CONFIG.get().id_prefix_length) {
+            return None;
+        }
+        let names = self.names.read().await;
+        names.get(&short_id_key(id))?.resolve()
+    }
     /// Gets the ID of the root node in the given path, if it exists and has a document defined.
     /// This can be used to, given a path, start interfacing with its nodes.
     pub async fn root_id(&self, path: &Path) -> Option<Uuid> {
@@ -293,12 +648,13 @@ impl Graph {
     pub async fn from_dir(
         dir: &Path,
         indices: HashMap<String, IndexCriteria>,
+        exclude: &ExcludeMatcher,
     ) -> (Self, Vec<Write>) {
         assert!(dir.is_dir());
 
-        // Fake creation events recursively for everything in the directory
-        let creations = DebouncedEvents::start_from_dir(dir);
-        let patch = GraphPatch::from_events(creations, dir).await;
+        // Fake creation events recursively for everything in the directory that isn't excluded
+        let creations = DebouncedEvents::start_from_dir(dir, exclude);
+        let patch = GraphPatch::from_events(creations, dir, exclude).await;
 
         let this = Self::new(indices);
         let writes = this.process_fs_patch(patch).await;
@@ -308,17 +664,26 @@ impl Graph {
     /// Rescans the given directory, completely reconstructing the graph from it, from scratch.
     /// This will take considerably longer than processing atomic file events, and should only be
     /// done if absolutely necessary. This returns any correcting writes needed.
-    #[tracing::instrument(skip(self))]
-    pub async fn rescan(&mut self, dir: &Path) -> Vec<Write> {
+    #[tracing::instrument(skip(self, exclude))]
+    pub async fn rescan(&mut self, dir: &Path, exclude: &ExcludeMatcher) -> Vec<Write> {
         let mut nodes = self.nodes.write().await;
         let index_locks = self.indices.write_all().await;
         let mut paths = self.paths.write().await;
+        let mut names = self.names.write().await;
         let mut invalid_connections = self.invalid_connections.write().await;
+        let mut resources = self.resources.write().await;
+        let mut acyclic_links = self.acyclic_links.write().await;
 
-        let (mut new_graph, writes) = Self::from_dir(dir, self.indices.criteria()).await;
+        let (mut new_graph, writes) = Self::from_dir(dir, self.indices.criteria(), exclude).await;
         *nodes = new_graph.nodes.into_inner();
         *paths = new_graph.paths.into_inner();
+        for path_node in paths.values_mut() {
+            self.clear_approved_tags(path_node.get_mut());
+        }
+        *names = new_graph.names.into_inner();
         *invalid_connections = new_graph.invalid_connections.into_inner();
+        *resources = new_graph.resources.into_inner();
+        *acyclic_links = new_graph.acyclic_links.into_inner();
 
         // Update each index in order (the new graph is guaranteed to have the same indices)
         for (index_name, mut index_map) in index_locks {
@@ -352,7 +717,7 @@ impl Graph {
         let mut full_nodes = Vec::new();
         for id in nodes.keys() {
             // A node listed in an index is guaranteed to exist
-            full_nodes.push(self.get_node(*id, options).await.unwrap());
+            full_nodes.push(self.get_node(*id, options.clone()).await.unwrap());
         }
 
         // In testing, we need a reliable order
@@ -361,6 +726,18 @@ impl Graph {
 
         full_nodes
     }
+    /// Gets a list of all the nodes with the given tag (or an alias of it), including those which
+    /// inherit it from a parent, or which have a more specific tag under it (e.g. `cs/algorithms`
+    /// for `cs`). Like [`Self::nodes`], this takes a format for links in titles.
+    #[tracing::instrument(skip(self))]
+    pub async fn tagged(&self, tag: &str, options: NodeOptions) -> Vec<Node> {
+        let tag = STARLING_CONFIG.get().canonical_tag(tag).to_string();
+        self.nodes(None, options)
+            .await
+            .into_iter()
+            .filter(|node| node.expanded_tags.contains(&tag))
+            .collect()
+    }
     /// Process a batch of updates from the filesystem. This operates as the start of a pipeline,
     /// generating modifications which in turn generate instructions for locking and graph updates.
     /// This will acquire read locks on the paths map and some individual paths as necessary to
@@ -380,16 +757,9 @@ impl Graph {
         self.process_renames(patch.renames).await;
 
         // Creations, deletions, and modifications need read guards, and so can all be done
-        // simultaneously without impacting anything else. Creations can be done synchronously, the
-        // others are async. We do deletions first to avoid possible ID conflicts and the like.
-        let mut creation_updates = Vec::new();
+        // simultaneously without impacting anything else. We do deletions first to avoid possible
+        // ID conflicts and the like.
         let paths = self.paths.read().await;
-        for path_patch in patch.creations {
-            let (path_node, mut updates_l) =
-                PathNode::new(path_patch.path, path_patch.contents_res, &index_checkers);
-            updates_l.push(GraphUpdate::CreatePathNode(path_node));
-            creation_updates.push(updates_l);
-        }
         let mut deletion_futs = Vec::new();
         for path in patch.deletions {
             // We by definition can't do anything with a bad deletion, so ignore it if we can't
@@ -402,50 +772,78 @@ impl Graph {
                 });
             }
         }
-        let mut modification_futs = Vec::new();
-        for path_patch in patch.modifications {
+        // This is a `Vec<Vec<GraphUpdate>>`
+        let deletion_updates = join_all(deletion_futs).await;
+
+        // Modifications need read guards on the path nodes they're updating, which we acquire
+        // up-front (in path order, like any other fine-grained locks) so the actual parsing can be
+        // done without touching the runtime
+        let mut creations = patch.creations;
+        let mut modification_patches = patch.modifications;
+        modification_patches.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        let mut modifications = Vec::new();
+        for path_patch in modification_patches {
             // If we can't find the path a modification is talking about, treat it as a creation
             if let Some(path_node) = paths.get(&path_patch.path) {
-                modification_futs.push(async {
-                    let path_node = path_node.read().await;
-                    let (new_path_node, mut updates_l) = path_node.update(
-                        path_patch.path.clone(),
-                        path_patch.contents_res,
-                        &index_checkers,
-                    );
-                    updates_l.push(GraphUpdate::ModifyPathNode {
-                        // We use the old path in case the new one has changed
-                        path: path_patch.path,
-                        new_node: new_path_node,
-                    });
-
-                    updates_l
-                });
+                modifications.push((path_node.read().await, path_patch));
             } else {
                 debug!(
                     "tried to modify path that didn't exist: {:?}",
                     &path_patch.path
                 );
-
-                let (path_node, mut updates_l) =
-                    PathNode::new(path_patch.path, path_patch.contents_res, &index_checkers);
-                updates_l.push(GraphUpdate::CreatePathNode(path_node));
-                creation_updates.push(updates_l);
+                creations.push(path_patch);
             }
         }
 
-        // These are both `Vec<Vec<GraphUpdate>>`
-        // TODO: If we get deadlocks, we may need to sort these by path so they read in a fixed
-        // order
-        let (deletion_updates, modification_updates) =
-            join(join_all(deletion_futs), join_all(modification_futs)).await;
-        // Existing updates are from creations, put everything else first to avoid creating a new
-        // ID (this can happen with Vim-style saves)
+        // Parsing is entirely CPU-bound (each path gets a full Org/Markdown parse and then
+        // tokenisation for connections), so we fan it out across a worker pool. On a large patch
+        // (like the initial scan), this is where almost all our time goes.
+        let (creation_updates, modification_updates) = parse_in_place(|| {
+            rayon::join(
+                || {
+                    creations
+                        .into_par_iter()
+                        .map(|path_patch| {
+                            let (path_node, mut updates_l) = PathNode::new(
+                                path_patch.path,
+                                path_patch.contents_res,
+                                &index_checkers,
+                            );
+                            updates_l.push(GraphUpdate::CreatePathNode(path_node));
+                            updates_l
+                        })
+                        .collect::<Vec<_>>()
+                },
+                || {
+                    modifications
+                        .into_par_iter()
+                        .map(|(path_node, path_patch)| {
+                            let (new_path_node, mut updates_l) = path_node.update(
+                                path_patch.path.clone(),
+                                path_patch.contents_res,
+                                &index_checkers,
+                            );
+                            updates_l.push(GraphUpdate::ModifyPathNode {
+                                // We use the old path in case the new one has changed
+                                path: path_patch.path,
+                                new_node: new_path_node,
+                            });
+
+                            updates_l
+                        })
+                        .collect::<Vec<_>>()
+                },
+            )
+        });
+
+        // Creation updates go last to avoid creating a new ID (this can happen with Vim-style
+        // saves)
         let mut updates = deletion_updates;
         updates.extend(modification_updates);
         updates.extend(creation_updates);
 
         // This doesn't get automatically dropped, so we have to do it manually to avoid a deadlock
+        // (the modification guards were consumed by the parsing above)
         drop(paths);
 
         self.process_updates(updates.into_iter().flat_map(|v| v.into_iter()))
@@ -463,6 +861,7 @@ impl Graph {
         let mut nodes = self.nodes.write().await;
         let mut indices = self.indices.write_all().await;
         let mut paths = self.paths.write().await;
+        let mut resources = self.resources.write().await;
         debug!("maps locked for renaming");
         for (from, to) in renames {
             // If we can't find the original path, we'll leave this (this is a valid case, see
@@ -470,6 +869,20 @@ impl Graph {
             if let Some(path_node) = paths.remove(&from) {
                 // We hold the only reference, writing is guaranteed
                 let mut path_node_ref = path_node.try_write().unwrap();
+                // Relative links to files will point somewhere else now
+                for (id, resource) in path_node_ref.resources() {
+                    let old_key = resource.key(&from);
+                    let new_key = resource.key(&to);
+                    if old_key != new_key {
+                        if let Some(citers) = resources.get_mut(&old_key) {
+                            citers.remove(&id);
+                            if citers.is_empty() {
+                                resources.remove(&old_key);
+                            }
+                        }
+                        resources.entry(new_key).or_default().insert(id);
+                    }
+                }
                 path_node_ref.rename(to.clone());
                 // Make sure all its nodes point to the new path
                 for node_id in path_node_ref.ids() {
@@ -497,7 +910,10 @@ impl Graph {
     async fn process_updates(&self, updates: impl Iterator<Item = GraphUpdate>) -> Vec<Write> {
         let mut should_lock_nodes = false;
         let mut should_lock_paths = false;
+        let mut should_lock_names = false;
         let mut should_lock_invalid_connections = false;
+        let mut should_lock_resources = false;
+        let mut should_lock_acyclic_links = false;
         let mut indices_to_lock = HashSet::new();
         // These are the IDs of nodes whose paths we'll need to lock (but not all of them will be
         // entered into the nodes map until after stage 1). If any of them don't exist, they'll be
@@ -515,7 +931,7 @@ impl Graph {
         // connections), which will be because they have new IDs (possibly forced and we need to
         // make them permanent) or new/updated connections, or one of their connection targets
         // updated its title.
-        let mut paths_to_write = HashSet::new();
+        let mut paths_to_write: HashMap<PathBuf, HashSet<WriteReason>> = HashMap::new();
 
         for update in updates {
             match update {
@@ -536,10 +952,18 @@ impl Graph {
                     // out which nodes we need to lock when we hit this instruction!)
                     should_lock_invalid_connections = true;
                     debug!("will lock `invalid_connections` for new node {id} in {path:?}");
+                    // If it does, we'll check those connections again, which might need to look
+                    // up names, and might be ones that can't form cycles
+                    should_lock_names = true;
+                    should_lock_acyclic_links = true;
+                    debug!("will lock `names` and `acyclic_links` to maybe recheck connections to {id}");
 
                     // A new node might have had an ID force-created for it during parsing, so
                     // we should write this path back to the disk to ensure ID stability
-                    paths_to_write.insert(path.clone());
+                    paths_to_write
+                        .entry(path.clone())
+                        .or_default()
+                        .insert(WriteReason::NewNodes);
                     debug!("will write to path {path:?} for new node {id}");
                     // We need to lock that path in order to write to it, and this ID comes
                     // from it, so locking that is sufficient
@@ -550,10 +974,21 @@ impl Graph {
                     debug!("will lock `nodes` for new node {id} in {path:?}");
                     map_updates.push(update);
                 }
+                GraphUpdate::RewritePath { id, path } => {
+                    // There's nothing to update, we just need to lock the path to write it
+                    paths_to_write
+                        .entry(path.clone())
+                        .or_default()
+                        .insert(WriteReason::TagAliases);
+                    nodes_to_lock.insert(id);
+                    debug!("will lock and write to path {path:?} after rewriting it");
+                }
                 GraphUpdate::RemoveNode(_) => {
                     should_lock_nodes = true;
+                    // Any links the node made that can't form cycles should go too
+                    should_lock_acyclic_links = true;
                     map_updates.push(update);
-                    debug!("will lock `nodes` for node removal");
+                    debug!("will lock `nodes` and `acyclic_links` for node removal");
                 }
                 GraphUpdate::AddNodeToIndex { id, ref path, ref index } => {
                     // We'll need to lock the index map to add the node to it
@@ -567,6 +1002,23 @@ impl Graph {
                     debug!("will lock index {index} to remove node {id}");
                     map_updates.push(update);
                 }
+                GraphUpdate::AddName { id, name } | GraphUpdate::RemoveName { id, name } => {
+                    // Connections to this name might have changed validity, and we'll find them
+                    // in both the names map and the invalid connections map
+                    map_updates.push(update);
+                    should_lock_names = true;
+                    should_lock_invalid_connections = true;
+                    debug!("will lock `names` and `invalid_connections` to update name {name} for {id}");
+                    // The connections we recheck might be ones that can't form cycles
+                    should_lock_acyclic_links = true;
+                    debug!("will lock `acyclic_links` to recheck connections to name {name}");
+                }
+                GraphUpdate::AddResource { id, ref resource }
+                | GraphUpdate::RemoveResource { id, ref resource } => {
+                    should_lock_resources = true;
+                    debug!("will lock `resources` to update resource {resource:?} for {id}");
+                    map_updates.push(update);
+                }
                 GraphUpdate::RemoveInvalidConnection { from, to } => {
                     map_updates.push(update);
                     should_lock_invalid_connections = true;
@@ -577,7 +1029,10 @@ impl Graph {
                 GraphUpdate::RemoveBacklink { on, from } => {
                     node_updates.push(update);
                     nodes_to_lock.insert(on);
-                    debug!("will lock {on} to remove backlink from {from}")
+                    debug!("will lock {on} to remove backlink from {from}");
+                    // The connection might have been one that can't form cycles
+                    should_lock_acyclic_links = true;
+                    debug!("will lock `acyclic_links` to remove link from {from} to {on}");
                 }
                 GraphUpdate::CheckConnection { from, to } => {
                     node_updates.push(update);
@@ -590,9 +1045,17 @@ impl Graph {
                     nodes_to_lock.insert(to);
                     debug!("will lock {to} to maybe add backlink from {from}");
 
+                    // The connection might be by name, in which case we'll need to resolve it
+                    should_lock_names = true;
+                    debug!("will lock `names` to maybe resolve connection from {from} to {to}");
+
                     // We also might need to add an invalid connection
                     should_lock_invalid_connections = true;
                     debug!("will lock `invalid_connections` to maybe add invalid connection from {from} to {to}");
+
+                    // And the connection might be one that can't form cycles
+                    should_lock_acyclic_links = true;
+                    debug!("will lock `acyclic_links` to maybe record link from {from} to {to}");
                 }
             }
         }
@@ -601,19 +1064,33 @@ impl Graph {
         let mut nodes = OptionFuture::from(should_lock_nodes.then(|| self.nodes.write())).await;
         let mut index_maps = self.indices.write_some(indices_to_lock).await;
         let mut paths = OptionFuture::from(should_lock_paths.then(|| self.paths.write())).await;
+        let mut names = OptionFuture::from(should_lock_names.then(|| self.names.write())).await;
         let mut invalid_connections = OptionFuture::from(
             should_lock_invalid_connections.then(|| self.invalid_connections.write()),
         )
         .await;
+        let mut resources =
+            OptionFuture::from(should_lock_resources.then(|| self.resources.write())).await;
+        let mut acyclic_links =
+            OptionFuture::from(should_lock_acyclic_links.then(|| self.acyclic_links.write())).await;
         if nodes.is_some() {
             debug!("nodes map locked");
         }
         if paths.is_some() {
             debug!("paths map locked");
         }
+        if names.is_some() {
+            debug!("names map locked");
+        }
         if invalid_connections.is_some() {
             debug!("invalid connections map locked");
         }
+        if resources.is_some() {
+            debug!("resources map locked");
+        }
+        if acyclic_links.is_some() {
+            debug!("acyclic links map locked");
+        }
 
         // Now we have what we need to run the stage 1 updates (which operate on maps). We'll
         // insert things with new locks here, which doesn't matter because nothing can get at them
@@ -631,6 +1108,8 @@ impl Graph {
                         error!("tried to create new path node for {:?} that was already present in graph", path_node.path());
                     }
 
+                    let mut path_node = path_node;
+                    self.clear_approved_tags(&mut path_node);
                     let path = path_node.path();
                     paths
                         .as_mut()
@@ -643,6 +1122,8 @@ impl Graph {
                         error!("tried to modify path node for {path:?} that wasn't in the graph");
                     }
 
+                    let mut new_node = new_node;
+                    self.clear_approved_tags(&mut new_node);
                     let path_node = paths.as_mut().unwrap().get_mut(&path).unwrap();
                     // Fine to blitz the other lock, there can't be any references to it
                     *path_node = RwLock::new(new_node);
@@ -670,11 +1151,11 @@ impl Graph {
                     // was an invalid connection (if it ever was). This is the only time we add
                     // more nodes to lock or create new instructions (fine because of the node/map
                     // update separation).
-                    if let Some(referrers) = invalid_connections.as_mut().unwrap().remove(&id) {
+                    if let Some(entry) = invalid_connections.as_mut().unwrap().remove(&id) {
                         nodes_to_lock.insert(id);
                         debug!("will lock {id} to maybe add backlinks for previously invalid connections");
 
-                        for referrer in referrers {
+                        for referrer in entry.referrers {
                             // NOTE: This is the only instance where we retroactively add an
                             // update. We replicate perfectly the logic we would have used to
                             // handle it though, including ordering the locking of the appropriate
@@ -699,6 +1180,7 @@ impl Graph {
                     } else {
                         debug!("tried to remove node {node_id} that wasn't in the graph");
                     }
+                    acyclic_links.as_mut().unwrap().remove(&node_id);
                 }
                 GraphUpdate::AddNodeToIndex { id, path, index } => {
                     let index_map = index_maps.get_mut(&index).unwrap();
@@ -722,16 +1204,76 @@ impl Graph {
                         );
                     }
                 }
+                GraphUpdate::AddName { id, name } | GraphUpdate::RemoveName { id, name } => {
+                    let names = names.as_mut().unwrap();
+                    let entry = names.entry(name).or_default();
+                    if matches!(update, GraphUpdate::AddName { .. }) {
+                        entry.nodes.insert(id);
+                        debug!("added name {name} for {id}");
+                    } else {
+                        entry.nodes.remove(&id);
+                        debug!("removed name {name} for {id}");
+                    }
+
+                    // Every connection to this name, resolved or not, needs to be checked again
+                    // (like with `AddNode`, this is a retroactively added update, and we lock the
+                    // old targets of resolved connections when we handle those in a moment)
+                    let referrers = invalid_connections
+                        .as_mut()
+                        .unwrap()
+                        .remove(&name)
+                        .map(|entry| entry.referrers)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .chain(entry.bindings.keys().copied())
+                        .collect::<HashSet<_>>();
+                    for referrer in referrers {
+                        node_updates.push(GraphUpdate::CheckConnection {
+                            from: referrer,
+                            to: name,
+                        });
+                        nodes_to_lock.insert(referrer);
+                        debug!("will lock {referrer} to check its connection to name {name}");
+                    }
+
+                    if entry.nodes.is_empty() && entry.bindings.is_empty() {
+                        names.remove(&name);
+                    }
+                }
+                GraphUpdate::AddResource { id, resource } => {
+                    debug!("recorded {id} as citing resource {resource:?}");
+                    resources
+                        .as_mut()
+                        .unwrap()
+                        .entry(resource)
+                        .or_default()
+                        .insert(id);
+                }
+                GraphUpdate::RemoveResource { id, resource } => {
+                    let resources = resources.as_mut().unwrap();
+                    if let Some(citers) = resources.get_mut(&resource) {
+                        citers.remove(&id);
+                        if citers.is_empty() {
+                            resources.remove(&resource);
+                        }
+                        debug!("removed {id} as citing resource {resource:?}");
+                    } else {
+                        debug!("tried to remove unrecorded resource {resource:?} for {id}");
+                    }
+                }
                 GraphUpdate::RemoveInvalidConnection { from, to } => {
-                    if let Some(invalid_referrers) =
-                        invalid_connections.as_mut().unwrap().get_mut(&from)
-                    {
-                        let removed = invalid_referrers.remove(&to);
+                    // Invalid connections are indexed by the ID they point to
+                    let invalid_connections = invalid_connections.as_mut().unwrap();
+                    if let Some(entry) = invalid_connections.get_mut(&to) {
+                        let removed = entry.referrers.remove(&from);
                         if removed {
                             debug!("removed invalid connection from {from} to {to}");
                         } else {
                             debug!("tried to remove invalid connection from {from} to {to} that wasn't in the graph");
                         }
+                        if entry.referrers.is_empty() {
+                            invalid_connections.remove(&to);
+                        }
                     } else {
                         debug!("tried to remove unrecorded invalid connection to {to}");
                     }
@@ -741,8 +1283,24 @@ impl Graph {
             }
         }
 
-        // We're guaranteed not to need the indices anymore
+        // Connections by name will need the node they resolve to locked, as well as the node they
+        // resolved to last time (if it's different, we'll need to remove the backlink from it).
+        // All the updates to the names map are done, so these won't change.
+        if let Some(names) = names.as_ref() {
+            for update in &node_updates {
+                if let GraphUpdate::CheckConnection { from, to } = update {
+                    if let Some(entry) = names.get(to) {
+                        nodes_to_lock.extend(entry.resolve());
+                        nodes_to_lock.extend(entry.bindings.get(from).copied());
+                        debug!("will lock targets of name {to} to check connection from {from}");
+                    }
+                }
+            }
+        }
+
+        // We're guaranteed not to need the indices or resources anymore
         drop(index_maps);
+        drop(resources);
         // We'll need to map from node IDs to paths to figure out which paths to lock, but we won't
         // need to change anything about this relation, so it's fine to have `nodes` as a read
         // guard
@@ -811,58 +1369,165 @@ impl Graph {
                     } else {
                         debug!("tried to remove backlink on unknown node {on}");
                     }
+                    remove_acyclic_link(acyclic_links.as_mut().unwrap(), from, on);
                 }
                 GraphUpdate::CheckConnection { from, to } => {
                     // Another instruction *could* have ripped this node out from under us
-                    if let Some(path_from) = nodes_ref.get(&from) {
-                        // Here, if the target doesn't exist, then we should log an invalid connection
-                        // (the existence of this update means we will have a write guard on that map)
-                        if let Some(path_to) = nodes_ref.get(&to) {
-                            // Add the backlink first and get the title
-                            let path_node_to = path_nodes.get_mut(path_to).unwrap();
-                            path_node_to.add_backlink(to, from);
-                            debug!("added backlink on {to} from {from}");
-
-                            let title = path_node_to
-                                .display_title(
-                                    to,
-                                    // We're getting the title of this node to display in our
-                                    //`from` node, let's use the format of the from node so we
-                                    // implant a title that makes sense (even though we're talking
-                                    // about insane nested connections here...)
-                                    if path_from.extension().unwrap_or_default() == "org" {
-                                        Format::Org
-                                    } else {
-                                        Format::Markdown
-                                    },
-                                )
-                                .unwrap()
-                                .join("/");
-
-                            // And then validate the connection and update the title of the target
-                            let path_node_from = path_nodes.get_mut(path_from).unwrap();
-                            path_node_from.validate_connection(from, to, title.clone());
-                            debug!("validated connection from {from} to {to} (\"{title}\")");
-
-                            // We've updated a title, which means we need to write the from path
-                            // back to the disk (this path is guaranteed already locked)
-                            paths_to_write.insert(path_from.clone());
-                            debug!("will write to {path_from:?} after possible link title update");
+                    let Some(path_from) = nodes_ref.get(&from) else {
+                        debug!("tried to check connection from unknown node {from}");
+                        continue;
+                    };
+                    let name_entry = names.as_mut().unwrap().get_mut(&to);
+                    // Similarly, the connection itself could have been removed (or this could
+                    // come from a stale record), in which case we shouldn't touch anything
+                    if !path_nodes.get(path_from).unwrap().has_connection(from, to) {
+                        if let Some(entry) = name_entry {
+                            if let Some(old_target) = entry.bindings.remove(&from) {
+                                remove_acyclic_link(
+                                    acyclic_links.as_mut().unwrap(),
+                                    from,
+                                    old_target,
+                                );
+                            }
+                        }
+                        debug!(
+                            "tried to check connection from {from} to {to} that no longer exists"
+                        );
+                        continue;
+                    }
+
+                    // If there's no node with the ID being connected to, this might be a
+                    // connection by name, which we'll try to resolve (ignoring anything that
+                    // resolves to a node that's just been removed)
+                    let target = if nodes_ref.contains_key(&to) {
+                        Some(to)
+                    } else {
+                        name_entry
+                            .as_ref()
+                            .and_then(|entry| entry.resolve())
+                            .filter(|target| nodes_ref.contains_key(target))
+                    };
+                    // If this connection resolved to something else last time, that node
+                    // shouldn't have a backlink anymore
+                    if let Some(entry) = name_entry {
+                        match entry.bindings.remove(&from) {
+                            Some(old_target) if Some(old_target) != target => {
+                                remove_acyclic_link(
+                                    acyclic_links.as_mut().unwrap(),
+                                    from,
+                                    old_target,
+                                );
+                                if let Some(path) = nodes_ref.get(&old_target) {
+                                    path_nodes
+                                        .get_mut(path)
+                                        .unwrap()
+                                        .remove_backlink(old_target, from);
+                                    debug!("removed backlink on {old_target} from {from} (connection to {to} no longer resolves there)");
+                                }
+                            }
+                            _ => {}
+                        }
+                        if let Some(target) = target {
+                            entry.bindings.insert(from, target);
+                        }
+                    }
+
+                    // Here, if the target doesn't exist, then we should log an invalid connection
+                    // (the existence of this update means we will have a write guard on that map)
+                    if let Some((target, path_to)) =
+                        target.and_then(|target| Some((target, nodes_ref.get(&target)?)))
+                    {
+                        // Add the backlink first and get the title
+                        // We're getting the title of this node to display in our `from`
+                        // node, let's use the format of the from node so we implant a title
+                        // that makes sense (even though we're talking about insane nested
+                        // connections here...)
+                        let format = path_nodes.get(path_from).unwrap().format();
+                        let path_node_to = path_nodes.get_mut(path_to).unwrap();
+                        path_node_to.add_backlink(target, from);
+                        debug!("added backlink on {target} from {from}");
+                        // If the target's ID isn't on the disk yet, it needs to be now
+                        if path_node_to.pin_id(target) {
+                            paths_to_write
+                                .entry(path_to.clone())
+                                .or_default()
+                                .insert(WriteReason::LinkTargets);
+                            debug!(
+                                "will write to {path_to:?} to add the id of linked node {target}"
+                            );
+                        }
+
+                        let title = path_node_to
+                            .display_title(target, format)
+                            .unwrap()
+                            .join("/");
+
+                        // And then validate the connection and update the title of the target
+                        let path_node_from = path_nodes.get_mut(path_from).unwrap();
+                        path_node_from.validate_connection(from, to, target, title.clone());
+                        debug!("validated connection from {from} to {to} (\"{title}\")");
+
+                        // Now it's valid, we can check the constraints on its types
+                        let path_node_from = path_nodes.get(path_from).unwrap();
+                        let violations = path_node_from.check_constraints(
+                            from,
+                            to,
+                            (
+                                target,
+                                path_nodes.get(path_to).unwrap().raw_node(target).unwrap(),
+                            ),
+                        );
+                        let acyclic_types = path_node_from.acyclic_types(from, to);
+                        if !violations.is_empty() {
+                            debug!(
+                                "connection from {from} to {to} breaks {} constraint(s)",
+                                violations.len()
+                            );
+                        }
+                        path_nodes
+                            .get_mut(path_from)
+                            .unwrap()
+                            .set_violations(from, to, violations);
+                        let acyclic_links = acyclic_links.as_mut().unwrap();
+                        if acyclic_types.is_empty() {
+                            remove_acyclic_link(acyclic_links, from, target);
                         } else {
-                            // This instruction is used to both add knowingly to the global map,
-                            // and to invalidate locally, so do both for good measure
-                            let path_node_from = path_nodes.get_mut(path_from).unwrap();
-                            path_node_from.invalidate_connection(from, to);
-                            invalid_connections
-                                .as_mut()
-                                .unwrap()
-                                .entry(to)
-                                .or_insert_with(HashSet::new)
-                                .insert(from);
-                            debug!("recorded invalid connection from {from} to {to}");
+                            acyclic_links
+                                .entry(from)
+                                .or_default()
+                                .insert(target, acyclic_types);
                         }
+
+                        // A connection by name might have been recorded as invalid before
+                        if target != to {
+                            let invalid_connections = invalid_connections.as_mut().unwrap();
+                            if let Some(entry) = invalid_connections.get_mut(&to) {
+                                entry.referrers.remove(&from);
+                                if entry.referrers.is_empty() {
+                                    invalid_connections.remove(&to);
+                                }
+                            }
+                        }
+
+                        // We've updated a title, which means we need to write the from path
+                        // back to the disk (this path is guaranteed already locked)
+                        paths_to_write
+                            .entry(path_from.clone())
+                            .or_default()
+                            .insert(WriteReason::LinkTitles);
+                        debug!("will write to {path_from:?} after possible link title update");
                     } else {
-                        debug!("tried to check connection from unknown node {from}");
+                        // This instruction is used to both add knowingly to the global map,
+                        // and to invalidate locally, so do both for good measure
+                        let path_node_from = path_nodes.get_mut(path_from).unwrap();
+                        path_node_from.invalidate_connection(from, to);
+                        remove_acyclic_link(acyclic_links.as_mut().unwrap(), from, to);
+                        let entry = invalid_connections.as_mut().unwrap().entry(to).or_default();
+                        if entry.name.is_none() {
+                            entry.name = path_node_from.connection_name(from, to);
+                        }
+                        entry.referrers.insert(from);
+                        debug!("recorded invalid connection from {from} to {to}");
                     }
                 }
 
@@ -874,23 +1539,43 @@ impl Graph {
         // convert their documents to strings
         paths_to_write
             .into_iter()
-            .filter_map(|path| {
+            .filter_map(|(path, reasons)| {
                 let path_node = path_nodes.get(&path).unwrap();
+                // Writing a document with parts missing would delete them
+                if path_node.is_partial() {
+                    warn!("not writing to {path:?}, parts of it couldn't be parsed");
+                    return None;
+                }
                 // There most certainly should be a document currently, but for future-proofness
                 // we'll allow there not to be
-                let format = if path.extension().unwrap_or_default() == "org" {
-                    Format::Org
-                } else {
-                    Format::Markdown
-                };
+                let format = path_node.format();
                 let document = path_node.document();
                 if let Some(document) = document {
+                    let contents = strip_ids(
+                        &document.to_document(format).into_string(format),
+                        path_node.ephemeral_ids(),
+                        format,
+                    );
+                    let contents = write_ulids(&contents, path_node.ulid_ids(), format);
+                    // In lazy mode, new nodes often won't need anything written at all
+                    if STARLING_CONFIG.get().lazy_ids_for(&path)
+                        && path_node.contents() == Some(&contents)
+                    {
+                        debug!("not writing to {path:?}, nothing would change");
+                        return None;
+                    }
                     let write = Write {
                         path: path.clone(),
-                        contents: document.to_document(format).into_string(format),
+                        contents,
                         source: WriteSource::Filesystem,
                         // This will be worked out by the conflict detector later
                         conflict: Conflict::None,
+                        base: path_node.contents().map(|c| c.to_string()),
+                        reasons: {
+                            let mut reasons = reasons.into_iter().collect::<Vec<_>>();
+                            reasons.sort();
+                            reasons
+                        },
                     };
                     debug!("produced filesystem write to {path:?}");
                     Some(write)
@@ -901,4 +1586,31 @@ impl Graph {
             })
             .collect()
     }
+    /// Clears any warnings about tags the user has already approved from the given path node,
+    /// which is about to be added to the graph.
+    fn clear_approved_tags(&self, path_node: &mut PathNode) {
+        for tag in self.approved_tags.lock().iter() {
+            path_node.approve_tag(tag);
+        }
+    }
+}
+
+/// Runs the given CPU-bound closure on the current thread, letting the runtime know that it's
+/// about to block if it's able to move other tasks elsewhere in the meantime. On a
+/// single-threaded runtime (e.g. in tests), this just runs the closure.
+fn parse_in_place<R>(f: impl FnOnce() -> R) -> R {
+    match Handle::current().runtime_flavor() {
+        RuntimeFlavor::MultiThread => tokio::task::block_in_place(f),
+        _ => f(),
+    }
+}
+
+/// Removes any record of links that can't form cycles from one node to another.
+fn remove_acyclic_link(acyclic_links: &mut AcyclicLinkMap, from: Uuid, to: Uuid) {
+    if let Some(targets) = acyclic_links.get_mut(&from) {
+        targets.remove(&to);
+        if targets.is_empty() {
+            acyclic_links.remove(&from);
+        }
+    }
 }
@@ -0,0 +1,280 @@
+use crate::{
+    config::STARLING_CONFIG,
+    conflict_detector::{Conflict, Write, WriteReason, WriteSource},
+    error::ApiError,
+};
+use crossbeam_queue::SegQueue;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// The name of the file in the journal directory that entries are appended to, one JSON object
+/// per line.
+static ENTRIES_FILE: &str = "journal.jsonl";
+/// The name of the directory in the journal directory that the contents of files before and after
+/// each write are kept in, named by the ID of the entry for the write, with `.before` or `.after`.
+static OBJECTS_DIR: &str = "objects";
+
+/// An append-only record of every write Starling has made, which lets the user see what's been
+/// changed behind their back, and undo it.
+///
+/// If the journal is being persisted, entries will be kept in the journal directory, along with
+/// the contents of each file before and after each write (so they can be undone after a restart).
+/// Otherwise, everything is kept in memory. Either way, only the contents around the most recent
+/// writes are kept (see [`crate::config::Config::journal_retention`]).
+pub struct Journal {
+    /// All the entries in the journal, oldest first.
+    entries: Mutex<Vec<JournalEntry>>,
+    /// The contents of files before and after each write, keyed by their object names (see
+    /// [`object_name`]), if there's no journal directory to put them in.
+    objects: Mutex<HashMap<String, String>>,
+    /// Writes which will undo earlier writes, which haven't yet been made.
+    undos: SegQueue<Write>,
+}
+impl Journal {
+    /// Creates a new, empty [`Journal`]. Any entries persisted from earlier runs can be loaded with
+    /// [`Self::load`].
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+            objects: Mutex::new(HashMap::new()),
+            undos: SegQueue::new(),
+        }
+    }
+    /// Loads any entries persisted in the journal directory by earlier runs. Entries which can't
+    /// be parsed are skipped with a warning.
+    pub fn load(&self) {
+        let (Some(dir), retention) = ({
+            let config = STARLING_CONFIG.get();
+            (
+                config.journal_dir().map(Path::to_path_buf),
+                config.journal_retention,
+            )
+        }) else {
+            return;
+        };
+        let Ok(contents) = std::fs::read_to_string(dir.join(ENTRIES_FILE)) else {
+            return;
+        };
+        let mut entries = self.entries.lock();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => warn!("skipping invalid journal entry: {err}"),
+            }
+        }
+        info!("loaded {} journal entries", entries.len());
+        // The retention might have been lowered since the last run
+        if retention > 0 {
+            for entry in entries.iter().rev().skip(retention) {
+                self.prune(Some(&dir), entry.id);
+            }
+        }
+    }
+    /// Records that the given contents were written to the given path, which previously had the
+    /// given contents (if it existed), returning the ID of the new entry. Failing to persist the
+    /// entry won't stop it from being recorded in memory. This might touch the disk, so it
+    /// shouldn't be called from async code directly.
+    pub fn record(
+        &self,
+        path: &Path,
+        before: Option<&str>,
+        after: &str,
+        source: WriteSource,
+        reasons: Vec<WriteReason>,
+    ) -> Uuid {
+        let (dir, retention) = {
+            let config = STARLING_CONFIG.get();
+            (
+                config.journal_dir().map(Path::to_path_buf),
+                config.journal_retention,
+            )
+        };
+        let entry = JournalEntry {
+            id: Uuid::new_v4(),
+            time: chrono::Utc::now().to_rfc3339(),
+            path: path.to_path_buf(),
+            before_hash: before.map(content_hash),
+            after_hash: content_hash(after),
+            source,
+            reasons,
+        };
+        let id = entry.id;
+        if let Some(before) = before {
+            self.store_object(dir.as_deref(), &object_name(id, false), before);
+        }
+        self.store_object(dir.as_deref(), &object_name(id, true), after);
+
+        if let Some(dir) = &dir {
+            let res = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join(ENTRIES_FILE))
+                .and_then(|mut file| {
+                    // Serializing this can't fail
+                    let line = serde_json::to_string(&entry).unwrap();
+                    writeln!(file, "{line}")
+                });
+            if let Err(err) = res {
+                warn!("failed to persist journal entry {id}: {err}");
+            }
+        }
+        let mut entries = self.entries.lock();
+        entries.push(entry);
+        // Only the contents around the most recent writes are kept, so the oldest of those might
+        // have just fallen out
+        if retention > 0 && entries.len() > retention {
+            self.prune(dir.as_deref(), entries[entries.len() - retention - 1].id);
+        }
+        id
+    }
+    /// Gets the given number of most recent entries in the journal, newest first.
+    pub fn list(&self, limit: usize) -> Vec<JournalEntry> {
+        self.entries
+            .lock()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+    /// Undoes the write with the given journal entry ID by restoring the contents the path had
+    /// before it. This will be sent through the conflict detector by the filesystem engine the
+    /// next time the filesystem is quiet, and merged with any changes made to the path since the
+    /// original write.
+    pub fn undo(&self, id: Uuid) -> Result<(), ApiError> {
+        let entry = self
+            .entries
+            .lock()
+            .iter()
+            .find(|entry| entry.id == id)
+            .cloned()
+            .ok_or(ApiError::JournalEntryNotFound { id })?;
+        let Some(before_hash) = &entry.before_hash else {
+            return Err(ApiError::CannotUndo {
+                id,
+                reason: "the path didn't exist before this write".to_string(),
+            });
+        };
+        let dir = STARLING_CONFIG.get().journal_dir().map(Path::to_path_buf);
+        // Contents that don't match the hashes they were recorded with have been tampered with
+        let (Some(before), Some(after)) = (
+            self.load_object(dir.as_deref(), &object_name(id, false))
+                .filter(|before| content_hash(before) == *before_hash),
+            self.load_object(dir.as_deref(), &object_name(id, true))
+                .filter(|after| content_hash(after) == entry.after_hash),
+        ) else {
+            return Err(ApiError::CannotUndo {
+                id,
+                reason: "the contents of the path around this write are no longer available"
+                    .to_string(),
+            });
+        };
+
+        self.undos.push(Write {
+            path: entry.path,
+            contents: before,
+            source: WriteSource::Other,
+            conflict: Conflict::None,
+            base: Some(after),
+            reasons: vec![WriteReason::Undo],
+        });
+        Ok(())
+    }
+    /// Takes all the writes which will undo earlier writes requested since this was last called.
+    /// Each of these has the contents the path had after the write being undone as its base.
+    pub fn take_undos(&self) -> Vec<Write> {
+        let mut writes = Vec::new();
+        while let Some(write) = self.undos.pop() {
+            writes.push(write);
+        }
+        writes
+    }
+
+    /// Stores the given contents under the given name in the given journal directory (or in
+    /// memory if there isn't one).
+    fn store_object(&self, dir: Option<&Path>, name: &str, contents: &str) {
+        match dir {
+            Some(dir) => {
+                let res = std::fs::create_dir_all(dir.join(OBJECTS_DIR))
+                    .and_then(|_| std::fs::write(dir.join(OBJECTS_DIR).join(name), contents));
+                if let Err(err) = res {
+                    warn!("failed to persist contents {name} for journal: {err}");
+                }
+            }
+            None => {
+                self.objects
+                    .lock()
+                    .insert(name.to_string(), contents.to_string());
+            }
+        }
+    }
+    /// Removes the contents stored around the write with the given journal entry ID from the given
+    /// journal directory (or from memory if there isn't one), once it's too old to be undone.
+    fn prune(&self, dir: Option<&Path>, id: Uuid) {
+        for name in [object_name(id, false), object_name(id, true)] {
+            match dir {
+                Some(dir) => match std::fs::remove_file(dir.join(OBJECTS_DIR).join(&name)) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("failed to prune contents {name} from journal: {err}");
+                    }
+                    _ => {}
+                },
+                None => {
+                    self.objects.lock().remove(&name);
+                }
+            }
+        }
+    }
+    /// Loads the contents with the given name from the given journal directory (or from memory if
+    /// there isn't one), if they're there.
+    fn load_object(&self, dir: Option<&Path>, name: &str) -> Option<String> {
+        match dir {
+            Some(dir) => std::fs::read_to_string(dir.join(OBJECTS_DIR).join(name)).ok(),
+            None => self.objects.lock().get(name).cloned(),
+        }
+    }
+}
+
+/// A record of a single write Starling made.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// A unique identifier for this write, used to undo it.
+    pub id: Uuid,
+    /// When the write was made, as an RFC 3339 timestamp.
+    pub time: String,
+    /// The (relative) path that was written to.
+    pub path: PathBuf,
+    /// The hash of the contents of the path before the write (see [`content_hash`]), or [`None`]
+    /// if it didn't exist (in which case the write can't be undone).
+    pub before_hash: Option<String>,
+    /// The hash of the contents written to the path.
+    pub after_hash: String,
+    /// Where the write came from.
+    pub source: WriteSource,
+    /// Why the write was made.
+    pub reasons: Vec<WriteReason>,
+}
+
+/// Gets the name the contents of the path before or after the write with the given journal entry
+/// ID are stored under. Every write gets its own objects, so contents can never be mixed up
+/// between writes.
+fn object_name(id: Uuid, after: bool) -> String {
+    format!("{id}.{}", if after { "after" } else { "before" })
+}
+
+/// Hashes the given contents for the journal (with 64-bit FNV-1a, which is stable across
+/// platforms and versions, unlike the standard library's hasher).
+pub fn content_hash(contents: &str) -> String {
+    let hash = contents.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
@@ -1,5 +1,6 @@
 use config::{Config, STARLING_CONFIG};
 use error::{DirError, Error};
+use exclude::ExcludeMatcher;
 use fmterr::fmterr;
 use fs_engine::FsEngine;
 use graph::{Graph, IndexCriteria};
@@ -10,20 +11,31 @@ use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::net::TcpListener;
 use tracing::info;
 
+mod attributes;
 mod config;
 mod conflict_detector;
+mod conflict_store;
 mod connection;
+mod constraints;
 mod debouncer;
+mod diff;
 mod error;
+mod exclude;
 mod fs_engine;
 mod graph;
+mod journal;
 mod logging;
 mod node;
 mod patch;
 mod path_node;
+mod pending_writes;
+mod recovery;
+mod schema;
 mod server;
 #[cfg(test)]
 mod tests;
+mod ulid;
+mod writer;
 
 #[tokio::main]
 async fn main() {
@@ -38,11 +50,23 @@ async fn main() {
 }
 
 async fn core() -> Result<(), Error> {
-    // The user will provide a directory as the first argument
-    let dir = std::env::args()
-        .nth(1)
-        .map(PathBuf::from)
-        .ok_or(Error::NoDir)?;
+    // The user will provide a directory as the first argument, optionally along with
+    // `--dry-run`, which stops us from writing to anything in it
+    let mut dir = None;
+    let mut dry_run = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            flag if flag.starts_with("--") => {
+                return Err(Error::UnknownFlag {
+                    flag: flag.to_string(),
+                })
+            }
+            _ if dir.is_none() => dir = Some(PathBuf::from(arg)),
+            _ => (),
+        }
+    }
+    let dir = dir.ok_or(Error::NoDir)?;
     // Later functions will panic if this isn't upheld
     if !dir.is_dir() {
         return Err(DirError::InvalidDir { path: dir }.into());
@@ -52,9 +76,21 @@ async fn core() -> Result<(), Error> {
         .map_err(|err| DirError::CanonicalizeFailed { path: dir, err })?;
 
     // Set up configuration and logging (we need config to know where to log)
-    STARLING_CONFIG.set(Config::from_dir(&dir)?);
+    let mut config = Config::from_dir(&dir)?;
+    config.read_only |= dry_run;
+    STARLING_CONFIG.set(config);
     setup_logging();
 
+    // Build the matcher for paths we shouldn't track, which needs to be shared with the engine
+    let exclude = {
+        let config = STARLING_CONFIG.get();
+        Arc::new(ExcludeMatcher::new(
+            &dir,
+            &config.exclude_paths,
+            config.respect_gitignore,
+        )?)
+    };
+
     let mut indices: HashMap<String, IndexCriteria> = HashMap::new();
     indices.insert(
         "action_items".to_string(),
@@ -74,11 +110,12 @@ async fn core() -> Result<(), Error> {
     );
 
     // Any errors on each path would be accumulated into each path, so this can't fail
-    let (graph, initial_writes) = Graph::from_dir(&dir, indices).await;
+    let (graph, initial_writes) = Graph::from_dir(&dir, indices, &exclude).await;
     let graph = Arc::new(graph);
+    graph.journal.load();
 
     // Start up the filesystem processing engine and let it run forever
-    let fs_engine = FsEngine::new(graph.clone(), initial_writes);
+    let fs_engine = FsEngine::new(graph.clone(), initial_writes, exclude);
     let fs_engine_task = fs_engine.run(&dir)?;
     info!("about to start filesystem engine");
     tokio::spawn(fs_engine_task);
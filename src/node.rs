@@ -8,15 +8,19 @@ use tokio::sync::RwLockReadGuard;
 use uuid::Uuid;
 
 use crate::{
-    connection::ConnectedNode,
+    attributes::Attributes,
+    config::STARLING_CONFIG,
+    connection::{ConnectedNode, Resource},
+    error::Diagnostic,
     graph::Graph,
     path_node::{PathNode, StarlingNode},
+    schema::{self, PropertyError, PropertyValue},
 };
 
 /// A representation of all the information about a single node in the graph.
 ///
 /// The information returned can be regulated with [`NodeOptions`].
-#[derive(Serialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Debug, PartialEq)]
 pub struct Node {
     // --- Basics ---
     /// The node's unique identifier.
@@ -29,6 +33,10 @@ pub struct Node {
     pub tags: HashSet<String>,
     /// The tags on this node's parents. There will be no duplicates here.
     pub parent_tags: HashSet<String>,
+    /// All the tags this node has or inherits, with aliases replaced by the tags they stand for,
+    /// and with the ancestors of any hierarchical tags (e.g. `cs` for `cs/algorithms`). This is
+    /// what should be used to check if a node has a tag.
+    pub expanded_tags: HashSet<String>,
     /// The ID of the parent, if there is one (this will be `None` for top-level nodes).
     pub parent_id: Option<Uuid>,
 
@@ -75,11 +83,15 @@ pub struct Node {
     /// This will only be populated if both connection and child connection information is
     /// requested.
     pub child_backlinks: HashMap<Uuid, NodeConnection>,
+    /// Links from this specific node to things outside the graph, like web pages and local files.
+    ///
+    /// This will only be populated if connection information is requested.
+    pub resources: HashSet<Resource>,
 }
 
 /// Metadata about a node. This is a simplification of the representation in a [`StarlingNode`] for
 /// transmission.
-#[derive(Serialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Debug, PartialEq)]
 pub struct NodeMetadata {
     /// The level of this node (0 for a root node) in the hierarchhy of the document it came from.
     /// This is essentially the number of `#`s at the start of the node in Markdown (or `*`s in
@@ -98,11 +110,23 @@ pub struct NodeMetadata {
     pub closed: Option<Timestamp>,
     /// The properties of the node. These are totally freeform.
     pub properties: HashMap<String, String>,
+    /// The parsed values of any properties which have types declared in the configuration (either
+    /// globally or in the schema for one of this node's tags), keyed by their names as declared.
+    /// Properties whose values don't match their types won't be present here.
+    pub typed_properties: HashMap<String, PropertyValue>,
+    /// Any ways in which the properties of this node don't match the schemas that apply to it.
+    pub property_errors: Vec<PropertyError>,
+    /// Any problems found with this node while parsing its path that didn't stop it from being
+    /// parsed (e.g. unknown tags in lenient mode).
+    pub diagnostics: Vec<Diagnostic>,
     /// A keyword at the start of the node, which will be one of the ones in the global config if
     /// it's present. These are used to indicate action states, like `TODO` or `NEXT`.
     pub keyword: Option<String>,
     /// Timestamps at the end of the node.
     pub timestamps: Vec<Timestamp>,
+    /// The attributes of the document this node is in (e.g. the keys in Markdown frontmatter, or
+    /// `#+KEY:` lines in Org), with their types. This will only be populated for root nodes.
+    pub attributes: Attributes,
 }
 
 /// A self-contained representation of a connection with (either to or from) another node. This
@@ -118,7 +142,7 @@ pub struct NodeConnection {
 }
 
 /// Options that can be used to customize the information returned about a node.
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct NodeOptions {
     /// Whether or not to return the body of this node (this may be arbitrarily large).
     #[serde(default)]
@@ -144,6 +168,11 @@ pub struct NodeOptions {
     /// If this is `true` and `connections` is false, this will be treated as `false`.
     #[serde(default)]
     pub child_connections: bool,
+    /// If given, only connections and backlinks with at least one of these types will be
+    /// returned, and they'll only be reported with these types. Backlinks are filtered by their
+    /// inverse types, so this can be used to filter on links in either direction.
+    #[serde(default)]
+    pub link_types: Option<HashSet<String>>,
     /// The format links should be serialized to (Markdown or Org).
     pub conn_format: Format,
 }
@@ -156,6 +185,7 @@ impl NodeOptions {
             children: false,
             connections: false,
             child_connections: false,
+            link_types: None,
             conn_format: format,
         }
     }
@@ -179,6 +209,10 @@ impl NodeOptions {
         self.child_connections = v;
         self
     }
+    pub fn link_types(mut self, v: &[&str]) -> Self {
+        self.link_types = Some(v.iter().map(|s| s.to_string()).collect());
+        self
+    }
 }
 
 impl Graph {
@@ -228,6 +262,7 @@ impl Graph {
         // Collect metadata if requested
         let mut metadata = None;
         if options.metadata {
+            let (typed_properties, property_errors) = schema::check_properties(uuid, raw_node);
             metadata = Some(NodeMetadata {
                 level: raw_node.level(),
                 priority: raw_node.priority.0.clone(),
@@ -235,8 +270,20 @@ impl Graph {
                 scheduled: raw_node.planning.scheduled.clone(),
                 closed: raw_node.planning.closed.clone(),
                 properties: (*raw_node.properties).clone(),
+                typed_properties,
+                property_errors,
+                diagnostics: path_node
+                    .diagnostics()
+                    .into_iter()
+                    .filter(|diagnostic| diagnostic.id == Some(uuid))
+                    .collect(),
                 keyword: raw_node.keyword.clone().map(|k| k.keyword),
                 timestamps: raw_node.timestamps.clone(),
+                attributes: if raw_node.level() == 0 {
+                    document.parsed_attributes.clone()
+                } else {
+                    Attributes::new()
+                },
             });
         }
 
@@ -254,7 +301,7 @@ impl Graph {
             // We'll need to lock connections in the root
             for conn in connected_node.connections() {
                 if conn.is_valid() {
-                    nodes_to_lock.insert(conn.id());
+                    nodes_to_lock.insert(conn.target());
                 }
             }
             // And backlinks in the root
@@ -276,7 +323,7 @@ impl Graph {
                         let connected_node = connected_root.node(&child.properties.id).unwrap();
                         for conn in connected_node.connections() {
                             if conn.is_valid() {
-                                nodes_to_lock.insert(conn.id());
+                                nodes_to_lock.insert(conn.target());
                             }
                         }
                         for backlink_id in connected_node.backlinks() {
@@ -314,19 +361,19 @@ impl Graph {
             // need!
             for conn in connected_node.connections() {
                 if conn.is_valid() {
-                    let path = nodes.get(&conn.id()).unwrap();
+                    let path = nodes.get(&conn.target()).unwrap();
                     let path_node = if path == node_path {
                         &path_node
                     } else {
-                        path_refs.get(nodes.get(&conn.id()).unwrap()).unwrap()
+                        path_refs.get(nodes.get(&conn.target()).unwrap()).unwrap()
                     };
 
                     connections.insert(
-                        conn.id(),
+                        conn.target(),
                         NodeConnection {
                             // title: node.title(options.conn_format),
                             title: path_node
-                                .display_title(conn.id(), options.conn_format)
+                                .display_title(conn.target(), options.conn_format)
                                 .unwrap(),
                             types: conn.types().map(|s| s.to_string()).collect(),
                         },
@@ -358,11 +405,9 @@ impl Graph {
                         // The types of connections the node made to us can be extracted by looking at
                         // the types of the connection to our node
                         types: node
-                            .connections_map()
-                            .get(&uuid)
-                            .unwrap()
-                            .types()
-                            .map(|s| s.to_string())
+                            .connections_to(uuid)
+                            .flat_map(|conn| conn.types())
+                            .map(backlink_type)
                             .collect(),
                     },
                 );
@@ -389,11 +434,11 @@ impl Graph {
                         let connected_node = connected_root.node(&child.properties.id).unwrap();
                         for conn in connected_node.connections() {
                             if conn.is_valid() {
-                                let path = nodes.get(&conn.id()).unwrap();
+                                let path = nodes.get(&conn.target()).unwrap();
                                 let path_node = if path == node_path {
                                     path_node
                                 } else {
-                                    path_refs.get(nodes.get(&conn.id()).unwrap()).unwrap()
+                                    path_refs.get(nodes.get(&conn.target()).unwrap()).unwrap()
                                 };
                                 // We're guaranteed to have a document, because we have a connection to a node in
                                 // there
@@ -407,10 +452,10 @@ impl Graph {
                                 // is one. Note that, because we're dealing with valid connections, the
                                 // title will be the same everywhere.
                                 child_connections
-                                    .entry(conn.id())
+                                    .entry(conn.target())
                                     .or_insert_with(|| NodeConnection {
                                         title: path_node
-                                            .display_title(conn.id(), conn_format)
+                                            .display_title(conn.target(), conn_format)
                                             .unwrap(),
                                         types: HashSet::new(),
                                     })
@@ -434,11 +479,9 @@ impl Graph {
                                 .node(backlink_id)
                                 .unwrap();
                             let types = node
-                                .connections_map()
-                                .get(&child.properties.id)
-                                .unwrap()
-                                .types()
-                                .map(|s| s.to_string())
+                                .connections_to(*child.properties.id)
+                                .flat_map(|conn| conn.types())
+                                .map(backlink_type)
                                 .collect::<HashSet<_>>();
 
                             // As with the connections, we might have many backlinks from the same node
@@ -484,6 +527,20 @@ impl Graph {
             }
         }
 
+        if let Some(link_types) = &options.link_types {
+            for map in [
+                &mut connections,
+                &mut backlinks,
+                &mut child_connections,
+                &mut child_backlinks,
+            ] {
+                map.retain(|_, conn| {
+                    conn.types.retain(|ty| link_types.contains(ty));
+                    !conn.types.is_empty()
+                });
+            }
+        }
+
         // After this, all fine-grained and coarse-grained locks get safely dropped
         Some(Node {
             id: uuid,
@@ -491,6 +548,9 @@ impl Graph {
             // title: connected_node.title(options.conn_format),
             path: node_path.clone(),
             tags: raw_node.tags.iter().cloned().collect(),
+            expanded_tags: STARLING_CONFIG
+                .get()
+                .expand_tags(raw_node.tags.iter().chain(parent_tags.iter())),
             parent_tags,
             parent_id,
 
@@ -505,6 +565,17 @@ impl Graph {
             child_connections,
             backlinks,
             child_backlinks,
+            resources: if options.connections {
+                connected_node.resources().clone()
+            } else {
+                HashSet::new()
+            },
         })
     }
 }
+
+/// Gets the type a backlink should be reported with, given the type of the connection it comes
+/// from (i.e. its inverse, if it has one).
+fn backlink_type(ty: &str) -> String {
+    STARLING_CONFIG.get().inverse_link_type(ty).to_string()
+}
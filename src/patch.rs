@@ -1,4 +1,8 @@
-use crate::debouncer::{DebouncedEvents, Event};
+use crate::{
+    config::STARLING_CONFIG,
+    debouncer::{DebouncedEvents, Event},
+    exclude::ExcludeMatcher,
+};
 use futures::{
     future::{join, join_all},
     Future,
@@ -34,7 +38,12 @@ impl GraphPatch {
     /// applied as a CPU-bound task. In essence, this does all the I/O that might be needed.
     ///
     /// Any errors in reading from a particular path will be stored as errors in the patch output.
-    pub async fn from_events(events: DebouncedEvents, dir: &Path) -> Self {
+    /// Creations and modifications on paths excluded by the given matcher will be ignored.
+    pub async fn from_events(
+        events: DebouncedEvents,
+        dir: &Path,
+        exclude: &ExcludeMatcher,
+    ) -> Self {
         let mut creations_futs = Vec::new();
         let mut modifications_futs = Vec::new();
         let mut renames = Vec::new();
@@ -51,12 +60,12 @@ impl GraphPatch {
                 match event {
                     Event::Delete(_) => deletions.push(new_path),
                     Event::Create(_) => {
-                        if let Some(patch_fut) = PathPatch::new(new_path, dir) {
+                        if let Some(patch_fut) = PathPatch::new(new_path, dir, exclude) {
                             creations_futs.push(patch_fut);
                         }
                     }
                     Event::Modify(_) => {
-                        if let Some(patch_fut) = PathPatch::new(new_path, dir) {
+                        if let Some(patch_fut) = PathPatch::new(new_path, dir, exclude) {
                             modifications_futs.push(patch_fut);
                         }
                     }
@@ -93,13 +102,19 @@ impl PathPatch {
     /// know where that is.
     ///
     /// This will return [`None`] if the path doesn't need a patch constructed from it (i.e. if it
-    /// isn't one of the types of files we track, or if it isn't a file at all).
-    #[tracing::instrument]
-    pub fn new(path: PathBuf, dir: &Path) -> Option<impl Future<Output = PathPatch>> {
+    /// isn't one of the types of files we track, if it isn't a file at all, or if it's excluded).
+    #[tracing::instrument(skip(exclude))]
+    pub fn new(
+        path: PathBuf,
+        dir: &Path,
+        exclude: &ExcludeMatcher,
+    ) -> Option<impl Future<Output = PathPatch>> {
         // We are the only tikme this path is *actually* used for the filesystem!
         let full_path = dir.join(&path);
-        let ext = full_path.extension().unwrap_or_default();
-        if (ext == "org" || ext == "md" || ext == "markdown") && full_path.is_file() {
+        if STARLING_CONFIG.get().format_for(&path).is_some()
+            && full_path.is_file()
+            && !exclude.is_excluded(&path, false)
+        {
             Some(async move {
                 // Read the contents
                 let contents_res = tokio::fs::read_to_string(&full_path).await;
@@ -1,9 +1,16 @@
+use crate::attributes::{parse_org_attributes, split_frontmatter, Attributes, MarkdownFrontmatter};
 use crate::graph::{GraphUpdate, IndexCriteria};
-use crate::{config::STARLING_CONFIG, connection::ConnectedDocument, error::PathParseError};
+use crate::{
+    config::{Config, IdScheme, TitleFallback, STARLING_CONFIG},
+    connection::{ConnectedDocument, Resource},
+    constraints::{self, AcyclicLinkMap, ConstraintViolation, ViolationKind},
+    error::{Diagnostic, PathParseError, PathParseWarning, Span},
+    recovery::{self, LineMap},
+    schema::{self, PropertyError},
+};
 use orgish::{Document, ForceUuidId, Format, Keyword, Node as OrgishNode};
-use serde::Deserialize;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 /// A single path in a directory tracked by a Starling instance. This path is an Org/Markdown file,
@@ -14,16 +21,36 @@ pub struct PathNode {
     /// A parsed version of the document found at this path. If an error occurred during parsing,
     /// this might be [`None`].
     document: Option<ConnectedDocument>,
+    /// The format the document at this path is parsed in. This is resolved from the path by the
+    /// configuration, and kept from the last parse if the path is renamed to something the
+    /// configuration can't resolve.
+    format: Format,
     /// The IDs of all the nodes at this path.
     ///
     /// We use a [`HashSet`] for this so we can easily compare which nodes have been
     /// added/removed when we update a path.
     node_ids: HashSet<Uuid>,
+    /// The IDs of the nodes at this path which weren't on the disk, and were derived from their
+    /// positions instead, which nothing has needed yet. These will be left out when the document
+    /// is written back (see [`Config::lazy_ids`]).
+    ephemeral_ids: HashSet<Uuid>,
+    /// The IDs of the nodes at this path which are written as ULIDs. Orgish only understands
+    /// UUIDs, so these are converted on the way in, and back again when the document is written.
+    ulid_ids: HashSet<Uuid>,
 
     /// An error that might have occurred while parsing this path. If this is present along with a
     /// `document`, the document will be the last successfully parsed version of the document, and
     /// this will be the latest error occurred on subsequent attempts to parse the document.
     pub error: Option<PathParseError>,
+    /// Any warnings from the last successful parse of this path. These apply to the current
+    /// `document`.
+    pub warnings: Vec<PathParseWarning>,
+    /// Whether or not the current `document` is missing parts of the file that couldn't be
+    /// parsed. If so, writing it back would lose those parts, so we won't.
+    partial: bool,
+    /// The contents of the file the current `document` was parsed from, which writes computed
+    /// from the document can be merged against if the file changes before they're made.
+    contents: Option<String>,
 }
 
 impl PathNode {
@@ -38,10 +65,19 @@ impl PathNode {
         // This is an invalid state (both `None`s), but one will be fixed immediately by
         // `.update()`
         let dummy = Self {
+            format: STARLING_CONFIG
+                .get()
+                .format_for(&path)
+                .unwrap_or(Format::Markdown),
             path: path.clone(),
             document: None,
             node_ids: HashSet::new(),
+            ephemeral_ids: HashSet::new(),
+            ulid_ids: HashSet::new(),
             error: None,
+            warnings: Vec::new(),
+            partial: false,
+            contents: None,
         };
         let (path_node, updates) = dummy.update(path, contents_res, index_checkers);
         (path_node, updates)
@@ -65,7 +101,7 @@ impl PathNode {
                 for conn in removed_node.connections() {
                     if conn.is_valid() {
                         updates.push(GraphUpdate::RemoveBacklink {
-                            on: conn.id(),
+                            on: conn.target(),
                             // The backlink is from the removed node, and found on the target of this
                             // connection
                             from: *removed_node_id,
@@ -92,6 +128,20 @@ impl PathNode {
                         index: index_name.clone(),
                     });
                 }
+                // And that it can't be linked to by name anymore
+                for name in removed_node.names() {
+                    updates.push(GraphUpdate::RemoveName {
+                        id: *removed_node_id,
+                        name: *name,
+                    });
+                }
+                // And that it's no longer recorded as citing any resources
+                for resource in removed_node.resources() {
+                    updates.push(GraphUpdate::RemoveResource {
+                        id: *removed_node_id,
+                        resource: resource.key(&self.path),
+                    });
+                }
 
                 // And then instruct the removal of the node entirely
                 updates.push(GraphUpdate::RemoveNode(*removed_node_id))
@@ -110,29 +160,32 @@ impl PathNode {
     /// accordingly. This returns the new [`PathNode`] and a series of updates to the rest of the
     /// graph.
     ///
-    /// This expects the given path to have the extension `.org`, `.md`, or `.markdown`.
+    /// This expects the given path to have one of the extensions tracked in the configuration.
     pub fn update(
         &self,
         path: PathBuf,
         contents_res: Result<String, std::io::Error>,
         index_checkers: &Vec<(IndexCriteria, String)>,
     ) -> (PathNode, Vec<GraphUpdate>) {
+        let format = STARLING_CONFIG
+            .get()
+            .format_for(&path)
+            .unwrap_or(self.format);
         let mut new_self = PathNode {
             path: path.clone(),
             node_ids: self.node_ids.clone(),
+            ephemeral_ids: self.ephemeral_ids.clone(),
+            ulid_ids: self.ulid_ids.clone(),
             document: None,
+            format,
             error: None,
+            warnings: Vec::new(),
+            partial: false,
+            contents: None,
         };
 
         match contents_res {
             Ok(contents) => {
-                // We're guaranteed to have one of `org`, `md`, or `markdown` as our extensions
-                let format = if path.extension().unwrap_or_default() == "org" {
-                    Format::Org
-                } else {
-                    Format::Markdown
-                };
-
                 match self._update(&mut new_self, path, contents, index_checkers, format) {
                     Ok(updates) => {
                         new_self.error = None;
@@ -142,6 +195,9 @@ impl PathNode {
                         new_self.error = Some(err);
                         // Unfortunately, we have to do this
                         new_self.document = self.document.clone();
+                        new_self.warnings = self.warnings.clone();
+                        new_self.partial = self.partial;
+                        new_self.contents = self.contents.clone();
 
                         (new_self, Vec::new())
                     }
@@ -152,6 +208,9 @@ impl PathNode {
                 new_self.error = Some(PathParseError::ReadFailed { path, err });
                 // Unfortunately, we have to do this
                 new_self.document = self.document.clone();
+                new_self.warnings = self.warnings.clone();
+                new_self.partial = self.partial;
+                new_self.contents = self.contents.clone();
 
                 (new_self, Vec::new())
             }
@@ -161,6 +220,11 @@ impl PathNode {
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
+    /// Gets the format the document at this path is parsed in, which should also be used to
+    /// write it back.
+    pub fn format(&self) -> Format {
+        self.format
+    }
     /// Returns the display title of the node with the given ID in this path, if it exists.
     pub fn display_title(&self, id: Uuid, conn_format: Format) -> Option<Vec<String>> {
         let root_node = &self.document()?.root;
@@ -184,6 +248,16 @@ impl PathNode {
     pub fn ids(&self) -> impl Iterator<Item = &Uuid> {
         self.node_ids.iter()
     }
+    /// Gets an iterator of all the resources cited by nodes in this path, along with the IDs of
+    /// the nodes citing them.
+    pub fn resources(&self) -> impl Iterator<Item = (Uuid, &Resource)> {
+        self.node_ids.iter().flat_map(|id| {
+            self.document()
+                .and_then(|doc| doc.root.node(id))
+                .into_iter()
+                .flat_map(move |node| node.resources().iter().map(move |res| (*id, res)))
+        })
+    }
     /// Adds a backlink to the node in this path with the given ID, coming from the other node with
     /// the given ID. If the requested node to which the backlink should be added is not present in
     /// this path, this will do nothing.
@@ -220,24 +294,185 @@ impl PathNode {
         }
     }
     /// Renders the connection from the node in this path with the given ID to the other node with
-    /// the given ID as valid, and updates its title to be the provided string.
+    /// the given ID as valid, and updates its title to be the provided string. The target is the
+    /// node the connection resolved to, which will be the same as `to` unless this is a
+    /// connection by name.
     ///
     /// For clarity, this does not *check* that the connection is valid, it simply sets it as
     /// valid.
-    pub fn validate_connection(&mut self, from: Uuid, to: Uuid, to_title: String) {
+    pub fn validate_connection(&mut self, from: Uuid, to: Uuid, target: Uuid, to_title: String) {
+        if let Some(node) = self
+            .document
+            .as_mut()
+            .and_then(|doc| doc.root.node_mut(&from))
+        {
+            node.validate_connection(to, target, to_title);
+        }
+    }
+    /// Checks the connection from the node in this path with the given ID to the given target
+    /// node (with its ID) against the constraints on its types, returning any it breaks. If the
+    /// node or the connection don't exist, this will return no violations.
+    pub fn check_constraints(
+        &self,
+        from: Uuid,
+        to: Uuid,
+        target: (Uuid, &StarlingNode),
+    ) -> Vec<ConstraintViolation> {
+        let Some(root) = self.document.as_ref().map(|doc| &doc.root) else {
+            return Vec::new();
+        };
+        let (Some(from_node), Some(conn)) = (
+            root.raw_node(&from),
+            root.node(&from)
+                .and_then(|node| node.connections_map().get(&to)),
+        ) else {
+            return Vec::new();
+        };
+
+        constraints::check_connection(conn.types().into_iter(), (from, from_node), target)
+    }
+    /// Records the constraints the connection from the node in this path with the given ID to the
+    /// node with the given ID breaks.
+    pub fn set_violations(&mut self, from: Uuid, to: Uuid, violations: Vec<ConstraintViolation>) {
         if let Some(node) = self
             .document
             .as_mut()
             .and_then(|doc| doc.root.node_mut(&from))
         {
-            node.validate_connection(to, to_title);
+            node.set_violations(to, violations);
         }
     }
+    /// Gets the title or alias the connection from the node in this path with the given ID to
+    /// the given key was written with, if it's a connection by name.
+    pub fn connection_name(&self, from: Uuid, to: Uuid) -> Option<String> {
+        self.document
+            .as_ref()
+            .and_then(|doc| doc.root.node(&from))
+            .and_then(|node| node.connections().find(|conn| conn.id() == to))
+            .and_then(|conn| conn.name().map(|name| name.to_string()))
+    }
+    /// Gets the types of the connection from the node in this path with the given ID to the node
+    /// with the given ID which can't form cycles.
+    pub fn acyclic_types(&self, from: Uuid, to: Uuid) -> HashSet<String> {
+        self.document
+            .as_ref()
+            .and_then(|doc| doc.root.node(&from))
+            .and_then(|node| node.connections_map().get(&to))
+            .map(|conn| constraints::acyclic_types(conn.types().into_iter()))
+            .unwrap_or_default()
+    }
+    /// Gets all the constraints broken by the valid connections from nodes in this path. Cycles
+    /// are found using the given map of links that can't form them.
+    pub fn violations(&self, links: &AcyclicLinkMap) -> Vec<ConstraintViolation> {
+        let Some(root) = self.document.as_ref().map(|doc| &doc.root) else {
+            return Vec::new();
+        };
+
+        let mut violations = Vec::new();
+        for id in &self.node_ids {
+            let Some(node) = root.node(id) else {
+                continue;
+            };
+            for conn in node.connections().filter(|conn| conn.is_valid()) {
+                violations.extend(conn.violations().iter().cloned());
+                for ty in constraints::acyclic_types(conn.types()) {
+                    if constraints::in_cycle(links, &ty, *id, conn.target()) {
+                        violations.push(ConstraintViolation {
+                            from: *id,
+                            to: conn.target(),
+                            ty,
+                            kind: ViolationKind::Cycle,
+                        });
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+    /// Gets all the ways in which the properties of nodes in this path don't match the schemas
+    /// that apply to them.
+    pub fn property_errors(&self) -> Vec<PropertyError> {
+        let Some(root) = self.document.as_ref().map(|doc| &doc.root) else {
+            return Vec::new();
+        };
+
+        self.node_ids
+            .iter()
+            .filter_map(|id| Some((*id, root.raw_node(id)?)))
+            .flat_map(|(id, node)| schema::check_properties(id, node).1)
+            .collect()
+    }
+    /// Gets the diagnostics for all the warnings from the last successful parse of this path,
+    /// most serious first.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self
+            .warnings
+            .iter()
+            .map(|warning| warning.to_diagnostic())
+            .collect::<Vec<_>>();
+        diagnostics.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.id.cmp(&b.id)));
+
+        diagnostics
+    }
+    /// Gets any tags used in this path that aren't in the configuration (which will only be
+    /// allowed in lenient mode).
+    pub fn unknown_tags(&self) -> impl Iterator<Item = &String> {
+        self.warnings.iter().filter_map(|warning| match warning {
+            PathParseWarning::UnknownTag { tag, .. } => Some(tag),
+            _ => None,
+        })
+    }
+    /// Removes any warnings about the given tag being unknown, which should be called once the
+    /// user has approved it.
+    pub fn approve_tag(&mut self, tag: &str) {
+        self.warnings.retain(
+            |warning| !matches!(warning, PathParseWarning::UnknownTag { tag: t, .. } if t == tag),
+        );
+    }
+    /// Returns the raw node with the given ID in this path, if it exists. This will have no title
+    /// or body.
+    pub fn raw_node(&self, id: Uuid) -> Option<&StarlingNode> {
+        self.document.as_ref()?.root.raw_node(&id)
+    }
+    /// Returns whether or not the node in this path with the given ID has any connections with
+    /// the given ID. If the node doesn't exist, this will return `false`.
+    pub fn has_connection(&self, from: Uuid, to: Uuid) -> bool {
+        self.document
+            .as_ref()
+            .and_then(|doc| doc.root.node(&from))
+            .is_some_and(|node| node.has_connection(&to))
+    }
 
+    /// Returns whether or not the current document is missing parts of the file which couldn't
+    /// be parsed, in which case it shouldn't be written back.
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+    /// Gets the contents of the file the current document was parsed from, if there is one.
+    pub fn contents(&self) -> Option<&str> {
+        self.contents.as_deref()
+    }
     /// Gets the root document of this path, if there is one.
     pub fn document(&self) -> Option<&ConnectedDocument> {
         self.document.as_ref()
     }
+    /// Gets the IDs of the nodes at this path which aren't on the disk, and shouldn't be written
+    /// to it.
+    pub fn ephemeral_ids(&self) -> &HashSet<Uuid> {
+        &self.ephemeral_ids
+    }
+    /// Marks the ID of the node with the given ID as needed (e.g. because something links to it),
+    /// so it'll be written to the disk with the rest of the document from now on. This returns
+    /// whether or not the ID wasn't going to be written before, in which case the path should be
+    /// written back.
+    pub fn pin_id(&mut self, id: Uuid) -> bool {
+        self.ephemeral_ids.remove(&id)
+    }
+    /// Gets the IDs of the nodes at this path which are written as ULIDs.
+    pub fn ulid_ids(&self) -> &HashSet<Uuid> {
+        &self.ulid_ids
+    }
 
     /// Internal helper function for updating that returns any errors that occur. This is intended
     /// for ergonomically handling errors that occur in the case where reading was successful.
@@ -249,115 +484,386 @@ impl PathNode {
         index_checkers: &Vec<(IndexCriteria, String)>,
         format: Format,
     ) -> Result<Vec<GraphUpdate>, PathParseError> {
-        // Parse as a basic document first
-        let mut document = StarlingDocument::from_str(&contents, format).map_err(|err| {
-            PathParseError::DocumentParseFailed {
-                path: path.clone(),
-                format,
-                err,
-            }
-        })?;
-
-        // Parse the format-specific attributes to extract a title and tags for the root
-        let (title, tags) =
-            match format {
-                // TODO: Support more than just YAML?
-                Format::Markdown => {
-                    let attributes = if document.attributes.starts_with("---")
-                        && document.attributes.ends_with("---")
-                    {
-                        // Remove the frontmatter delimiters
-                        document.attributes[3..document.attributes.len() - 3].to_string()
-                    } else {
-                        return Err(PathParseError::FrontmatterNotYaml {
-                            path: path.to_path_buf(),
-                        });
-                    };
-                    let frontmatter: MarkdownFrontmatter = serde_yaml::from_str(&attributes)
-                        .map_err(|err| PathParseError::InvalidFrontmatter {
-                            path: path.to_path_buf(),
-                            err,
-                        })?;
-                    (frontmatter.title, frontmatter.tags)
-                }
-                Format::Org => {
-                    let mut title = None;
-                    let mut tags: Option<Vec<String>> = None;
-                    for line in document.attributes.lines() {
-                        if line.to_lowercase().starts_with("#+title: ") {
-                            title = Some(line.split_once(": ").unwrap().1);
-                        }
-                        if line.to_lowercase().starts_with("#+tags: ")
-                            || line.to_lowercase().starts_with("#+filetags: ")
-                        {
-                            let tags_str = line.split_once(": ").unwrap().1;
-                            // Tags can be delimited like `:hello:world:test:` or `hello world test`
-                            // or `hello, world, test`. Helpfully, none of the delimiter characters are
-                            // allowed within tags, so we can just split on all of them at once and go
-                            // from there.
-                            tags = Some(
-                                tags_str
-                                    .split(|c| c == ':' || c == ' ' || c == ',')
-                                    .filter(|s| !s.is_empty())
-                                    .map(|s| s.to_string())
-                                    .collect(),
-                            );
+        // IDs written as ULIDs are converted to UUIDs first, since that's all Orgish can parse, and
+        // they'll be put back when the document is written (the original contents are kept for
+        // merging against)
+        let original = contents;
+        let (contents, mut ulid_ids) = read_ulids(&original, format);
+        // Orgish only understands YAML frontmatter, so split off any other style ourselves and
+        // implant it as the raw attributes afterward (these are written back verbatim, so the
+        // style will be preserved)
+        let (text, extra_attributes) = match format {
+            Format::Markdown => match split_frontmatter(&contents) {
+                Some((attributes, rest)) => (rest, Some(attributes)),
+                None => (contents.as_str(), None),
+            },
+            Format::Org => (contents.as_str(), None),
+        };
+        let mut line_map = LineMap::offset(contents[..contents.len() - text.len()].lines().count());
+        // Parse as a basic document first, and if that fails, try again without any subtrees
+        // that can't be parsed on their own (this path won't be written to until it's fixed,
+        // otherwise they'd be lost)
+        let mut warnings = Vec::new();
+        let mut recovered_text = None;
+        let mut document = match StarlingDocument::from_str(text, format) {
+            Ok(document) => document,
+            Err(err) => match recovery::recover(text, format, &line_map) {
+                Ok(recovered) => {
+                    warnings.extend(recovered.skipped.into_iter().map(|span| {
+                        PathParseWarning::SubtreeSkipped {
+                            path: path.clone(),
+                            span,
                         }
-                    }
+                    }));
+                    line_map = recovered.map;
+                    new_self.partial = true;
+                    recovered_text = Some(recovered.contents);
+                    recovered.document
+                }
+                Err(span) => {
+                    return Err(PathParseError::DocumentParseFailed {
+                        path: path.clone(),
+                        format,
+                        span,
+                        err,
+                    })
+                }
+            },
+        };
+        let text = recovered_text.as_deref().unwrap_or(text);
+        if let Some(attributes) = extra_attributes {
+            document.attributes = attributes.to_string();
+        }
 
-                    if title.is_none() {
-                        return Err(PathParseError::OrgNoTitle {
-                            path: path.to_path_buf(),
-                        });
+        // Parse the format-specific attributes to extract a title and tags for the root, along
+        // with everything else in them
+        let (title, tags, attributes) = match format {
+            // No frontmatter at all is fine, we'll try to infer a title
+            Format::Markdown if document.attributes.trim().is_empty() => {
+                (None, None, Attributes::new())
+            }
+            Format::Markdown => {
+                // Frontmatter is always at the very start of the file
+                let span = recovery::block_span(&contents, 0, document.attributes.lines().count());
+                let frontmatter = MarkdownFrontmatter::parse(&document.attributes)
+                    .ok_or_else(|| PathParseError::UnknownFrontmatter {
+                        path: path.to_path_buf(),
+                        span,
+                    })?
+                    .map_err(|err| PathParseError::InvalidFrontmatter {
+                        path: path.to_path_buf(),
+                        span,
+                        err: Box::new(err),
+                    })?;
+                (frontmatter.title, frontmatter.tags, frontmatter.attributes)
+            }
+            Format::Org => {
+                let mut title = None;
+                let mut tags: Option<Vec<String>> = None;
+                for line in document.attributes.lines() {
+                    if line.to_lowercase().starts_with("#+title: ") {
+                        title = Some(line.split_once(": ").unwrap().1);
+                    }
+                    if line.to_lowercase().starts_with("#+tags: ")
+                        || line.to_lowercase().starts_with("#+filetags: ")
+                    {
+                        let tags_str = line.split_once(": ").unwrap().1;
+                        // Tags can be delimited like `:hello:world:test:` or `hello world test`
+                        // or `hello, world, test`. Helpfully, none of the delimiter characters are
+                        // allowed within tags, so we can just split on all of them at once and go
+                        // from there.
+                        tags = Some(
+                            tags_str
+                                .split(|c| c == ':' || c == ' ' || c == ',')
+                                .filter(|s| !s.is_empty())
+                                .map(|s| s.to_string())
+                                .collect(),
+                        );
                     }
-                    (title.unwrap().to_string(), tags)
                 }
-            };
+
+                (
+                    title.map(|t| t.to_string()),
+                    tags,
+                    parse_org_attributes(&document.attributes),
+                )
+            }
+        };
         // Resolve `None` to `Vec::new()`
         let tags = tags.unwrap_or_default();
+        // If there wasn't a title, go through the configured fallbacks, warning on the first that
+        // works (so the user knows where the title came from)
+        let title = match title {
+            Some(title) => title,
+            None => {
+                let (title, fallback) = STARLING_CONFIG
+                    .get()
+                    .title_fallbacks
+                    .iter()
+                    .find_map(|fallback| {
+                        let title = match fallback {
+                            TitleFallback::Heading => document
+                                .root
+                                .children()
+                                .first()
+                                .map(|heading| heading.title.clone()),
+                            TitleFallback::Filename => path
+                                .file_stem()
+                                .map(|stem| stem.to_string_lossy().to_string()),
+                        };
+                        title
+                            .filter(|title| !title.trim().is_empty())
+                            .map(|title| (title, *fallback))
+                    })
+                    .ok_or_else(|| PathParseError::NoTitle {
+                        path: path.to_path_buf(),
+                    })?;
+                warnings.push(PathParseWarning::TitleInferred {
+                    path: path.to_path_buf(),
+                    title: title.clone(),
+                    fallback,
+                });
+
+                title
+            }
+        };
 
         // Implant the title and tags back into the document so we can parse more easily
         document.root.title = title;
         *document.root.tags = tags;
 
+        // Orgish gave random IDs to any nodes without them on the disk, but in lazy mode they get
+        // IDs derived from where they are instead, so they're stable without being written, and
+        // otherwise they get IDs in whatever scheme the user wants
+        struct Derivation<'a> {
+            path: &'a Path,
+            format: Format,
+            lazy: bool,
+            scheme: IdScheme,
+            /// The original contents of the file, lowercased so IDs written in any case can be
+            /// found.
+            source: String,
+            /// The IDs of all the nodes at this path the last time it was parsed.
+            old_ids: &'a HashSet<Uuid>,
+            /// The IDs of the nodes at this path which were ephemeral the last time it was parsed.
+            old_ephemeral_ids: &'a HashSet<Uuid>,
+            /// The position of the node being traversed, in the order they're traversed.
+            position: usize,
+            /// The IDs of the nodes which have been found to be ephemeral so far.
+            ephemeral_ids: HashSet<Uuid>,
+            /// The IDs of the nodes which are written as ULIDs, which new ones will join if that's
+            /// the scheme.
+            ulid_ids: &'a mut HashSet<Uuid>,
+        }
+        fn derive_ids(node: &mut StarlingNode, derivation: &mut Derivation) {
+            if !derivation
+                .source
+                .contains(&(*node.properties.id).to_string())
+            {
+                let id = if derivation.lazy {
+                    ephemeral_id(derivation.path, derivation.position)
+                } else {
+                    derivation.scheme.generate()
+                };
+                node.properties.id = id_for(id, derivation.format);
+                if derivation.scheme == IdScheme::Ulid {
+                    derivation.ulid_ids.insert(id);
+                }
+                // If something's needed this ID already, it's on its way to the disk
+                let pinned =
+                    derivation.old_ids.contains(&id) && !derivation.old_ephemeral_ids.contains(&id);
+                if derivation.lazy && !pinned {
+                    derivation.ephemeral_ids.insert(id);
+                }
+            }
+            // Perfectly safe, we aren't modifying the levels of any children
+            for child in node.unchecked_mut_children() {
+                derivation.position += 1;
+                derive_ids(child, derivation);
+            }
+        }
+        let (lazy, scheme) = {
+            let config = STARLING_CONFIG.get();
+            (config.lazy_ids_for(&path), config.id_scheme)
+        };
+        let ephemeral_ids = if lazy || scheme != IdScheme::default() {
+            let mut derivation = Derivation {
+                path: &path,
+                format,
+                lazy,
+                scheme,
+                source: contents.to_lowercase(),
+                old_ids: &self.node_ids,
+                old_ephemeral_ids: &self.ephemeral_ids,
+                position: 0,
+                ephemeral_ids: HashSet::new(),
+                ulid_ids: &mut ulid_ids,
+            };
+            derive_ids(&mut document.root, &mut derivation);
+            derivation.ephemeral_ids
+        } else {
+            HashSet::new()
+        };
+
         // Recursively iterate through the whole document and do the following:
         //  - Find any invalid tags
         //  - Accumulate all IDs
+        //
+        // In lenient mode, problems are recorded as warnings instead, and duplicate IDs are
+        // replaced so the nodes can still be told apart.
+        struct Traversal<'a> {
+            lenient: bool,
+            format: Format,
+            path: &'a PathBuf,
+            /// The original contents of the file.
+            source: &'a str,
+            /// The lines in the original file each node covers (excluding its children), in the
+            /// order they're traversed, if they could be worked out.
+            node_lines: Option<Vec<(usize, usize)>>,
+            /// The index of the node being traversed.
+            idx: usize,
+            /// The IDs of the nodes which are written as ULIDs.
+            ulid_ids: &'a mut HashSet<Uuid>,
+        }
+        impl Traversal<'_> {
+            /// Gets the span of the given text in the node currently being traversed.
+            fn span(&self, needle: &str) -> Option<Span> {
+                let (start, end) = *self.node_lines.as_ref()?.get(self.idx)?;
+                recovery::find_span(self.source, start, end, needle)
+            }
+        }
         fn traverse(
-            node: &StarlingNode,
-            valid_tags: &[String],
-            path: PathBuf,
+            node: &mut StarlingNode,
+            traversal: &mut Traversal,
             ids: &mut HashSet<Uuid>,
+            warnings: &mut Vec<PathParseWarning>,
         ) -> Result<(), PathParseError> {
             // Make sure this ID hasn't been accounted for before in this path (doesn't check
             // against the rest of the graph)
             if !ids.insert(*node.properties.id) {
-                return Err(PathParseError::InternalDuplicateId {
-                    path,
-                    id: *node.properties.id,
+                let id = *node.properties.id;
+                let span = traversal.span(&id.to_string());
+                if !traversal.lenient {
+                    return Err(PathParseError::InternalDuplicateId {
+                        path: traversal.path.clone(),
+                        id,
+                        span,
+                    });
+                }
+                node.properties.id = fresh_id(traversal.format, traversal.ulid_ids);
+                ids.insert(*node.properties.id);
+                warnings.push(PathParseWarning::DuplicateId {
+                    path: traversal.path.clone(),
+                    id,
+                    new_id: *node.properties.id,
+                    span,
                 });
             }
 
             for tag in node.tags.iter() {
-                if valid_tags.iter().all(|t| t != tag) {
-                    return Err(PathParseError::InvalidTag {
-                        path,
+                if !STARLING_CONFIG.get().is_valid_tag(tag) {
+                    let span = traversal.span(tag);
+                    if !traversal.lenient {
+                        return Err(PathParseError::InvalidTag {
+                            path: traversal.path.clone(),
+                            tag: tag.to_string(),
+                            span,
+                        });
+                    }
+                    warnings.push(PathParseWarning::UnknownTag {
+                        path: traversal.path.clone(),
+                        id: *node.properties.id,
                         tag: tag.to_string(),
+                        span,
                     });
                 }
             }
-            for child in node.children() {
-                traverse(child, valid_tags, path.clone(), ids)?;
+            // Perfectly safe, we aren't modifying the levels of any children
+            for child in node.unchecked_mut_children() {
+                traversal.idx += 1;
+                traverse(child, traversal, ids, warnings)?;
             }
             Ok(())
         }
-        let valid_tags = &STARLING_CONFIG.get().tags;
+        fn count_nodes(node: &StarlingNode) -> usize {
+            1 + node.children().iter().map(count_nodes).sum::<usize>()
+        }
+        // The root covers everything before the first heading, including any frontmatter
+        let total_lines = contents.lines().count();
+        let node_starts = std::iter::once(0)
+            .chain(
+                recovery::heading_lines(text, format)
+                    .into_iter()
+                    .map(|(line, _)| line_map.original_line(line)),
+            )
+            .collect::<Vec<_>>();
+        let node_lines = (node_starts.len() == count_nodes(&document.root)).then(|| {
+            node_starts
+                .iter()
+                .enumerate()
+                .map(|(idx, start)| {
+                    (
+                        *start,
+                        node_starts.get(idx + 1).copied().unwrap_or(total_lines),
+                    )
+                })
+                .collect()
+        });
         let mut node_ids = HashSet::new();
-        traverse(&document.root, valid_tags, path.clone(), &mut node_ids)?;
+        let mut traversal = Traversal {
+            lenient: STARLING_CONFIG.get().lenient,
+            format,
+            path: &path,
+            source: &contents,
+            node_lines,
+            idx: 0,
+            ulid_ids: &mut ulid_ids,
+        };
+        traverse(
+            &mut document.root,
+            &mut traversal,
+            &mut node_ids,
+            &mut warnings,
+        )?;
+
+        // If we've been asked to, replace any tag aliases on headings with the tags they stand for
+        // (the root's tags come from the attributes, which are written back verbatim, so there's
+        // no point changing those)
+        fn normalize_tags(node: &mut StarlingNode, config: &Config) -> bool {
+            let mut changed = false;
+            for tag in node.tags.iter_mut() {
+                if let Some(canonical) = config.tag_aliases.get(tag) {
+                    *tag = canonical.clone();
+                    changed = true;
+                }
+            }
+            for child in node.unchecked_mut_children() {
+                changed |= normalize_tags(child, config);
+            }
+            changed
+        }
+        let root_id = *document.root.properties.id;
+        let tags_normalized = {
+            let config = STARLING_CONFIG.get();
+            config.normalize_tag_aliases
+                && document
+                    .root
+                    .unchecked_mut_children()
+                    .iter_mut()
+                    .fold(false, |changed, child| {
+                        normalize_tags(child, &config) | changed
+                    })
+        };
 
         // Parse connections for the whole document (this will also check which indices each node
         // is in)
-        let mut connected_doc = ConnectedDocument::from_document(document, index_checkers, format);
+        let mut connected_doc = ConnectedDocument::from_document(
+            document,
+            attributes,
+            &ulid_ids,
+            index_checkers,
+            format,
+        );
 
         // If we're updating from a previous version of the document, we should transfer connection
         // information over (i.e. retained connections that were originally valid should remain
@@ -375,7 +881,7 @@ impl PathNode {
                 for conn in removed_node.connections() {
                     if conn.is_valid() {
                         updates.push(GraphUpdate::RemoveBacklink {
-                            on: conn.id(),
+                            on: conn.target(),
                             // The backlink is from the removed node, and found on the target of this
                             // connection
                             from: *removed_node_id,
@@ -402,6 +908,20 @@ impl PathNode {
                         index: index_name.clone(),
                     });
                 }
+                // And that it can't be linked to by name anymore
+                for name in removed_node.names() {
+                    updates.push(GraphUpdate::RemoveName {
+                        id: *removed_node_id,
+                        name: *name,
+                    });
+                }
+                // And that it's no longer recorded as citing any resources
+                for resource in removed_node.resources() {
+                    updates.push(GraphUpdate::RemoveResource {
+                        id: *removed_node_id,
+                        resource: resource.key(&self.path),
+                    });
+                }
 
                 // And then instruct the removal of the node entirely
                 updates.push(GraphUpdate::RemoveNode(*removed_node_id));
@@ -430,8 +950,32 @@ impl PathNode {
                         index: index_name.clone(),
                     });
                 }
+                // And let it be linked to by name
+                for name in new_node.names() {
+                    updates.push(GraphUpdate::AddName {
+                        id: *new_node_id,
+                        name: *name,
+                    });
+                }
+                for resource in new_node.resources() {
+                    updates.push(GraphUpdate::AddResource {
+                        id: *new_node_id,
+                        resource: resource.key(&path),
+                    });
+                }
             }
+            let constrained = !STARLING_CONFIG.get().link_constraints.is_empty();
             for retained_node_id in node_ids.intersection(&self.node_ids) {
+                // If there are link constraints, changes to the tags or keyword of this node could
+                // change which of the connections to and from it break them
+                let constraints_changed = constrained && {
+                    let old_raw = old_doc.root.raw_node(retained_node_id).unwrap();
+                    let new_raw = connected_doc.root.raw_node(retained_node_id).unwrap();
+                    !old_raw.tags.iter().eq(new_raw.tags.iter())
+                        || old_raw.keyword.as_ref().map(|k| &k.keyword)
+                            != new_raw.keyword.as_ref().map(|k| &k.keyword)
+                };
+
                 // This node was retained, let's check over the connections to transfer over
                 // validities
                 let new_node = connected_doc.root.node_mut(retained_node_id).unwrap();
@@ -439,18 +983,28 @@ impl PathNode {
                 // We'll need to remove things from this map to see what's left over at the end, so
                 // let's clone it
                 let mut old_node_connections = old_node.connections_map().clone();
+                // New connections will be checked after the old ones are removed, so a backlink
+                // that's removed and re-added (e.g. when a link by name is rewritten to use the ID
+                // it resolved to) is kept
+                let mut checks = Vec::new();
 
                 for mut new_conn in new_node.connections_mut() {
-                    if let Some(valid) = old_node_connections
-                        .remove(&new_conn.id())
-                        .map(|conn| conn.valid())
-                    {
+                    if let Some(old_conn) = old_node_connections.remove(&new_conn.id()) {
                         // A connection to this target was also in the old node, we can inherit its
                         // validity (if valid, that won't change except by a removal, which would
                         // be processed after this). We'll leave the types and titles as they are
                         // in the new version.
-                        if valid {
+                        if old_conn.valid() {
                             new_conn.set_valid(true);
+                            new_conn.set_resolved(old_conn.resolved());
+                            new_conn.set_violations(old_conn.violations().to_vec());
+                            // The constraints on the connection might be different now though
+                            if constraints_changed || old_conn.types() != new_conn.types() {
+                                checks.push(GraphUpdate::CheckConnection {
+                                    from: *retained_node_id,
+                                    to: new_conn.id(),
+                                });
+                            }
                         } else {
                             // This was invalid in the previous version, it will only become valid
                             // if it was to a node which was just created, in which case it was
@@ -460,7 +1014,7 @@ impl PathNode {
                         }
                     } else {
                         // This connection is new, we'll need to validate it explicitly
-                        updates.push(GraphUpdate::CheckConnection {
+                        checks.push(GraphUpdate::CheckConnection {
                             from: *retained_node_id,
                             to: new_conn.id(),
                         })
@@ -472,7 +1026,7 @@ impl PathNode {
                     // If this connection was valid, we should remove the backlink
                     if raw_conn.valid() {
                         updates.push(GraphUpdate::RemoveBacklink {
-                            on: id,
+                            on: raw_conn.resolved().unwrap_or(id),
                             from: *retained_node_id,
                         })
                     } else {
@@ -483,6 +1037,7 @@ impl PathNode {
                         })
                     }
                 }
+                updates.extend(checks);
 
                 // Transfer all the backlinks over (graph updates from other updated nodes will
                 // tell us which to heed)
@@ -494,12 +1049,13 @@ impl PathNode {
                 // as well); it doesn't matter which format we use for this
                 let old_title = old_node.title(Format::Markdown);
                 let new_title = new_node.title(Format::Markdown);
-                if old_title != new_title {
-                    // The title has changed, we should revalidate all connections from other nodes
-                    // to this one (i.e. the backlinks). We don't have all the backlinks that
-                    // *will* be present, but we don't need to, because any new ones will see the
-                    // new title anyway. We only care about the existing ones, and including ones
-                    // that end up invalid is fine, because they'll be invalidated anyway!
+                if old_title != new_title || constraints_changed {
+                    // The title (or something link constraints depend on) has changed, we should
+                    // revalidate all connections from other nodes to this one (i.e. the
+                    // backlinks). We don't have all the backlinks that *will* be present, but we
+                    // don't need to, because any new ones will see the new title anyway. We only
+                    // care about the existing ones, and including ones that end up invalid is
+                    // fine, because they'll be invalidated anyway!
                     for backlink_id in new_node.backlinks() {
                         updates.push(GraphUpdate::CheckConnection {
                             from: *backlink_id,
@@ -526,6 +1082,42 @@ impl PathNode {
                         index: added_index_name.clone(),
                     });
                 }
+                // And likewise for the names it can be linked to by
+                for removed_name in old_node.names().difference(new_node.names()) {
+                    updates.push(GraphUpdate::RemoveName {
+                        id: *retained_node_id,
+                        name: *removed_name,
+                    });
+                }
+                for added_name in new_node.names().difference(old_node.names()) {
+                    updates.push(GraphUpdate::AddName {
+                        id: *retained_node_id,
+                        name: *added_name,
+                    });
+                }
+                // And the resources it cites
+                let old_resources = old_node
+                    .resources()
+                    .iter()
+                    .map(|resource| resource.key(&self.path))
+                    .collect::<HashSet<_>>();
+                let new_resources = new_node
+                    .resources()
+                    .iter()
+                    .map(|resource| resource.key(&path))
+                    .collect::<HashSet<_>>();
+                for removed_resource in old_resources.difference(&new_resources) {
+                    updates.push(GraphUpdate::RemoveResource {
+                        id: *retained_node_id,
+                        resource: removed_resource.clone(),
+                    });
+                }
+                for added_resource in new_resources.difference(&old_resources) {
+                    updates.push(GraphUpdate::AddResource {
+                        id: *retained_node_id,
+                        resource: added_resource.clone(),
+                    });
+                }
             }
         } else {
             // This is the first version of the document, we'll issue node adding and connection
@@ -544,6 +1136,18 @@ impl PathNode {
                         index: index_name.clone(),
                     });
                 }
+                for name in node.names() {
+                    updates.push(GraphUpdate::AddName {
+                        id: *node_id,
+                        name: *name,
+                    });
+                }
+                for resource in node.resources() {
+                    updates.push(GraphUpdate::AddResource {
+                        id: *node_id,
+                        resource: resource.key(&path),
+                    });
+                }
                 for conn in node.connections() {
                     updates.push(GraphUpdate::CheckConnection {
                         from: *node_id,
@@ -553,18 +1157,155 @@ impl PathNode {
             }
         }
 
+        // Any aliases we replaced should be replaced on the disk too
+        if tags_normalized {
+            updates.push(GraphUpdate::RewritePath {
+                id: root_id,
+                path: path.clone(),
+            });
+        }
+
         // Actually update everything in-place!
         new_self.document = Some(connected_doc);
         new_self.node_ids = node_ids;
+        new_self.ephemeral_ids = ephemeral_ids;
+        new_self.ulid_ids = ulid_ids;
+        new_self.warnings = warnings;
+        new_self.contents = Some(original);
 
         Ok(updates)
     }
 }
 
-#[derive(Deserialize)]
-struct MarkdownFrontmatter {
-    title: String,
-    tags: Option<Vec<String>>,
+/// Generates a new ID for a node, in the configured scheme, recording it in the given IDs written
+/// as ULIDs if it's one of those.
+fn fresh_id(format: Format, ulid_ids: &mut HashSet<Uuid>) -> ForceUuidId {
+    let scheme = STARLING_CONFIG.get().id_scheme;
+    let id = scheme.generate();
+    if scheme == IdScheme::Ulid {
+        ulid_ids.insert(id);
+    }
+    id_for(id, format)
+}
+
+/// Creates an Orgish ID for the given UUID. Orgish won't let us create these directly, so we get
+/// it to do this for us, by parsing an empty document whose root has the ID.
+fn id_for(id: Uuid, format: Format) -> ForceUuidId {
+    let text = match format {
+        Format::Markdown => format!("<!--PROPERTIES\nID: {id}\n-->\n"),
+        Format::Org => format!(":PROPERTIES:\n:ID: {id}\n:END:\n"),
+    };
+    StarlingDocument::from_str(&text, format)
+        .expect("empty document with an id should always parse")
+        .root
+        .properties
+        .id
+}
+
+/// Derives an ID for the node at the given position (in traversal order, where the root is at
+/// zero) in the given path, for nodes without IDs on the disk in lazy mode. This is a 128-bit
+/// FNV-1a hash of the two as a version 8 UUID, so it'll be the same every time the path is parsed,
+/// as long as the node doesn't move.
+fn ephemeral_id(path: &Path, position: usize) -> Uuid {
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .chain(std::iter::once(0))
+        .chain((position as u64).to_le_bytes())
+        .fold(0x6c62272e07bb014262b821756295c58d_u128, |hash, byte| {
+            (hash ^ byte as u128).wrapping_mul(0x0000000001000000000000000000013b)
+        });
+    Uuid::new_v8(hash.to_be_bytes())
+}
+
+/// Removes the given IDs from the given contents of a document in the given format, which were
+/// written by Orgish. Property drawers left empty by this are removed too.
+pub fn strip_ids(contents: &str, ids: &HashSet<Uuid>, format: Format) -> String {
+    if ids.is_empty() {
+        return contents.to_string();
+    }
+    let (open, close, prefix) = match format {
+        Format::Markdown => ("<!--PROPERTIES", "-->", "ID:"),
+        Format::Org => (":PROPERTIES:", ":END:", ":ID:"),
+    };
+    let is_stripped = |line: &str| {
+        line.trim()
+            .strip_prefix(prefix)
+            .and_then(|id| Uuid::parse_str(id.trim()).ok())
+            .is_some_and(|id| ids.contains(&id))
+    };
+
+    let lines = contents.split_inclusive('\n').collect::<Vec<_>>();
+    let mut stripped = String::with_capacity(contents.len());
+    let mut idx = 0;
+    while idx < lines.len() {
+        // A drawer with nothing but an ID we're removing in it should go entirely
+        if lines[idx].trim() == open
+            && lines.get(idx + 1).is_some_and(|line| is_stripped(line))
+            && lines.get(idx + 2).is_some_and(|line| line.trim() == close)
+        {
+            idx += 3;
+            continue;
+        }
+        if !is_stripped(lines[idx]) {
+            stripped.push_str(lines[idx]);
+        }
+        idx += 1;
+    }
+    stripped
+}
+
+/// Rewrites the IDs in the given contents of a document in the given format which are written as
+/// ULIDs as UUIDs, so Orgish can parse them, returning the new contents and those IDs.
+pub fn read_ulids(contents: &str, format: Format) -> (String, HashSet<Uuid>) {
+    let mut ids = HashSet::new();
+    let contents = map_ids(contents, format, |id| {
+        let id = crate::ulid::decode(id)?;
+        ids.insert(id);
+        Some(id.to_string())
+    });
+    (contents, ids)
+}
+
+/// Rewrites the given IDs in the given contents of a document in the given format, which were
+/// written by Orgish, as ULIDs. This undoes [`read_ulids`].
+pub fn write_ulids(contents: &str, ids: &HashSet<Uuid>, format: Format) -> String {
+    if ids.is_empty() {
+        return contents.to_string();
+    }
+    map_ids(contents, format, |id| {
+        Uuid::parse_str(id)
+            .ok()
+            .filter(|id| ids.contains(id))
+            .map(crate::ulid::encode)
+    })
+}
+
+/// Replaces the IDs in the given contents of a document in the given format with whatever the
+/// given function returns for them, leaving those it returns [`None`] for alone.
+fn map_ids(contents: &str, format: Format, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let prefix = match format {
+        Format::Markdown => "ID:",
+        Format::Org => ":ID:",
+    };
+    let mut mapped = String::with_capacity(contents.len());
+    for line in contents.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start().len();
+        let new_id = line[indent..]
+            .strip_prefix(prefix)
+            .and_then(|rest| f(rest.trim()).map(|new_id| (rest, new_id)));
+        match new_id {
+            Some((rest, new_id)) => {
+                let id = rest.trim();
+                let start = line.len() - rest.len() + (rest.len() - rest.trim_start().len());
+                mapped.push_str(&line[..start]);
+                mapped.push_str(&new_id);
+                mapped.push_str(&line[start + id.len()..]);
+            }
+            None => mapped.push_str(line),
+        }
+    }
+    mapped
 }
 
 /// The Orgish documents used in Starling, based heavily off the global configuration.
@@ -0,0 +1,113 @@
+use crate::{
+    conflict_detector::{Write, WriteReason},
+    diff::unified_diff,
+    error::ApiError,
+};
+use crossbeam_queue::SegQueue;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// A store of the writes Starling would have made if it weren't in read-only mode. These are held
+/// until the user decides to apply or discard each of them, and the ones they apply are handed back
+/// to the [`crate::fs_engine::FsEngine`].
+pub struct PendingWrites {
+    /// All the writes which haven't been applied or discarded, in the order they were computed.
+    writes: Mutex<Vec<PendingWrite>>,
+    /// Writes which the user has chosen to apply, which haven't yet been made.
+    approved: SegQueue<PendingWrite>,
+}
+impl PendingWrites {
+    /// Creates a new, empty [`PendingWrites`] store.
+    pub fn new() -> Self {
+        Self {
+            writes: Mutex::new(Vec::new()),
+            approved: SegQueue::new(),
+        }
+    }
+    /// Records the given write, which wasn't made, returning the ID of the new pending write. The
+    /// contents the write was computed from (its base) are kept so it can be merged with any
+    /// changes made to the path before it's applied.
+    ///
+    /// Any writes already pending for the same path will be dropped, because the new write will
+    /// have been computed from more recent contents.
+    pub fn record(&self, write: Write) -> Uuid {
+        let name = write.path.to_string_lossy();
+        let diff = unified_diff(
+            write.base.as_deref().unwrap_or(""),
+            &write.contents,
+            &format!("disk/{name}"),
+            &format!("starling/{name}"),
+        );
+        let pending = PendingWrite {
+            id: Uuid::new_v4(),
+            path: write.path,
+            contents: write.contents,
+            diff,
+            reasons: write.reasons,
+            base: write.base,
+        };
+        let id = pending.id;
+
+        let mut writes = self.writes.lock();
+        writes.retain(|w| w.path != pending.path);
+        writes.push(pending);
+        id
+    }
+    /// Gets all the writes which haven't been applied or discarded, in the order they were
+    /// computed.
+    pub fn list(&self) -> Vec<PendingWrite> {
+        self.writes.lock().clone()
+    }
+    /// Applies the pending write with the given ID. It will be made by the filesystem engine the
+    /// next time the filesystem is quiet, even in read-only mode, and merged with any changes made
+    /// to the path since it was computed.
+    pub fn apply(&self, id: Uuid) -> Result<(), ApiError> {
+        let write = self.take(id)?;
+        self.approved.push(write);
+        Ok(())
+    }
+    /// Discards the pending write with the given ID, so it will never be made.
+    pub fn discard(&self, id: Uuid) -> Result<(), ApiError> {
+        self.take(id).map(|_| ())
+    }
+    /// Takes all the writes the user has chosen to apply since this was last called.
+    pub fn take_approved(&self) -> Vec<PendingWrite> {
+        let mut writes = Vec::new();
+        while let Some(write) = self.approved.pop() {
+            writes.push(write);
+        }
+        writes
+    }
+
+    /// Removes the pending write with the given ID from the store.
+    fn take(&self, id: Uuid) -> Result<PendingWrite, ApiError> {
+        let mut writes = self.writes.lock();
+        let idx = writes
+            .iter()
+            .position(|w| w.id == id)
+            .ok_or(ApiError::PendingWriteNotFound { id })?;
+        Ok(writes.remove(idx))
+    }
+}
+
+/// A write which wasn't made because Starling is in read-only mode.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingWrite {
+    /// A unique identifier for this write, used to apply or discard it.
+    pub id: Uuid,
+    /// The (relative) path the write would go to.
+    pub path: PathBuf,
+    /// The contents Starling would write.
+    pub contents: String,
+    /// A unified diff from the contents this write was computed from to the contents Starling
+    /// would write.
+    pub diff: String,
+    /// Why Starling would make this write.
+    pub reasons: Vec<WriteReason>,
+    /// The contents this write was computed from, if the path existed, which the write will be
+    /// merged against if it's applied later.
+    #[serde(skip)]
+    pub base: Option<String>,
+}
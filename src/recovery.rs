@@ -0,0 +1,192 @@
+use crate::{
+    error::{Position, Span},
+    path_node::StarlingDocument,
+};
+use orgish::Format;
+
+/// A mapping from lines in a piece of text that was parsed to the lines they came from in the
+/// original file, which might be different if parts of the file were left out.
+#[derive(Clone, Debug)]
+pub struct LineMap {
+    /// The (0-indexed) line in the parsed text at which each unbroken segment of the file starts,
+    /// and the line in the original file that corresponds to.
+    segments: Vec<(usize, usize)>,
+}
+impl LineMap {
+    /// Creates a map for text that's the whole of the original file after the given number of
+    /// lines (e.g. frontmatter split off before parsing).
+    pub fn offset(lines: usize) -> Self {
+        Self {
+            segments: vec![(0, lines)],
+        }
+    }
+    /// Gets the (0-indexed) line in the original file that the given line in the parsed text
+    /// came from.
+    pub fn original_line(&self, line: usize) -> usize {
+        let (parsed, original) = self
+            .segments
+            .iter()
+            .rev()
+            .find(|(parsed, _)| *parsed <= line)
+            .unwrap_or(&(0, 0));
+        original + (line - parsed)
+    }
+}
+
+/// A document which couldn't be parsed as a whole, but which could be once the subtrees that
+/// couldn't be parsed on their own were left out.
+pub struct Recovered {
+    /// The recovered document.
+    pub document: StarlingDocument,
+    /// The contents of the document, without the subtrees that were left out.
+    pub contents: String,
+    /// A mapping from lines in the recovered contents back to lines in the original file.
+    pub map: LineMap,
+    /// The spans of the subtrees that were left out.
+    pub skipped: Vec<Span>,
+}
+
+/// Attempts to recover the given document contents (which will have been found not to parse) by
+/// splitting them into the top-level subtrees of headings and leaving out any of those which
+/// can't be parsed on their own. The given line map should map the contents back to the original
+/// file.
+///
+/// If the document can't be recovered, this will return the span of the first part of it which
+/// couldn't be parsed, if that could be found.
+pub fn recover(contents: &str, format: Format, map: &LineMap) -> Result<Recovered, Option<Span>> {
+    let lines = contents.split_inclusive('\n').collect::<Vec<_>>();
+    let headings = heading_lines(contents, format);
+    // The shallowest headings are the roots of the subtrees we'll try on their own (usually these
+    // will be top-level, but a document doesn't have to have any top-level headings)
+    let top_level = headings.iter().map(|(_, level)| *level).min();
+    let mut bounds = vec![0];
+    bounds.extend(
+        headings
+            .iter()
+            .filter(|(_, level)| Some(*level) == top_level)
+            .map(|(line, _)| *line)
+            .filter(|line| *line != 0),
+    );
+    bounds.push(lines.len());
+
+    let mut recovered = String::new();
+    let mut segments = Vec::new();
+    let mut skipped = Vec::new();
+    let mut recovered_lines = 0;
+    for (idx, chunk_lines) in bounds.windows(2).enumerate() {
+        let (start, end) = (chunk_lines[0], chunk_lines[1]);
+        if start == end {
+            continue;
+        }
+        let chunk = lines[start..end].concat();
+        if StarlingDocument::from_str(&chunk, format).is_ok() {
+            segments.push((recovered_lines, map.original_line(start)));
+            recovered.push_str(&chunk);
+            recovered_lines += end - start;
+        } else {
+            let span = lines_span(&lines, start, end, map);
+            // Everything before the first heading belongs to the root, which we can't leave out
+            if idx == 0 && start < headings.first().map(|(line, _)| *line).unwrap_or(end) {
+                return Err(Some(span));
+            }
+            skipped.push(span);
+        }
+    }
+
+    // If every part parsed on its own, we can't say where the problem is
+    if skipped.is_empty() {
+        return Err(None);
+    }
+    let document =
+        StarlingDocument::from_str(&recovered, format).map_err(|_| skipped.first().copied())?;
+
+    Ok(Recovered {
+        document,
+        contents: recovered,
+        map: LineMap { segments },
+        skipped,
+    })
+}
+
+/// Finds the (0-indexed) lines of all the headings in the given document contents, along with
+/// their levels, in the order they appear (which is the order Orgish will parse them in).
+pub fn heading_lines(contents: &str, format: Format) -> Vec<(usize, usize)> {
+    let marker = match format {
+        Format::Org => '*',
+        Format::Markdown => '#',
+    };
+    let mut in_fence = false;
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            // Markdown code blocks can contain lines that look like headings
+            if format == Format::Markdown && (line.starts_with("```") || line.starts_with("~~~")) {
+                in_fence = !in_fence;
+                return None;
+            }
+            if in_fence {
+                return None;
+            }
+
+            let level = line.chars().take_while(|c| *c == marker).count();
+            (level > 0 && line[level..].starts_with([' ', '\t'])).then_some((idx, level))
+        })
+        .collect()
+}
+
+/// Finds the first occurrence of the given text in the given (0-indexed) range of lines in the
+/// original file, falling back to the whole of the first line in the range if it can't be found.
+pub fn find_span(original: &str, start: usize, end: usize, needle: &str) -> Option<Span> {
+    let lines = original.lines().collect::<Vec<_>>();
+    let first = *lines.get(start)?;
+    for (idx, line) in lines[start..end.min(lines.len())].iter().enumerate() {
+        if let Some(byte_idx) = line.find(needle) {
+            let column = line[..byte_idx].chars().count() + 1;
+            return Some(Span {
+                start: Position {
+                    line: start + idx + 1,
+                    column,
+                },
+                end: Position {
+                    line: start + idx + 1,
+                    column: column + needle.chars().count(),
+                },
+            });
+        }
+    }
+
+    Some(Span {
+        start: Position {
+            line: start + 1,
+            column: 1,
+        },
+        end: Position {
+            line: start + 1,
+            column: first.chars().count() + 1,
+        },
+    })
+}
+
+/// Gets the span covering the given (0-indexed) range of lines in the original file, if there are
+/// any lines in it.
+pub fn block_span(original: &str, start: usize, end: usize) -> Option<Span> {
+    let lines = original.split_inclusive('\n').collect::<Vec<_>>();
+    (start < end && end <= lines.len()).then(|| lines_span(&lines, start, end, &LineMap::offset(0)))
+}
+
+/// Gets the span covering the given (0-indexed) range of the given lines, mapped back to the
+/// original file.
+fn lines_span(lines: &[&str], start: usize, end: usize, map: &LineMap) -> Span {
+    let last = lines[end - 1].trim_end_matches(['\r', '\n']);
+    Span {
+        start: Position {
+            line: map.original_line(start) + 1,
+            column: 1,
+        },
+        end: Position {
+            line: map.original_line(end - 1) + 1,
+            column: last.chars().count() + 1,
+        },
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    config::{PropertyType, STARLING_CONFIG},
+    path_node::StarlingNode,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The parsed value of a property with a declared type.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Date(NaiveDate),
+    /// A duration, in minutes.
+    Duration(u64),
+    List(Vec<String>),
+    Enum(String),
+}
+
+/// A way in which a node's properties don't match the schemas that apply to it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PropertyError {
+    /// The node with the bad property.
+    pub id: Uuid,
+    /// The name of the property, as it was declared in the schema.
+    pub property: String,
+    /// What's wrong with the property.
+    pub kind: PropertyErrorKind,
+}
+/// The problems a property can have.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyErrorKind {
+    /// The property is required for nodes with the given tag, but it wasn't present.
+    Missing { tag: String },
+    /// The property's value couldn't be parsed as the type it was declared as.
+    Invalid { value: String },
+}
+
+impl PropertyType {
+    /// Parses the given raw property value as this type, returning [`None`] if it isn't valid.
+    pub fn parse(&self, raw: &str) -> Option<PropertyValue> {
+        let raw = raw.trim();
+        Some(match self {
+            Self::String => PropertyValue::String(raw.to_string()),
+            Self::Int => PropertyValue::Int(raw.parse().ok()?),
+            Self::Float => PropertyValue::Float(raw.parse().ok()?),
+            Self::Bool => PropertyValue::Bool(raw.parse().ok()?),
+            Self::Date => {
+                // Org timestamps look like `<2024-01-01 Mon>` or `[2024-01-01 Mon 10:00]`
+                let date = raw
+                    .trim_start_matches(['<', '['])
+                    .split_whitespace()
+                    .next()?
+                    .trim_end_matches(['>', ']']);
+                PropertyValue::Date(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?)
+            }
+            Self::Duration => PropertyValue::Duration(parse_duration(raw)?),
+            Self::List => PropertyValue::List(
+                raw.split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            ),
+            Self::Enum(variants) => {
+                PropertyValue::Enum(variants.iter().find(|v| v.as_str() == raw)?.clone())
+            }
+        })
+    }
+}
+
+/// Parses a duration in minutes from either `H:MM` or a series of amounts with units (`min`, `h`,
+/// `d`, or `w`), like `1h 30min` or `2d`.
+fn parse_duration(raw: &str) -> Option<u64> {
+    if let Some((hours, minutes)) = raw.split_once(':') {
+        let minutes: u64 = minutes.parse().ok().filter(|m| *m < 60)?;
+        return Some(hours.parse::<u64>().ok()? * 60 + minutes);
+    }
+
+    let mut total = 0;
+    let mut rest = raw.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let multiplier = match &rest[..unit_len] {
+            "m" | "min" | "mins" => 1,
+            "h" | "hr" | "hrs" => 60,
+            "d" => 60 * 24,
+            "w" => 60 * 24 * 7,
+            _ => return None,
+        };
+        total += amount * multiplier;
+        rest = rest[unit_len..].trim_start();
+    }
+
+    Some(total)
+}
+
+/// Checks the properties of the given node against the property types and tag schemas in the
+/// global configuration, returning the parsed values of all the properties with types, and any
+/// errors. Property names are matched case-insensitively, as in Org.
+pub fn check_properties(
+    id: Uuid,
+    node: &StarlingNode,
+) -> (HashMap<String, PropertyValue>, Vec<PropertyError>) {
+    let config = STARLING_CONFIG.get();
+    let get = |name: &str| {
+        node.properties
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    };
+
+    // Types from tag schemas take precedence over global ones
+    let mut types = config
+        .property_types
+        .iter()
+        .map(|(name, ty)| (name.to_uppercase(), (name, ty)))
+        .collect::<HashMap<_, _>>();
+    let mut errors = Vec::new();
+    let mut tags = config
+        .expand_tags(node.tags.iter())
+        .into_iter()
+        .collect::<Vec<_>>();
+    // Make sure more specific schemas are applied after the more general ones they refine
+    tags.sort_unstable();
+    for tag in tags {
+        let Some(schema) = config.tag_schemas.get(&tag) else {
+            continue;
+        };
+        types.extend(
+            schema
+                .types
+                .iter()
+                .map(|(name, ty)| (name.to_uppercase(), (name, ty))),
+        );
+        for name in &schema.required {
+            if get(name).is_none() {
+                errors.push(PropertyError {
+                    id,
+                    property: name.clone(),
+                    kind: PropertyErrorKind::Missing { tag: tag.clone() },
+                });
+            }
+        }
+    }
+
+    let mut values = HashMap::new();
+    for (name, ty) in types.into_values() {
+        let Some(raw) = get(name) else {
+            continue;
+        };
+        match ty.parse(raw) {
+            Some(value) => {
+                values.insert(name.clone(), value);
+            }
+            None => errors.push(PropertyError {
+                id,
+                property: name.clone(),
+                kind: PropertyErrorKind::Invalid { value: raw.clone() },
+            }),
+        }
+    }
+    errors.sort_by(|a, b| a.property.cmp(&b.property));
+
+    (values, errors)
+}
@@ -1,13 +1,21 @@
-use crate::{config::STARLING_CONFIG, error::DirError, graph::Graph, node::NodeOptions};
+use crate::{
+    config::STARLING_CONFIG,
+    conflict_store::Resolution,
+    error::{ApiError, Diagnostic, DirError, ErrorReport},
+    graph::{Graph, InvalidConnection},
+    node::NodeOptions,
+};
 use axum::{
-    extract::{Path, Query, State},
-    response::IntoResponse,
-    routing::get,
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Path, Query, Request, State},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
 use chrono::NaiveDate;
 use orgish::Timestamp;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     path::{Path as StdPath, PathBuf},
     sync::Arc,
@@ -22,6 +30,103 @@ struct QueryOptions {
     use_bincode: bool,
 }
 
+#[derive(Deserialize)]
+struct JournalOptions {
+    /// The maximum number of entries to return, newest first.
+    #[serde(default = "default_journal_limit")]
+    limit: usize,
+}
+fn default_journal_limit() -> usize {
+    100
+}
+
+#[derive(Deserialize)]
+struct CitationsOptions {
+    /// The resource to get the citers of, which, being a URL or a path, can't go in the path of
+    /// the request.
+    resource: String,
+}
+
+/// A JSON request body, which will be rejected with a structured error if it's invalid.
+struct ApiJson<T>(T);
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for ApiJson<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) =
+            Json::<T>::from_request(req, state)
+                .await
+                .map_err(|err| ApiError::BadOptions {
+                    message: err.body_text(),
+                })?;
+        Ok(Self(value))
+    }
+}
+
+/// Parameters in the path of a request, which will be rejected with a structured error if they're
+/// invalid (like [`ApiJson`]).
+struct ApiPath<T>(T);
+#[async_trait]
+impl<T: DeserializeOwned + Send, S: Send + Sync> FromRequestParts<S> for ApiPath<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| ApiError::BadOptions {
+                message: err.body_text(),
+            })?;
+        Ok(Self(value))
+    }
+}
+
+/// A query string, which will be rejected with a structured error if it's invalid (like
+/// [`ApiJson`]).
+struct ApiQuery<T>(T);
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for ApiQuery<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| ApiError::BadOptions {
+                message: err.body_text(),
+            })?;
+        Ok(Self(value))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::NodeNotFound { .. }
+            | ApiError::UnresolvedNodeId { .. }
+            | ApiError::PathNotFound { .. }
+            | ApiError::ConflictNotFound { .. }
+            | ApiError::PendingWriteNotFound { .. }
+            | ApiError::JournalEntryNotFound { .. }
+            | ApiError::TagNotPending { .. } => StatusCode::NOT_FOUND,
+            ApiError::BadOptions { .. } => StatusCode::BAD_REQUEST,
+            ApiError::InvalidResolution { .. } | ApiError::CannotUndo { .. } => {
+                StatusCode::CONFLICT
+            }
+        };
+        (status, Json(self.report())).into_response()
+    }
+}
+
+/// The problems with a single path, as reported by `/errors/:path`.
+#[derive(Serialize)]
+struct PathErrors {
+    /// Either the error that stopped the path from being parsed, or all the invalid connections in
+    /// it (see [`Graph::errors`]).
+    errors: Result<Vec<InvalidConnection>, ErrorReport>,
+    /// Problems which didn't stop the path from being parsed.
+    diagnostics: Vec<Diagnostic>,
+}
+
 /// Creates the Axum app for serving over the network, using the given [`Graph`] and root path,
 /// which *must* have been canonicalized.
 pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
@@ -41,34 +146,228 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
         .route(
             "/node/:id",
             get(
-                |Path(id): Path<Uuid>,
-                 Query(QueryOptions { use_bincode }): Query<QueryOptions>,
+                |ApiPath(id): ApiPath<String>,
+                 ApiQuery(QueryOptions { use_bincode }): ApiQuery<QueryOptions>,
                  State(graph): State<Arc<Graph>>,
-                 Json(opts): Json<NodeOptions>| async move {
-                    let node_info = graph.get_node(id, opts).await;
-                    if use_bincode {
+                 ApiJson(opts): ApiJson<NodeOptions>| async move {
+                    let id = graph
+                        .resolve_id(&id)
+                        .await
+                        .ok_or(ApiError::UnresolvedNodeId { id })?;
+                    let node_info = graph
+                        .get_node(id, opts)
+                        .await
+                        .ok_or(ApiError::NodeNotFound { id })?;
+                    Ok::<_, ApiError>(if use_bincode {
                         bincode::serialize(&node_info).unwrap().into_response()
                     } else {
                         Json(node_info).into_response()
-                    }
+                    })
                 },
             ),
         )
         .route(
             "/errors/:path",
             get(
-                |Path(path): Path<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
                     let errors = graph.errors(&path).await;
-                    Json(errors)
+                    let diagnostics = graph.diagnostics(&path).await;
+                    let (errors, diagnostics) = errors
+                        .zip(diagnostics)
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(PathErrors {
+                        errors,
+                        diagnostics,
+                    }))
+                },
+            ),
+        )
+        .route(
+            "/errors/:path/resources",
+            get({
+                let dir = dir.to_path_buf();
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                    let dangling = graph
+                        .dangling_resources(&path, &dir)
+                        .await
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(dangling))
+                }
+            }),
+        )
+        .route(
+            "/errors/:path/constraints",
+            get(
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                    let violations = graph
+                        .constraint_violations(&path)
+                        .await
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(violations))
+                },
+            ),
+        )
+        .route(
+            "/errors/:path/properties",
+            get(
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                    let errors = graph
+                        .property_errors(&path)
+                        .await
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(errors))
+                },
+            ),
+        )
+        .route(
+            "/citations",
+            get(
+                |ApiQuery(CitationsOptions { resource }): ApiQuery<CitationsOptions>,
+                 State(graph): State<Arc<Graph>>| async move {
+                    let citations = graph.citations(&resource).await;
+                    Json(citations)
+                },
+            ),
+        )
+        .route(
+            "/tagged/:tag",
+            get(
+                |ApiPath(tag): ApiPath<String>,
+                 ApiQuery(QueryOptions { use_bincode }): ApiQuery<QueryOptions>,
+                 State(graph): State<Arc<Graph>>,
+                 ApiJson(opts): ApiJson<NodeOptions>| async move {
+                    let nodes = graph.tagged(&tag, opts).await;
+                    if use_bincode {
+                        bincode::serialize(&nodes).unwrap().into_response()
+                    } else {
+                        Json(nodes).into_response()
+                    }
+                },
+            ),
+        )
+        .route(
+            "/tags/pending",
+            get(|State(graph): State<Arc<Graph>>| async move {
+                let tags = graph.pending_tags().await;
+                Json(tags)
+            }),
+        )
+        .route(
+            "/tags/pending/:tag",
+            post(
+                |ApiPath(tag): ApiPath<String>, State(graph): State<Arc<Graph>>| async move {
+                    graph.approve_tag(&tag).await?;
+                    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+                },
+            ),
+        )
+        .route(
+            "/conflicts",
+            get(|State(graph): State<Arc<Graph>>| async move {
+                let conflicts = graph.conflicts.list();
+                Json(conflicts)
+            }),
+        )
+        .route(
+            "/conflicts/:id",
+            get(
+                |ApiPath(id): ApiPath<Uuid>, State(graph): State<Arc<Graph>>| async move {
+                    let conflict = graph
+                        .conflicts
+                        .get(id)
+                        .ok_or(ApiError::ConflictNotFound { id })?;
+                    Ok::<_, ApiError>(Json(conflict))
+                },
+            ),
+        )
+        .route(
+            "/conflicts/:id/diff",
+            get(
+                |ApiPath(id): ApiPath<Uuid>, State(graph): State<Arc<Graph>>| async move {
+                    let conflict = graph
+                        .conflicts
+                        .get(id)
+                        .ok_or(ApiError::ConflictNotFound { id })?;
+                    let diff = tokio::task::spawn_blocking(move || conflict.diff())
+                        .await
+                        .unwrap();
+                    Ok::<_, ApiError>(diff)
+                },
+            ),
+        )
+        .route(
+            "/conflicts/:id/resolve",
+            post(
+                |ApiPath(id): ApiPath<Uuid>,
+                 State(graph): State<Arc<Graph>>,
+                 ApiJson(resolution): ApiJson<Resolution>| async move {
+                    graph.conflicts.resolve(id, resolution)?;
+                    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+                },
+            ),
+        )
+        .route(
+            "/pending-writes",
+            get(|State(graph): State<Arc<Graph>>| async move {
+                let writes = graph.pending_writes.list();
+                Json(writes)
+            }),
+        )
+        .route(
+            "/pending-writes/:id",
+            post(
+                |ApiPath(id): ApiPath<Uuid>, State(graph): State<Arc<Graph>>| async move {
+                    graph.pending_writes.apply(id)?;
+                    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+                },
+            )
+            .delete(
+                |ApiPath(id): ApiPath<Uuid>, State(graph): State<Arc<Graph>>| async move {
+                    graph.pending_writes.discard(id)?;
+                    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+                },
+            ),
+        )
+        .route(
+            "/journal",
+            get(
+                |ApiQuery(JournalOptions { limit }): ApiQuery<JournalOptions>,
+                 State(graph): State<Arc<Graph>>| async move {
+                    let entries = graph.journal.list(limit);
+                    Json(entries)
+                },
+            ),
+        )
+        .route(
+            "/undo/:write_id",
+            post(
+                |ApiPath(id): ApiPath<Uuid>, State(graph): State<Arc<Graph>>| async move {
+                    graph.journal.undo(id)?;
+                    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+                },
+            ),
+        )
+        .route(
+            "/warnings/:path",
+            get(
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                    let warnings = graph
+                        .warnings(&path)
+                        .await
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(warnings))
                 },
             ),
         )
         .route(
             "/root-id/:path",
             get(
-                |Path(path): Path<PathBuf>, State(graph): State<Arc<Graph>>| async move {
-                    let root_id = graph.root_id(&path).await;
-                    Json(root_id)
+                |ApiPath(path): ApiPath<PathBuf>, State(graph): State<Arc<Graph>>| async move {
+                    let root_id = graph
+                        .root_id(&path)
+                        .await
+                        .ok_or(ApiError::PathNotFound { path })?;
+                    Ok::<_, ApiError>(Json(root_id))
                 },
             ),
         )
@@ -76,8 +375,8 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
             "/nodes",
             get(
                 |State(graph): State<Arc<Graph>>,
-                 Query(QueryOptions { use_bincode }): Query<QueryOptions>,
-                 Json(opts): Json<NodeOptions>| async move {
+                 ApiQuery(QueryOptions { use_bincode }): ApiQuery<QueryOptions>,
+                 ApiJson(opts): ApiJson<NodeOptions>| async move {
                     let nodes = graph.nodes(None, opts).await;
                     if use_bincode {
                         bincode::serialize(&nodes).unwrap().into_response()
@@ -103,6 +402,13 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
                 Json(cfg.link_types.clone())
             }),
         )
+        .route(
+            "/info/inverse-link-types",
+            get(|| async {
+                let cfg = STARLING_CONFIG.get();
+                Json(cfg.inverse_link_types.clone())
+            }),
+        )
         .route(
             "/info/default-link-type",
             get(|| async {
@@ -121,7 +427,7 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
         .route(
             "/utils/next-timestamp",
             // Returns the next repeat of the given timestamp if there is one.
-            get(|Json(ts): Json<Timestamp>| async {
+            get(|ApiJson(ts): ApiJson<Timestamp>| async {
                 let next_ts = ts.into_next_repeat().ok();
                 Json(next_ts)
             }),
@@ -129,7 +435,7 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
         .route(
             "/utils/next-timestamp/:after",
             get(
-                |Path(after): Path<NaiveDate>, Json(ts): Json<Timestamp>| async move {
+                |ApiPath(after): ApiPath<NaiveDate>, ApiJson(ts): ApiJson<Timestamp>| async move {
                     let next_ts = ts.into_next_repeat_after(after).ok();
                     Json(next_ts)
                 },
@@ -142,8 +448,8 @@ pub fn make_app(graph: Arc<Graph>, dir: &StdPath) -> Result<Router, DirError> {
             &format!("/index/{}/nodes", index_name),
             get(
                 |State(graph): State<Arc<Graph>>,
-                 Query(QueryOptions { use_bincode }): Query<QueryOptions>,
-                 Json(opts): Json<NodeOptions>| async move {
+                 ApiQuery(QueryOptions { use_bincode }): ApiQuery<QueryOptions>,
+                 ApiJson(opts): ApiJson<NodeOptions>| async move {
                     let nodes = graph.nodes(Some(&index_name), opts).await;
 
                     if use_bincode {
@@ -0,0 +1,177 @@
+use crate::attributes::*;
+use crate::error::FrontmatterError;
+
+#[test]
+fn frontmatter_styles_should_be_detected() {
+    assert_eq!(
+        FrontmatterStyle::detect("---\ntitle: Test\n---"),
+        Some(FrontmatterStyle::Yaml)
+    );
+    assert_eq!(
+        FrontmatterStyle::detect("+++\ntitle = \"Test\"\n+++"),
+        Some(FrontmatterStyle::Toml)
+    );
+    assert_eq!(
+        FrontmatterStyle::detect(";;;\n\"title\": \"Test\"\n;;;"),
+        Some(FrontmatterStyle::Json)
+    );
+    assert_eq!(
+        FrontmatterStyle::detect("{\n  \"title\": \"Test\"\n}"),
+        Some(FrontmatterStyle::Json)
+    );
+    assert_eq!(FrontmatterStyle::detect("# Heading"), None);
+    // Bare objects need their opening brace on a line of its own
+    assert_eq!(FrontmatterStyle::detect("{ \"title\": \"Test\" }"), None);
+    assert_eq!(FrontmatterStyle::detect("{{< figure >}}"), None);
+}
+
+#[test]
+fn frontmatter_should_parse_in_all_styles() {
+    for attributes in [
+        "---\ntitle: Test\ntags:\n    - hello\n---",
+        "+++\ntitle = \"Test\"\ntags = [\"hello\"]\n+++",
+        ";;;\n{ \"title\": \"Test\", \"tags\": [\"hello\"] }\n;;;",
+        ";;;\n\"title\": \"Test\",\n\"tags\": [\"hello\"]\n;;;",
+        "{\n  \"title\": \"Test\",\n  \"tags\": [\"hello\"]\n}",
+    ] {
+        let frontmatter = MarkdownFrontmatter::parse(attributes).unwrap().unwrap();
+        assert_eq!(frontmatter.title.as_deref(), Some("Test"));
+        assert_eq!(frontmatter.tags, Some(vec!["hello".to_string()]));
+    }
+
+    assert!(MarkdownFrontmatter::parse("").is_none());
+    assert!(matches!(
+        MarkdownFrontmatter::parse("+++\ntags = \"hello\"\n+++"),
+        Some(Err(FrontmatterError::Toml(_)))
+    ));
+}
+
+#[test]
+fn non_yaml_frontmatter_should_split() {
+    let toml = "+++\ntitle = \"Test\"\n+++\n\n# Heading\n";
+    assert_eq!(
+        split_frontmatter(toml),
+        Some(("+++\ntitle = \"Test\"\n+++", "\n# Heading\n"))
+    );
+
+    let json = ";;;\n\"title\": \"Test\"\n;;;\nBody";
+    assert_eq!(
+        split_frontmatter(json),
+        Some((";;;\n\"title\": \"Test\"\n;;;", "Body"))
+    );
+
+    let bare_json = "{\n  \"title\": \"{Test}\"\n}\n\nBody";
+    assert_eq!(
+        split_frontmatter(bare_json),
+        Some(("{\n  \"title\": \"{Test}\"\n}", "\nBody"))
+    );
+    assert_eq!(
+        split_frontmatter("{\n  \"title\": \"Test\"\n}\n"),
+        Some(("{\n  \"title\": \"Test\"\n}", ""))
+    );
+    assert_eq!(
+        split_frontmatter("{\n  \"title\": \"Test\"\n}\n<!--PROPERTIES\n-->"),
+        Some(("{\n  \"title\": \"Test\"\n}", "<!--PROPERTIES\n-->"))
+    );
+
+    // Anything else starting with a brace is just part of the document
+    assert_eq!(
+        split_frontmatter("{{< figure src=\"a.png\" >}}\n\nBody"),
+        None
+    );
+    assert_eq!(split_frontmatter("{\"title\": \"Test\"}\n\nBody"), None);
+    assert_eq!(split_frontmatter("{\n  \"title\": \"Test\"\n}\nBody"), None);
+    assert_eq!(
+        split_frontmatter("{\n  \"title\": \"Test\"\n} Body\n\n"),
+        None
+    );
+    assert_eq!(split_frontmatter("{\n  1, 2\n}\n\nBody"), None);
+
+    // Orgish handles YAML itself, and unterminated frontmatter isn't frontmatter at all
+    assert_eq!(split_frontmatter("---\ntitle: Test\n---\nBody"), None);
+    assert_eq!(split_frontmatter("+++\ntitle = \"Test\"\nBody"), None);
+    assert_eq!(split_frontmatter("Body"), None);
+}
+
+#[test]
+fn all_frontmatter_keys_should_be_typed() {
+    let frontmatter = MarkdownFrontmatter::parse(
+        "---\ntitle: Test\nauthor: Someone\ndraft: false\nrating: 4\nscore: 4.5\naliases:\n    - Other\nsource:\n---",
+    )
+    .unwrap()
+    .unwrap();
+    let attributes = frontmatter.attributes;
+    assert_eq!(
+        attributes.get("title"),
+        Some(&AttributeValue::String("Test".to_string()))
+    );
+    assert_eq!(
+        attributes.get("author"),
+        Some(&AttributeValue::String("Someone".to_string()))
+    );
+    assert_eq!(attributes.get("draft"), Some(&AttributeValue::Bool(false)));
+    assert_eq!(attributes.get("rating"), Some(&AttributeValue::Int(4)));
+    assert_eq!(attributes.get("score"), Some(&AttributeValue::Float(4.5)));
+    assert_eq!(
+        attributes.get("aliases"),
+        Some(&AttributeValue::List(vec![AttributeValue::String(
+            "Other".to_string()
+        )]))
+    );
+    assert_eq!(attributes.get("source"), Some(&AttributeValue::Null));
+
+    // TOML dates should come through as strings
+    let frontmatter = MarkdownFrontmatter::parse("+++\ntitle = \"Test\"\ndate = 2024-01-01\n+++")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        frontmatter.attributes.get("date"),
+        Some(&AttributeValue::String("2024-01-01".to_string()))
+    );
+}
+
+#[test]
+fn org_keywords_should_be_parsed() {
+    let attributes =
+        parse_org_attributes("#+TITLE: Test\n#+author: Someone\n#+SOURCE: a\n#+source: b\nText");
+    assert_eq!(
+        attributes.get("title"),
+        Some(&AttributeValue::String("Test".to_string()))
+    );
+    assert_eq!(
+        attributes.get("author"),
+        Some(&AttributeValue::String("Someone".to_string()))
+    );
+    assert_eq!(
+        attributes.get("source"),
+        Some(&AttributeValue::List(vec![
+            AttributeValue::String("a".to_string()),
+            AttributeValue::String("b".to_string())
+        ]))
+    );
+    assert_eq!(attributes.len(), 3);
+}
+
+#[test]
+fn unsupported_frontmatter_values_should_be_skipped() {
+    let frontmatter = MarkdownFrontmatter::parse(
+        "---\ntitle: Test\ntags:\n    - a\n? [complex, key]\n: value\nnested:\n    ? [complex, key]\n    : value\nrating: !custom 4\n---",
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(frontmatter.title, Some("Test".to_string()));
+    assert_eq!(frontmatter.tags, Some(vec!["a".to_string()]));
+    let attributes = frontmatter.attributes;
+    assert_eq!(attributes.len(), 3);
+    assert!(!attributes.contains_key("nested"));
+    assert_eq!(attributes.get("rating"), Some(&AttributeValue::Int(4)));
+
+    // Numbers too big for an integer should still come through
+    let frontmatter = MarkdownFrontmatter::parse("{\n\"big\": 18446744073709551615\n}")
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        frontmatter.attributes.get("big"),
+        Some(AttributeValue::Float(_))
+    ));
+}
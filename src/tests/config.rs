@@ -0,0 +1,269 @@
+use crate::{
+    config::{vault_name, Config, FileFormat, IdScheme, LinkConstraint},
+    error::ConfigParseError,
+};
+use orgish::Format;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn default_formats_should_resolve() {
+    let config = Config::default();
+    assert_eq!(config.format_for(Path::new("test.org")), Some(Format::Org));
+    assert_eq!(
+        config.format_for(Path::new("nested/test.md")),
+        Some(Format::Markdown)
+    );
+    assert_eq!(
+        config.format_for(Path::new("test.markdown")),
+        Some(Format::Markdown)
+    );
+    assert_eq!(config.format_for(Path::new("test.txt")), None);
+    assert_eq!(config.format_for(Path::new("org")), None);
+}
+
+#[test]
+fn directory_formats_should_be_defaults() {
+    let mut config = Config::default();
+    config
+        .extensions
+        .insert("txt".to_string(), FileFormat::Markdown);
+    config
+        .directory_formats
+        .insert(PathBuf::from("agenda"), FileFormat::Org);
+    config
+        .directory_formats
+        .insert(PathBuf::from("agenda/exports"), FileFormat::Markdown);
+
+    // Mapped extensions should always decide the format
+    assert_eq!(
+        config.format_for(Path::new("agenda/notes.txt")),
+        Some(Format::Markdown)
+    );
+    assert_eq!(
+        config.format_for(Path::new("agenda/nested/notes.md")),
+        Some(Format::Markdown)
+    );
+    // Files without extensions in a directory with a default should use that
+    assert_eq!(
+        config.format_for(Path::new("agenda/nested/todo")),
+        Some(Format::Org)
+    );
+    // The deepest directory should win
+    assert_eq!(
+        config.format_for(Path::new("agenda/exports/todo")),
+        Some(Format::Markdown)
+    );
+    // Untracked extensions and hidden files shouldn't be picked up by directory formats
+    assert_eq!(config.format_for(Path::new("agenda/image.png")), None);
+    assert_eq!(config.format_for(Path::new("agenda/notes.agenda")), None);
+    assert_eq!(config.format_for(Path::new("agenda/.starlingignore")), None);
+    // And outside them, files without extensions aren't tracked
+    assert_eq!(config.format_for(Path::new("todo")), None);
+}
+
+#[test]
+fn inverse_link_types_should_validate() {
+    let mut config = Config {
+        log_directory: Some(std::env::temp_dir()),
+        ..Config::default()
+    };
+    config
+        .link_types
+        .extend(["supports", "supported-by", "related"].map(String::from));
+    config.inverse_link_types.extend([
+        ("supports".to_string(), "supported-by".to_string()),
+        ("related".to_string(), "related".to_string()),
+    ]);
+    config.validate().unwrap();
+    assert_eq!(config.inverse_link_type("supports"), "supported-by");
+    // Declaring one direction is enough if both are link types
+    assert_eq!(config.inverse_link_type("supported-by"), "supports");
+    assert_eq!(config.inverse_link_type("related"), "related");
+    assert_eq!(config.inverse_link_type("link"), "link");
+
+    config
+        .inverse_link_types
+        .insert("unknown".to_string(), "known".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::UnknownInverseLinkType { .. })
+    ));
+    config.inverse_link_types.remove("unknown");
+    config
+        .inverse_link_types
+        .insert("supported-by".to_string(), "related".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::InconsistentInverseLinkType { .. })
+    ));
+}
+
+#[test]
+fn tags_should_expand() {
+    let mut config = Config {
+        log_directory: Some(std::env::temp_dir()),
+        ..Config::default()
+    };
+    config
+        .tags
+        .extend(["cs/algorithms/graphs", "machine-learning"].map(String::from));
+    config
+        .tag_aliases
+        .insert("ml".to_string(), "machine-learning".to_string());
+    // Aliases can stand for ancestors of tags
+    config
+        .tag_aliases
+        .insert("comp-sci".to_string(), "cs".to_string());
+    config.validate().unwrap();
+
+    // Ancestors of hierarchical tags and aliases are allowed, but not partial levels
+    assert!(config.is_valid_tag("cs"));
+    assert!(config.is_valid_tag("cs/algorithms"));
+    assert!(config.is_valid_tag("ml"));
+    assert!(!config.is_valid_tag("c"));
+    assert!(!config.is_valid_tag("cs/algo"));
+
+    assert_eq!(
+        config.expand_tags(&["cs/algorithms".to_string(), "ml".to_string()]),
+        ["cs", "cs/algorithms", "machine-learning"]
+            .map(String::from)
+            .into()
+    );
+    assert_eq!(
+        config.expand_tags(&["comp-sci".to_string()]),
+        ["cs"].map(String::from).into()
+    );
+
+    // But not for other aliases, or partial levels
+    config
+        .tag_aliases
+        .insert("learning".to_string(), "ml".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::UnknownAliasedTag { .. })
+    ));
+    config.tag_aliases.remove("learning");
+    config
+        .tag_aliases
+        .insert("algo".to_string(), "cs/algo".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::UnknownAliasedTag { .. })
+    ));
+    config.tag_aliases.remove("algo");
+
+    config
+        .tag_aliases
+        .insert("ai".to_string(), "artificial-intelligence".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::UnknownAliasedTag { .. })
+    ));
+}
+
+#[test]
+fn link_constraints_should_need_known_types() {
+    let mut config: Config = toml::from_str(
+        r#"
+link_types = ["parent"]
+
+[link_constraints.parent]
+target_tags = ["concept"]
+acyclic = true
+"#,
+    )
+    .unwrap();
+    config.log_directory = Some(std::env::temp_dir());
+    config.validate().unwrap();
+    assert_eq!(
+        config.link_constraints["parent"],
+        LinkConstraint {
+            target_tags: vec!["concept".to_string()],
+            acyclic: true,
+            ..Default::default()
+        }
+    );
+
+    config
+        .link_constraints
+        .insert("blocks".to_string(), LinkConstraint::default());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigParseError::UnknownConstrainedLinkType { .. })
+    ));
+}
+
+#[test]
+fn id_schemes_should_generate_their_versions() {
+    let config: Config = toml::from_str(r#"id_scheme = "uuid_v7""#).unwrap();
+    assert_eq!(config.id_scheme, IdScheme::UuidV7);
+    assert_eq!(Config::default().id_scheme, IdScheme::UuidV4);
+
+    assert_eq!(IdScheme::UuidV4.generate().get_version_num(), 4);
+    let first = IdScheme::UuidV7.generate();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    let second = IdScheme::UuidV7.generate();
+    assert_eq!(first.get_version_num(), 7);
+    // These should sort in the order they were made
+    assert!(first < second);
+
+    let config: Config = toml::from_str(r#"id_scheme = "ulid""#).unwrap();
+    assert_eq!(config.id_scheme, IdScheme::Ulid);
+    let first = IdScheme::Ulid.generate();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    let second = IdScheme::Ulid.generate();
+    assert!(first < second);
+}
+
+#[test]
+fn vault_names_should_differ_per_root() {
+    let root = std::env::temp_dir().join(format!("starling-vault-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("notes")).unwrap();
+    std::fs::create_dir_all(root.join("other/notes")).unwrap();
+
+    // Directories with the same name in different places shouldn't share anything
+    let name = vault_name(&root.join("notes"));
+    assert!(name.starts_with("notes-"));
+    assert_ne!(name, vault_name(&root.join("other/notes")));
+    // But the same directory should always get the same name, however it's reached
+    assert_eq!(name, vault_name(&root.join("other/../notes")));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn journal_should_only_be_persisted_when_enabled() {
+    // By default, the journal is kept in memory, even if it has somewhere to go
+    let journal_dir =
+        std::env::temp_dir().join(format!("starling-journal-{}", uuid::Uuid::new_v4()));
+    let mut config = Config {
+        log_directory: Some(std::env::temp_dir()),
+        journal_directory: Some(journal_dir.clone()),
+        ..Config::default()
+    };
+    config.validate().unwrap();
+    assert_eq!(config.journal_dir(), None);
+    assert!(!journal_dir.exists());
+
+    config.persist_journal = true;
+    config.validate().unwrap();
+    assert_eq!(config.journal_dir(), Some(journal_dir.as_path()));
+    assert!(journal_dir.is_dir());
+
+    std::fs::remove_dir_all(journal_dir).unwrap();
+}
+
+#[test]
+fn lazy_ids_should_apply_in_directories() {
+    let mut config = Config {
+        lazy_id_directories: vec![PathBuf::from("daily")],
+        ..Config::default()
+    };
+    assert!(config.lazy_ids_for(Path::new("daily/today.md")));
+    assert!(config.lazy_ids_for(Path::new("daily/2024/today.md")));
+    assert!(!config.lazy_ids_for(Path::new("dailyish.md")));
+    assert!(!config.lazy_ids_for(Path::new("notes/daily/today.md")));
+
+    config.lazy_ids = true;
+    assert!(config.lazy_ids_for(Path::new("notes/today.md")));
+}
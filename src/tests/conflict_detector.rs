@@ -11,6 +11,8 @@ fn other_write(path: &str) -> Write {
         contents: String::new(),
         source: WriteSource::Other,
         conflict: Conflict::None,
+        base: None,
+        reasons: Vec::new(),
     }
 }
 
@@ -21,6 +23,8 @@ fn fs_write(path: &str) -> Write {
         contents: String::new(),
         source: WriteSource::Filesystem,
         conflict: Conflict::None,
+        base: None,
+        reasons: Vec::new(),
     }
 }
 
@@ -32,6 +36,8 @@ fn other_conflict(path: &str) -> Write {
         contents: String::new(),
         source: WriteSource::Other,
         conflict: Conflict::Simple,
+        base: None,
+        reasons: Vec::new(),
     }
 }
 
@@ -86,6 +92,35 @@ fn deletion_should_drop_write() {
     assert_eq!(cd.detect_conflicts(p1, p1_writes), Vec::new());
 }
 
+#[test]
+fn fs_writes_with_base_should_conflict() {
+    let mut cd = ConflictDetector::new();
+
+    let p1 = cd.register_update();
+    let with_base = Write {
+        base: Some(String::new()),
+        ..fs_write("foo")
+    };
+    let p1_writes = vec![with_base.clone(), fs_write("bar")];
+
+    cd.add_patch(DebouncedEvents::from_sequential(
+        vec![
+            Event::Modify(PathBuf::from("foo")),
+            Event::Modify(PathBuf::from("bar")),
+        ]
+        .into_iter(),
+    ));
+
+    // The write with a base could be merged, so it should be kept for that
+    assert_eq!(
+        cd.detect_conflicts(p1, p1_writes),
+        vec![Write {
+            conflict: Conflict::Simple,
+            ..with_base
+        }]
+    );
+}
+
 #[test]
 fn fs_writes_should_be_dropped_on_conflict() {
     let mut cd = ConflictDetector::new();
@@ -214,6 +249,8 @@ fn nightmare_rename_should_be_detected() {
             contents: String::new(),
             source: WriteSource::Other,
             conflict: Conflict::Multi(["bar".into(), "baz".into()].into()),
+            base: None,
+            reasons: Vec::new(),
         }]
     );
 }
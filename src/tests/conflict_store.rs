@@ -0,0 +1,196 @@
+use crate::{
+    conflict_detector::{Conflict, Write, WriteReason, WriteSource},
+    conflict_store::{is_sidecar, sidecar_path, ConflictStore, Resolution},
+    error::ApiError,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Creates a [`Write`] of the given contents to the given path with the given conflict for
+/// testing.
+fn conflicted_write(path: &str, contents: &str, conflict: Conflict) -> Write {
+    Write {
+        path: PathBuf::from(path),
+        contents: contents.to_string(),
+        source: WriteSource::Filesystem,
+        conflict,
+        base: None,
+        reasons: Vec::new(),
+    }
+}
+
+#[test]
+fn conflicts_should_be_stored_until_resolved() {
+    let store = ConflictStore::new();
+    let id = store.record(
+        conflicted_write("foo.md", "mine\n", Conflict::Simple),
+        Some("disk\n".to_string()),
+        None,
+    );
+
+    let conflict = store.get(id).unwrap();
+    assert_eq!(conflict.disk_contents, Some("disk\n".to_string()));
+    assert!(conflict.candidates.is_empty());
+    assert_eq!(
+        conflict.diff(),
+        "--- disk/foo.md\n+++ starling/foo.md\n@@ -1,1 +1,1 @@\n-disk\n+mine\n"
+    );
+
+    // Keeping the disk contents shouldn't write anything
+    store.resolve(id, Resolution::KeepDisk).unwrap();
+    assert!(store.list().is_empty());
+    assert!(store.take_resolved().is_empty());
+    assert!(matches!(
+        store.resolve(id, Resolution::KeepDisk),
+        Err(ApiError::ConflictNotFound { .. })
+    ));
+}
+
+#[test]
+fn forced_resolution_should_write() {
+    let store = ConflictStore::new();
+    let old_id = store.record(
+        conflicted_write("foo.md", "old\n", Conflict::Simple),
+        None,
+        None,
+    );
+    // A newer conflict on the same path should replace the old one
+    let id = store.record(
+        conflicted_write("foo.md", "mine\n", Conflict::Simple),
+        None,
+        None,
+    );
+    assert!(store.get(old_id).is_none());
+    assert_eq!(store.list().len(), 1);
+
+    store.resolve(id, Resolution::ForceMine).unwrap();
+    assert_eq!(
+        store.take_resolved(),
+        vec![Write {
+            path: PathBuf::from("foo.md"),
+            contents: "mine\n".to_string(),
+            source: WriteSource::Other,
+            conflict: Conflict::None,
+            base: None,
+            reasons: vec![WriteReason::ConflictResolution],
+        }]
+    );
+}
+
+#[test]
+fn multi_conflicts_should_need_candidate_target() {
+    let store = ConflictStore::new();
+    let candidates = ["bar.md", "baz.md"].into_iter().map(PathBuf::from);
+    let id = store.record(
+        conflicted_write(
+            "foo.md",
+            "mine\n",
+            Conflict::Multi(candidates.collect::<HashSet<_>>()),
+        ),
+        None,
+        None,
+    );
+    assert_eq!(
+        store.get(id).unwrap().candidates,
+        vec![PathBuf::from("bar.md"), PathBuf::from("baz.md")]
+    );
+
+    assert!(matches!(
+        store.resolve(
+            id,
+            Resolution::Target {
+                path: PathBuf::from("qux.md")
+            }
+        ),
+        Err(ApiError::InvalidResolution { .. })
+    ));
+    store
+        .resolve(
+            id,
+            Resolution::Target {
+                path: PathBuf::from("baz.md"),
+            },
+        )
+        .unwrap();
+    let writes = store.take_resolved();
+    assert_eq!(writes.len(), 1);
+    assert_eq!(writes[0].path, PathBuf::from("baz.md"));
+}
+
+#[test]
+fn simple_conflicts_should_reject_targets() {
+    let store = ConflictStore::new();
+    let id = store.record(
+        conflicted_write("foo.md", "mine\n", Conflict::Simple),
+        None,
+        None,
+    );
+    assert!(matches!(
+        store.resolve(
+            id,
+            Resolution::Target {
+                path: PathBuf::from("foo.md")
+            }
+        ),
+        Err(ApiError::InvalidResolution { .. })
+    ));
+    // The conflict should still be there
+    assert!(store.get(id).is_some());
+}
+
+#[test]
+fn sidecars_should_sit_next_to_files() {
+    assert_eq!(
+        sidecar_path(Path::new("notes/foo.md")),
+        PathBuf::from("notes/foo.starling-conflict.md")
+    );
+    assert_eq!(
+        sidecar_path(Path::new("foo.draft.org")),
+        PathBuf::from("foo.draft.starling-conflict.org")
+    );
+    assert_eq!(
+        sidecar_path(Path::new("foo")),
+        PathBuf::from("foo.starling-conflict")
+    );
+
+    assert!(is_sidecar(&sidecar_path(Path::new("notes/foo.md"))));
+    assert!(is_sidecar(&sidecar_path(Path::new("foo"))));
+    assert!(!is_sidecar(Path::new("notes/foo.md")));
+    assert!(!is_sidecar(Path::new("starling-conflict.md")));
+}
+
+#[test]
+fn sidecars_should_be_removed_on_resolution() {
+    let store = ConflictStore::new();
+    let sidecar = sidecar_path(Path::new("foo.md"));
+    let id = store.record(
+        conflicted_write("foo.md", "mine\n", Conflict::Simple),
+        None,
+        Some(sidecar.clone()),
+    );
+    assert_eq!(store.get(id).unwrap().sidecar, Some(sidecar.clone()));
+
+    store.resolve(id, Resolution::ForceMine).unwrap();
+    assert_eq!(store.take_stale_sidecars(), vec![sidecar]);
+    assert!(store.take_stale_sidecars().is_empty());
+}
+
+#[test]
+fn deleting_sidecar_should_discard_conflict() {
+    let store = ConflictStore::new();
+    let sidecar = sidecar_path(Path::new("foo.md"));
+    let id = store.record(
+        conflicted_write("foo.md", "mine\n", Conflict::Simple),
+        None,
+        Some(sidecar.clone()),
+    );
+
+    assert_eq!(store.discard_sidecar(Path::new("bar.md")), None);
+    assert_eq!(store.discard_sidecar(&sidecar), Some(id));
+    assert!(store.list().is_empty());
+    // The user already deleted it, and nothing should be written
+    assert!(store.take_stale_sidecars().is_empty());
+    assert!(store.take_resolved().is_empty());
+}
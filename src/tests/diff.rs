@@ -0,0 +1,109 @@
+use crate::diff::{diff_lines, merge3, unified_diff, DiffOp};
+
+#[test]
+fn diff_should_keep_common_lines() {
+    let old = ["a", "b", "c", "d"];
+    let new = ["a", "x", "c", "d", "e"];
+    assert_eq!(
+        diff_lines(&old, &new),
+        vec![
+            DiffOp::Equal(0, 0),
+            DiffOp::Delete(1),
+            DiffOp::Insert(1),
+            DiffOp::Equal(2, 2),
+            DiffOp::Equal(3, 3),
+            DiffOp::Insert(4),
+        ]
+    );
+}
+
+#[test]
+fn diff_should_be_minimal() {
+    // The example from Myers' paper, which has five differences
+    let old = ["a", "b", "c", "a", "b", "b", "a"];
+    let new = ["c", "b", "a", "b", "a", "c"];
+    let ops = diff_lines(&old, &new);
+    let changes = ops
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Equal(..)))
+        .count();
+    assert_eq!(changes, 5);
+
+    // Applying the steps should give the new lines back
+    let applied = ops
+        .iter()
+        .filter_map(|op| match op {
+            DiffOp::Equal(i, _) => Some(old[*i]),
+            DiffOp::Insert(j) => Some(new[*j]),
+            DiffOp::Delete(_) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(applied, new);
+}
+
+#[test]
+fn unified_diff_should_show_context() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n";
+    assert_eq!(
+        unified_diff(old, new, "a", "b"),
+        "--- a\n+++ b\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+    );
+    assert_eq!(unified_diff(old, old, "a", "b"), "");
+}
+
+#[test]
+fn unified_diff_should_split_distant_hunks() {
+    let old = (1..=20).map(|n| format!("{n}\n")).collect::<String>();
+    let new = (1..=20)
+        .map(|n| match n {
+            2 => "two\n".to_string(),
+            19 => "nineteen\n".to_string(),
+            n => format!("{n}\n"),
+        })
+        .collect::<String>();
+    let diff = unified_diff(&old, &new, "a", "b");
+    assert_eq!(diff.matches("@@ -").count(), 2);
+    assert!(diff.contains("@@ -1,5 +1,5 @@\n"));
+    assert!(diff.contains("@@ -16,5 +16,5 @@\n"));
+}
+
+#[test]
+fn unified_diff_should_handle_empty_sides() {
+    assert_eq!(
+        unified_diff("", "new", "a", "b"),
+        "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+new\n\\ No newline at end of file\n"
+    );
+}
+
+#[test]
+fn merge_should_combine_separate_changes() {
+    let base = "title\n\nsee [[old title]]\n\nbody\n";
+    let ours = "title\n\nsee [[old title]]\n\nbody, edited\n";
+    let theirs = "title\n\nsee [[new title]]\n\nbody\n";
+    assert_eq!(
+        merge3(base, ours, theirs),
+        Some("title\n\nsee [[new title]]\n\nbody, edited\n".to_string())
+    );
+    // Making the same change on both sides is fine too
+    assert_eq!(merge3(base, theirs, theirs), Some(theirs.to_string()));
+}
+
+#[test]
+fn merge_should_handle_insertions() {
+    let base = "* Heading\nbody\n";
+    let ours = "* Heading\nbody\nmore body\n";
+    let theirs = "* Heading\n:PROPERTIES:\n:ID: 1\n:END:\nbody\n";
+    assert_eq!(
+        merge3(base, ours, theirs),
+        Some("* Heading\n:PROPERTIES:\n:ID: 1\n:END:\nbody\nmore body\n".to_string())
+    );
+}
+
+#[test]
+fn merge_should_fail_on_overlapping_changes() {
+    let base = "a\nb\nc\n";
+    let ours = "a\nB\nc\n";
+    let theirs = "a\nbee\nc\n";
+    assert_eq!(merge3(base, ours, theirs), None);
+}
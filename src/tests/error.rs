@@ -0,0 +1,50 @@
+use crate::error::{ApiError, FrontmatterError, PathParseError};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+#[test]
+fn path_errors_should_report_details() {
+    let report = PathParseError::InvalidTag {
+        path: PathBuf::from("test.org"),
+        tag: "typo".to_string(),
+        span: None,
+    }
+    .report();
+    assert_eq!(report.code, "invalid_tag");
+    assert_eq!(report.path, Some(PathBuf::from("test.org")));
+    assert_eq!(report.tag, Some("typo".to_string()));
+    assert!(report.sources.is_empty());
+
+    // Causes should be kept separately
+    let err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    let report = PathParseError::InvalidFrontmatter {
+        path: PathBuf::from("test.md"),
+        span: None,
+        err: Box::new(FrontmatterError::Json(err)),
+    }
+    .report();
+    assert_eq!(report.code, "invalid_frontmatter");
+    assert!(!report.message.contains("json"));
+    assert_eq!(report.sources.len(), 2);
+    assert_eq!(report.sources[0], "invalid json frontmatter");
+}
+
+#[test]
+fn api_errors_should_report_details() {
+    let id = Uuid::new_v4();
+    let report = ApiError::NodeNotFound { id }.report();
+    assert_eq!(report.code, "node_not_found");
+    assert_eq!(report.id, Some(id));
+    assert_eq!(report.path, None);
+}
+
+#[test]
+fn unknown_pending_tags_should_report_the_tag() {
+    let report = ApiError::TagNotPending {
+        tag: "typo".to_string(),
+    }
+    .report();
+    assert_eq!(report.code, "tag_not_pending");
+    assert_eq!(report.tag, Some("typo".to_string()));
+    assert_eq!(report.id, None);
+}
@@ -0,0 +1,136 @@
+use crate::{debouncer::DebouncedEvents, exclude::ExcludeMatcher};
+use std::path::{Path, PathBuf};
+
+/// Creates a fresh directory with the given files (and their parent directories), returning its
+/// canonicalized path.
+fn setup_dir(files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("starling-exclude-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir.canonicalize().unwrap()
+}
+
+#[test]
+fn config_patterns_should_be_globs() {
+    let dir = setup_dir(&[]);
+    let exclude = ExcludeMatcher::new(
+        &dir,
+        &[
+            "*.draft.md".to_string(),
+            "archive/".to_string(),
+            "/top.org".to_string(),
+        ],
+        true,
+    )
+    .unwrap();
+
+    assert!(exclude.is_excluded(Path::new("test.draft.md"), false));
+    assert!(exclude.is_excluded(Path::new("nested/test.draft.md"), false));
+    assert!(!exclude.is_excluded(Path::new("test.md"), false));
+    // Directory patterns should exclude everything inside them, but not files of the same name
+    assert!(exclude.is_excluded(Path::new("archive"), true));
+    assert!(exclude.is_excluded(Path::new("archive/old.md"), false));
+    assert!(exclude.is_excluded(Path::new("nested/archive/old.md"), false));
+    assert!(!exclude.is_excluded(Path::new("archive"), false));
+    // Anchored patterns should only apply at the root
+    assert!(exclude.is_excluded(Path::new("top.org"), false));
+    assert!(!exclude.is_excluded(Path::new("nested/top.org"), false));
+    // Git's internals should always be excluded
+    assert!(exclude.is_excluded(Path::new(".git/HEAD"), false));
+    assert!(exclude.is_excluded(Path::new("nested/test.starling-conflict.md"), false));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_config_pattern_should_fail() {
+    let dir = setup_dir(&[]);
+    assert!(ExcludeMatcher::new(&dir, &["notes/[z-a]".to_string()], true).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ignore_files_should_be_respected() {
+    let dir = setup_dir(&[
+        (".gitignore", "build/\n*.tmp.md\n"),
+        (".starlingignore", "private/\n"),
+        // Deeper files should be able to override shallower ones
+        ("notes/.starlingignore", "!keep.tmp.md\n"),
+    ]);
+    let exclude = ExcludeMatcher::new(&dir, &[], true).unwrap();
+
+    assert!(exclude.is_excluded(Path::new("build/out.md"), false));
+    assert!(exclude.is_excluded(Path::new("private/secret.org"), false));
+    assert!(exclude.is_excluded(Path::new("notes/test.tmp.md"), false));
+    assert!(!exclude.is_excluded(Path::new("notes/keep.tmp.md"), false));
+    assert!(!exclude.is_excluded(Path::new("notes/test.md"), false));
+
+    // Without respecting `.gitignore`, only the Starling ignore files should apply
+    let exclude = ExcludeMatcher::new(&dir, &[], false).unwrap();
+    assert!(!exclude.is_excluded(Path::new("build/out.md"), false));
+    assert!(exclude.is_excluded(Path::new("private/secret.org"), false));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn config_whitelists_should_override_ignore_files() {
+    let dir = setup_dir(&[(".starlingignore", "*.draft.md\n")]);
+    let exclude = ExcludeMatcher::new(&dir, &["!keep.draft.md".to_string()], true).unwrap();
+
+    assert!(exclude.is_excluded(Path::new("test.draft.md"), false));
+    assert!(!exclude.is_excluded(Path::new("keep.draft.md"), false));
+    assert!(!exclude.is_excluded(Path::new("nested/keep.draft.md"), false));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ignore_files_should_reload() {
+    let dir = setup_dir(&[("notes/.starlingignore", "*.org\n")]);
+    let exclude = ExcludeMatcher::new(&dir, &[], true).unwrap();
+    assert!(exclude.is_excluded(Path::new("notes/test.org"), false));
+    assert!(!exclude.is_excluded(Path::new("notes/test.md"), false));
+
+    std::fs::write(dir.join("notes/.starlingignore"), "*.md\n").unwrap();
+    exclude.reload(Path::new("notes/.starlingignore"));
+    assert!(!exclude.is_excluded(Path::new("notes/test.org"), false));
+    assert!(exclude.is_excluded(Path::new("notes/test.md"), false));
+
+    std::fs::remove_file(dir.join("notes/.starlingignore")).unwrap();
+    exclude.reload(Path::new("notes/.starlingignore"));
+    assert!(!exclude.is_excluded(Path::new("notes/test.md"), false));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn start_from_dir_should_skip_excluded() {
+    let dir = setup_dir(&[
+        (".starlingignore", "archive/\n"),
+        ("test.md", ""),
+        ("test.draft.md", ""),
+        ("archive/old.md", ""),
+        ("archive/nested/old.org", ""),
+        ("notes/test.org", ""),
+    ]);
+    let exclude = ExcludeMatcher::new(&dir, &["*.draft.md".to_string()], true).unwrap();
+
+    let mut paths = DebouncedEvents::start_from_dir(&dir, &exclude)
+        .into_iter()
+        .map(|(path, _, _)| path)
+        .filter(|path| path.extension().is_some())
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [PathBuf::from("notes/test.org"), PathBuf::from("test.md")]
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
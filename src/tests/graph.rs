@@ -1,14 +1,25 @@
 use crate::{
-    config::{Config, STARLING_CONFIG},
+    attributes::AttributeValue,
+    config::{Config, LinkConstraint, PropertyType, TagSchema, STARLING_CONFIG},
+    connection::{Resource, ResourceKind},
+    constraints::{ConstraintViolation, ViolationKind},
+    error::{ApiError, Position, Severity, Span},
     graph::*,
     node::{Node, NodeConnection, NodeMetadata, NodeOptions},
     patch::{GraphPatch, PathPatch},
     path_node::StarlingNode,
+    schema::{PropertyError, PropertyErrorKind, PropertyValue},
 };
 use chrono::NaiveDate;
 use orgish::{timestamp::DateTime, Format, Timestamp};
-use std::{collections::HashMap, sync::atomic::Ordering};
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use uuid::Uuid;
 
 /// Trait that allows [&str; _] to be converted to a `HashSet<String>`.
@@ -61,22 +72,96 @@ fn opts_all_conns() -> NodeOptions {
         children: false,
         connections: true,
         child_connections: true,
+        link_types: None,
         conn_format: Format::Markdown,
     }
 }
 
+/// Produces the error reported for a connection to an ID no node has.
+fn missing_id(id: &str) -> InvalidConnection {
+    InvalidConnection {
+        id: Some(id.uuid()),
+        name: None,
+        reason: InvalidConnectionReason::NotFound,
+        candidates: Vec::new(),
+    }
+}
+
+/// Produces the error reported for a connection by a name no node has.
+fn missing_name(name: &str) -> InvalidConnection {
+    InvalidConnection {
+        id: None,
+        name: Some(name.to_string()),
+        reason: InvalidConnectionReason::NotFound,
+        candidates: Vec::new(),
+    }
+}
+
 /// Sets up the global configuration (making sure not to do so twice, because otherwise
 /// multi-threaded test interleaving can produce nasty deadlocks).
-fn setup_config() {
+pub(super) fn setup_config() {
     if !STARLING_CONFIG.setup.load(Ordering::SeqCst) {
         let mut config = Config::default();
         config.link_types.push("diff".to_string());
         config.link_types.push("other".to_string());
+        config.link_types.push("supports".to_string());
+        config.link_types.push("related".to_string());
+        config.inverse_link_types.extend([
+            ("supports".to_string(), "supported-by".to_string()),
+            ("related".to_string(), "related".to_string()),
+        ]);
+        config
+            .property_types
+            .insert("EFFORT".to_string(), PropertyType::Duration);
+        config.tag_schemas.insert(
+            "paper".to_string(),
+            TagSchema {
+                required: vec!["AUTHOR".to_string(), "YEAR".to_string()],
+                types: map! { "YEAR".to_string() => PropertyType::Int },
+            },
+        );
+        config
+            .tag_aliases
+            .insert("algo".to_string(), "cs/algorithms".to_string());
+        config.normalize_tag_aliases = true;
+        config.lenient = true;
+        config.collect_unknown_tags = true;
+        config.link_types.push("parent".to_string());
+        config.link_types.push("blocks".to_string());
+        config.link_constraints.extend([
+            (
+                "parent".to_string(),
+                LinkConstraint {
+                    target_tags: vec!["concept".to_string()],
+                    acyclic: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "blocks".to_string(),
+                LinkConstraint {
+                    action_items: true,
+                    ..Default::default()
+                },
+            ),
+        ]);
         config.tags.extend(
-            ["hello", "world", "foo", "parent_tag", "child_tag"]
-                .iter()
-                .map(|s| s.to_string()),
+            [
+                "hello",
+                "world",
+                "foo",
+                "parent_tag",
+                "child_tag",
+                "concept",
+                "paper",
+                "cs/algorithms",
+            ]
+            .iter()
+            .map(|s| s.to_string()),
         );
+        config.id_prefix_length = 8;
+        // Lazy IDs are only tested in their own directory, so they don't affect anything else
+        config.lazy_id_directories.push(PathBuf::from("lazy"));
         STARLING_CONFIG.set(config);
     }
 }
@@ -182,7 +267,7 @@ async fn should_parse_connections_and_node_data() {
     // We should have the invalid connection registered in file 1
     assert_eq!(
         graph.errors(&PathBuf::from("file_1.md")).await,
-        Some(Ok(vec!["6d93b936-5952-4707-89dd-69ca06c60854".uuid()]))
+        Some(Ok(vec![missing_id("6d93b936-5952-4707-89dd-69ca06c60854")]))
     );
     // And no invalid connections on file 2
     assert_eq!(
@@ -206,6 +291,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_1.md"),
             tags: ["hello", "world"].into_hs(),
             parent_tags: [].into(),
+            expanded_tags: ["hello", "world"].into_hs(),
             parent_id: None,
             children: Vec::new(),
             metadata: None,
@@ -246,7 +332,8 @@ async fn should_parse_connections_and_node_data() {
                     title: ["File 1", "Node 2"].into_v(),
                     types: ["link"].into_hs()
                 }
-            }
+            },
+            resources: HashSet::new()
         }
     );
     // Node 1
@@ -264,6 +351,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_1.md"),
             tags: ["parent_tag"].into_hs(),
             parent_tags: ["hello", "world"].into_hs(),
+            expanded_tags: ["hello", "world", "parent_tag"].into_hs(),
             parent_id: Some("5d93b936-5952-4707-89dd-69ca06c60850".uuid()),
             children: Vec::new(),
             metadata: None,
@@ -300,7 +388,8 @@ async fn should_parse_connections_and_node_data() {
                     title: ["File 1", "Node 2"].into_v(),
                     types: ["link"].into_hs()
                 }
-            }
+            },
+            resources: HashSet::new()
         }
     );
     // Node 1.1
@@ -318,6 +407,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_1.md"),
             tags: [].into(),
             parent_tags: ["hello", "world", "parent_tag"].into_hs(),
+            expanded_tags: ["hello", "world", "parent_tag"].into_hs(),
             parent_id: Some("5d93b936-5952-4707-89dd-69ca06c60851".uuid()),
             children: Vec::new(),
             metadata: None,
@@ -345,7 +435,8 @@ async fn should_parse_connections_and_node_data() {
                     types: ["other"].into_hs()
                 }
             },
-            child_backlinks: map! {}
+            child_backlinks: map! {},
+            resources: HashSet::new()
         }
     );
     // Node 1.1.1
@@ -363,6 +454,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_1.md"),
             tags: ["child_tag"].into_hs(),
             parent_tags: ["hello", "world", "parent_tag"].into_hs(),
+            expanded_tags: ["hello", "world", "parent_tag", "child_tag"].into_hs(),
             parent_id: Some("5d93b936-5952-4707-89dd-69ca06c60852".uuid()),
             children: Vec::new(),
             metadata: None,
@@ -372,12 +464,17 @@ async fn should_parse_connections_and_node_data() {
                     title: ["File 1", "Node 1", "Node 1.1"].into_v(),
                     types: ["other"].into_hs()
                 }
-                // Links with invalid types and UUIDs, along with URL connection, are not
-                // registered
+                // Links with invalid types and UUIDs are not registered
             },
             backlinks: map! {},
             child_connections: map! {},
-            child_backlinks: map! {}
+            child_backlinks: map! {},
+            // The URL is registered as a resource instead
+            resources: HashSet::from([Resource {
+                kind: ResourceKind::Url,
+                ty: "link".into(),
+                target: "https://example.com".into()
+            }])
         }
     );
     // Node 2
@@ -395,6 +492,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_1.md"),
             tags: [].into_hs(),
             parent_tags: ["hello", "world"].into_hs(),
+            expanded_tags: ["hello", "world"].into_hs(),
             parent_id: Some("5d93b936-5952-4707-89dd-69ca06c60850".uuid()),
             children: Vec::new(),
             metadata: None,
@@ -407,7 +505,8 @@ async fn should_parse_connections_and_node_data() {
             },
             backlinks: map! {},
             child_connections: map! {},
-            child_backlinks: map! {}
+            child_backlinks: map! {},
+            resources: HashSet::new()
         }
     );
     // File 2
@@ -425,6 +524,7 @@ async fn should_parse_connections_and_node_data() {
             path: PathBuf::from("file_2.md"),
             tags: ["foo"].into_hs(),
             parent_tags: [].into(),
+            expanded_tags: ["foo"].into_hs(),
             parent_id: None,
             children: Vec::new(),
             metadata: None,
@@ -442,7 +542,8 @@ async fn should_parse_connections_and_node_data() {
                 }
             },
             child_connections: map! {},
-            child_backlinks: map! {}
+            child_backlinks: map! {},
+            resources: HashSet::new()
         }
     );
 
@@ -457,6 +558,7 @@ async fn should_parse_connections_and_node_data() {
                     children: false,
                     connections: false,
                     child_connections: false,
+                    link_types: None,
                     conn_format: Format::Markdown
                 }
             )
@@ -670,8 +772,12 @@ Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
             properties: map! {
                 "LOCATION".into() => "Test".into()
             },
+            typed_properties: HashMap::new(),
+            property_errors: Vec::new(),
+            diagnostics: Vec::new(),
             keyword: Some("TODO".into()),
-            timestamps: Vec::new()
+            timestamps: Vec::new(),
+            attributes: HashMap::new()
         })
     );
     assert_eq!(node.tags, ["child_tag"].into_hs());
@@ -719,42 +825,49 @@ Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
             properties: map! {
                 "LOCATION".into() => "Test".into()
             },
+            typed_properties: HashMap::new(),
+            property_errors: Vec::new(),
+            diagnostics: Vec::new(),
             keyword: Some("DONE".into()),
-            timestamps: Vec::new()
+            timestamps: Vec::new(),
+            attributes: HashMap::new()
         })
     );
 }
 
 #[tokio::test]
-async fn renames_should_work() {
+async fn alternative_frontmatter_should_round_trip() {
     setup_config();
 
-    let file_1 = r#"---
-title: File 1
-tags:
-    - hello
-    - world
----
+    let file_1 = r#"+++
+title = "File 1"
+tags = ["hello"]
++++
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+ID: 8097edb8-7a66-45fe-aec3-eb957f511ab0
 -->
 
-# TODO [#A] Node 1 :child_tag:
-SCHEDULED: <2024-01-01>
+Here's [File 2](link:8097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+    let file_2 = r#";;;
+"title": "File 2",
+"tags": ["world"]
+;;;
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
-LOCATION: Test
+ID: 8097edb8-7a66-45fe-aec3-eb957f511ab1
 -->
 
-This is a test file."#;
-    let file_2 = r#"---
-title: File 2
----
+Here's [File 3](link:8097edb8-7a66-45fe-aec3-eb957f511ab2)."#;
+    let file_3 = r#"{
+  "title": "File 3"
+}
+<!--PROPERTIES
+ID: 8097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
 
-Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+Here's [File 1](link:8097edb8-7a66-45fe-aec3-eb957f511ab0)."#;
 
     let graph = Graph::new(HashMap::new());
-    graph
+    let writes = graph
         .process_fs_patch(GraphPatch {
             renames: Vec::new(),
             deletions: Vec::new(),
@@ -767,59 +880,137 @@ Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
                     path: PathBuf::from("file_2.md"),
                     contents_res: Ok(file_2.into()),
                 },
+                PathPatch {
+                    path: PathBuf::from("file_3.md"),
+                    contents_res: Ok(file_3.into()),
+                },
             ],
             modifications: Vec::new(),
         })
-        .await;
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // Every file should be written back in exactly its original style
+    assert_eq!(writes.get(&PathBuf::from("file_1.md")).unwrap(), file_1);
+    assert_eq!(writes.get(&PathBuf::from("file_2.md")).unwrap(), file_2);
+    assert_eq!(writes.get(&PathBuf::from("file_3.md")).unwrap(), file_3);
+
+    for (id, title, tags) in [
+        (
+            "8097edb8-7a66-45fe-aec3-eb957f511ab0",
+            "File 1",
+            ["hello"].into_hs(),
+        ),
+        (
+            "8097edb8-7a66-45fe-aec3-eb957f511ab1",
+            "File 2",
+            ["world"].into_hs(),
+        ),
+        (
+            "8097edb8-7a66-45fe-aec3-eb957f511ab2",
+            "File 3",
+            [].into_hs(),
+        ),
+    ] {
+        let node = graph
+            .get_node(id.uuid(), NodeOptions::new(Format::Markdown))
+            .await
+            .unwrap();
+        assert_eq!(node.title, [title]);
+        assert_eq!(node.tags, tags);
+    }
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_3.md")).await,
+        Some(Ok(Vec::new()))
+    );
+}
 
-    let writes = graph
+#[tokio::test]
+async fn root_attributes_should_be_exposed() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+author: Someone
+rating: 4
+---
+<!--PROPERTIES
+ID: 9097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+# Node 1
+<!--PROPERTIES
+ID: 9097edb8-7a66-45fe-aec3-eb957f511ab1
+-->"#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
         .process_fs_patch(GraphPatch {
-            renames: vec![(PathBuf::from("file_1.md"), PathBuf::from("file_1_new.md"))],
+            renames: Vec::new(),
             deletions: Vec::new(),
-            creations: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("file_1.md"),
+                contents_res: Ok(file_1.into()),
+            }],
             modifications: Vec::new(),
         })
         .await;
-    // No writes should result from a rename
-    assert!(writes.is_empty());
+
+    let root = graph
+        .get_node(
+            "9097edb8-7a66-45fe-aec3-eb957f511ab0".uuid(),
+            NodeOptions::new(Format::Markdown).metadata(true),
+        )
+        .await
+        .unwrap();
     assert_eq!(
-        graph
-            .get_node(
-                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
-                NodeOptions::new(Format::Markdown)
-            )
-            .await
-            .unwrap()
-            .path,
-        PathBuf::from("file_1_new.md")
+        root.metadata.unwrap().attributes,
+        map! {
+            "title".to_string() => AttributeValue::String("File 1".into()),
+            "author".to_string() => AttributeValue::String("Someone".into()),
+            "rating".to_string() => AttributeValue::Int(4)
+        }
     );
+    // Only the root should have the document's attributes
+    let node = graph
+        .get_node(
+            "9097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).metadata(true),
+        )
+        .await
+        .unwrap();
+    assert!(node.metadata.unwrap().attributes.is_empty());
 }
 
 #[tokio::test]
-async fn node_deletion_should_remove_backlinks() {
+async fn missing_titles_should_be_inferred() {
     setup_config();
 
-    let file_1 = r#"---
-title: File 1
----
-<!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+    let file_1 = r#"<!--PROPERTIES
+ID: a097edb8-7a66-45fe-aec3-eb957f511ab0
 -->
 
-# Node 1
+# First Heading
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+ID: a097edb8-7a66-45fe-aec3-eb957f511ab1
 -->
 
-This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
-    let file_2 = r#"---
-title: File 2
+Some text."#;
+    let file_2 = r#"<!--PROPERTIES
+ID: a097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+Just some text."#;
+    let file_3 = r#"---
+tags:
+    - hello
 ---
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
+ID: a097edb8-7a66-45fe-aec3-eb957f511ab3
 -->
 
-Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+Some text."#;
 
     let graph = Graph::new(HashMap::new());
     graph
@@ -832,103 +1023,88 @@ Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
                     contents_res: Ok(file_1.into()),
                 },
                 PathPatch {
-                    path: PathBuf::from("file_2.md"),
+                    path: PathBuf::from("notes/file_2.md"),
                     contents_res: Ok(file_2.into()),
                 },
+                PathPatch {
+                    path: PathBuf::from("file_3.md"),
+                    contents_res: Ok(file_3.into()),
+                },
             ],
             modifications: Vec::new(),
         })
         .await;
 
-    // We should initially have a backlink on file 2 coming from node 1
-    assert_eq!(
-        graph
-            .get_node(
-                "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
-                NodeOptions::new(Format::Markdown).connections(true)
-            )
+    for (path, id, title) in [
+        (
+            "file_1.md",
+            "a097edb8-7a66-45fe-aec3-eb957f511ab0",
+            "First Heading",
+        ),
+        (
+            "notes/file_2.md",
+            "a097edb8-7a66-45fe-aec3-eb957f511ab2",
+            "file_2",
+        ),
+        (
+            "file_3.md",
+            "a097edb8-7a66-45fe-aec3-eb957f511ab3",
+            "file_3",
+        ),
+    ] {
+        // These should all be valid, but with warnings
+        assert_eq!(
+            graph.errors(&PathBuf::from(path)).await,
+            Some(Ok(Vec::new()))
+        );
+        assert_eq!(graph.warnings(&PathBuf::from(path)).await.unwrap().len(), 1);
+        let node = graph
+            .get_node(id.uuid(), NodeOptions::new(Format::Markdown))
             .await
-            .unwrap()
-            .backlinks,
-        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid() => NodeConnection {
-            title: ["File 1", "Node 1"].into_v(),
-            types: ["link"].into_hs()
-        }}
-    );
+            .unwrap();
+        assert_eq!(node.title, [title]);
+    }
 
-    let file_1_updated = r#"---
-title: File 1
----
-<!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
--->"#;
-    let writes = graph
+    // Adding a title should clear the warning
+    let new_file_2 = format!("---\ntitle: File 2\n---\n{file_2}");
+    graph
         .process_fs_patch(GraphPatch {
             renames: Vec::new(),
             deletions: Vec::new(),
             creations: Vec::new(),
             modifications: vec![PathPatch {
-                path: PathBuf::from("file_1.md"),
-                contents_res: Ok(file_1_updated.into()),
+                path: PathBuf::from("notes/file_2.md"),
+                contents_res: Ok(new_file_2),
             }],
         })
         .await;
-    assert!(writes.is_empty());
-    // And now we should have no backlinks on file 2 (node 1's reference is gone)
-    assert!(graph
-        .get_node(
-            "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
-            NodeOptions::new(Format::Markdown).connections(true)
-        )
-        .await
-        .unwrap()
-        .backlinks
-        .is_empty());
-    // And there should be no valid connections in file 2 either
-    assert!(graph
-        .get_node(
-            "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
-            NodeOptions::new(Format::Markdown).connections(true)
-        )
-        .await
-        .unwrap()
-        .connections
-        .is_empty());
-    // But the invalid connection should be there
     assert_eq!(
-        graph.errors(&PathBuf::from("file_2.md")).await,
-        Some(Ok(vec!["7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid()]))
+        graph.warnings(&PathBuf::from("notes/file_2.md")).await,
+        Some(Vec::new())
     );
 }
 
 #[tokio::test]
-async fn file_deletion_should_work() {
+async fn wiki_links_should_round_trip() {
     setup_config();
 
     let file_1 = r#"---
 title: File 1
 ---
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
--->
-
-# Node 1
-<!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+ID: b097edb8-7a66-45fe-aec3-eb957f511ab0
 -->
 
-This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
+Links to [[b097edb8-7a66-45fe-aec3-eb957f511ab1|Old Title]], [[other:b097edb8-7a66-45fe-aec3-eb957f511ab1|Old Title]], and [[b097edb8-7a66-45fe-aec3-eb957f511ab1]]. This one is broken: [[b097edb8-7a66-45fe-aec3-eb957f511ab9|Nothing]], and this is just a wiki link [[Some Note]]."#;
     let file_2 = r#"---
 title: File 2
 ---
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
--->
-
-Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+ID: b097edb8-7a66-45fe-aec3-eb957f511ab1
+-->"#;
 
     let graph = Graph::new(HashMap::new());
-    graph
+    let writes = graph
         .process_fs_patch(GraphPatch {
             renames: Vec::new(),
             deletions: Vec::new(),
@@ -944,78 +1120,1428 @@ Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
             ],
             modifications: Vec::new(),
         })
-        .await;
-
-    // We should initially have a backlink on node 1 coming from file 2
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // Titles should be updated, but types and missing titles should be left as they were
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1.replace("|Old Title]]", "|File 2]]")
+    );
+    // The untyped wiki link is a link by name, which won't resolve
     assert_eq!(
         graph
-            .get_node(
-                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
-                NodeOptions::new(Format::Markdown).connections(true)
-            )
+            .errors(&PathBuf::from("file_1.md"))
             .await
             .unwrap()
-            .backlinks,
-        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid() => NodeConnection {
-            title: ["File 2"].into_v(),
-            types: ["link"].into_hs()
-        }}
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        HashSet::from([
+            missing_id("b097edb8-7a66-45fe-aec3-eb957f511ab9"),
+            missing_name("Some Note")
+        ])
+    );
+
+    let node = graph
+        .get_node(
+            "b097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks,
+        map! {
+            "b097edb8-7a66-45fe-aec3-eb957f511ab0".uuid() => NodeConnection {
+                title: ["File 1"].into_v(),
+                types: ["link", "other"].into_hs()
+            }
+        }
     );
 
+    // Retitling the target should update the titled links
     let writes = graph
         .process_fs_patch(GraphPatch {
             renames: Vec::new(),
-            deletions: vec![PathBuf::from("file_2.md")],
+            deletions: Vec::new(),
             creations: Vec::new(),
-            modifications: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("file_2.md"),
+                contents_res: Ok(file_2.replace("File 2", "File 2+")),
+            }],
         })
-        .await;
-    assert!(writes.is_empty());
-    // And now we should have no backlinks on node 1
-    assert!(graph
-        .get_node(
-            "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
-            NodeOptions::new(Format::Markdown).connections(true)
-        )
-        .await
-        .unwrap()
-        .backlinks
-        .is_empty());
-    // And no valid connections either
-    assert!(graph
-        .get_node(
-            "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
-            NodeOptions::new(Format::Markdown).connections(true)
-        )
         .await
-        .unwrap()
-        .connections
-        .is_empty());
-    // But the invalid connection should be there
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
     assert_eq!(
-        graph.errors(&PathBuf::from("file_1.md")).await,
-        Some(Ok(vec!["7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid()]))
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1.replace("|Old Title]]", "|File 2+]]")
     );
 }
 
 #[tokio::test]
-async fn new_node_should_validate_existing_references() {
+async fn name_links_should_resolve() {
     setup_config();
 
     let file_1 = r#"---
 title: File 1
 ---
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
--->"#;
-    let file_2 = r#"---
+ID: c097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+Links to [Old Title](link:file  2), [[Second]], and [[link:Twin|Twin]]. This one is unresolved: [Nothing](link:Nowhere)."#;
+    let file_2 = r#"---
+title: File 2
+aliases:
+    - Second
+---
+<!--PROPERTIES
+ID: c097edb8-7a66-45fe-aec3-eb957f511ab1
+-->
+
+# Twin
+<!--PROPERTIES
+ID: c097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+# Twin
+<!--PROPERTIES
+ID: c097edb8-7a66-45fe-aec3-eb957f511ab3
+-->"#;
+    let file_3 = r#"#+title: File 3
+
+* Somewhere
+:PROPERTIES:
+:ID: c097edb8-7a66-45fe-aec3-eb957f511ab4
+:ALIASES: Nowhere, Elsewhere
+:END:"#;
+
+    let graph = Graph::new(HashMap::new());
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // Names are matched loosely, and are written back as they were
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1.replace("[Old Title]", "[File 2]")
+    );
+    async fn errors(graph: &Graph) -> HashSet<InvalidConnection> {
+        graph
+            .errors(&PathBuf::from("file_1.md"))
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+    // Ambiguous names shouldn't resolve either
+    assert_eq!(
+        errors(&graph).await,
+        HashSet::from([
+            InvalidConnection {
+                id: None,
+                name: Some("Twin".to_string()),
+                reason: InvalidConnectionReason::Ambiguous,
+                candidates: vec![
+                    "c097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+                    "c097edb8-7a66-45fe-aec3-eb957f511ab3".uuid()
+                ],
+            },
+            missing_name("Nowhere")
+        ])
+    );
+    let node = graph
+        .get_node(
+            "c097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks,
+        map! {
+            "c097edb8-7a66-45fe-aec3-eb957f511ab0".uuid() => NodeConnection {
+                title: ["File 1"].into_v(),
+                types: ["link"].into_hs()
+            }
+        }
+    );
+
+    // Retitling one of the twins should make the other one resolve
+    let file_2_new = file_2
+        .replacen("# Twin", "# Other", 2)
+        .replacen("# Other", "# Twin", 1);
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("file_2.md"),
+                contents_res: Ok(file_2_new),
+            }],
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1
+            .replace("[Old Title]", "[File 2]")
+            .replace("|Twin]]", "|File 2/Twin]]")
+    );
+    assert_eq!(
+        errors(&graph).await,
+        HashSet::from([missing_name("Nowhere")])
+    );
+    let node = graph
+        .get_node(
+            "c097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks.keys().collect::<Vec<_>>(),
+        vec![&"c097edb8-7a66-45fe-aec3-eb957f511ab0".uuid()]
+    );
+
+    // A new node with a matching alias should resolve the last one
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("file_3.org"),
+                contents_res: Ok(file_3.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1
+            .replace("[Old Title]", "[File 2]")
+            .replace("|Twin]]", "|File 2/Twin]]")
+            .replace("[Nothing]", "[File 3/Somewhere]")
+    );
+    assert!(errors(&graph).await.is_empty());
+}
+
+#[tokio::test]
+async fn short_ids_should_not_clash_with_titles() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: d097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+Links to [Target](d197edb8), and [Twins](d297edb8)."#;
+    // The first heading's title is another node's short ID
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: d197edb8-7a66-45fe-aec3-eb957f511ab1
+-->
+
+# d297edb8
+<!--PROPERTIES
+ID: d397edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+# Twin 1
+<!--PROPERTIES
+ID: d297edb8-7a66-45fe-aec3-eb957f511ab3
+-->
+
+# Twin 2
+<!--PROPERTIES
+ID: d297edb8-7a66-45fe-aec3-eb957f511ab4
+-->"#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+    // The unique short ID should resolve, and the shared one shouldn't, even though there's a
+    // node with it as its title
+    assert_eq!(
+        graph
+            .errors(&PathBuf::from("file_1.md"))
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        HashSet::from([InvalidConnection {
+            id: None,
+            name: Some("d297edb8".to_string()),
+            reason: InvalidConnectionReason::Ambiguous,
+            candidates: vec![
+                "d297edb8-7a66-45fe-aec3-eb957f511ab3".uuid(),
+                "d297edb8-7a66-45fe-aec3-eb957f511ab4".uuid()
+            ],
+        }])
+    );
+    let node = graph
+        .get_node(
+            "d197edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks,
+        map! {
+            "d097edb8-7a66-45fe-aec3-eb957f511ab0".uuid() => NodeConnection {
+                title: ["File 1"].into_v(),
+                types: ["link"].into_hs()
+            }
+        }
+    );
+    let node = graph
+        .get_node(
+            "d397edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true),
+        )
+        .await
+        .unwrap();
+    assert!(node.backlinks.is_empty());
+}
+
+#[tokio::test]
+async fn org_search_links_should_resolve() {
+    setup_config();
+
+    let file_1 = r#"#+title: File 1
+
+* Alpha
+:PROPERTIES:
+:ID: e097edb8-7a66-45fe-aec3-eb957f511ab1
+:CUSTOM_ID: alpha-id
+:END:
+See [[*Beta][the other one]].
+* Beta
+:PROPERTIES:
+:ID: e097edb8-7a66-45fe-aec3-eb957f511ab2
+:END:
+Back to [[#alpha-id]]."#;
+    let file_2 = r#"#+title: File 2
+
+* Gamma
+:PROPERTIES:
+:ID: e097edb8-7a66-45fe-aec3-eb957f511ab3
+:END:
+Custom IDs work from anywhere: [[#alpha-id][Alpha]]. Headings don't: [[*Alpha][Alpha]]."#;
+
+    let graph = Graph::new(HashMap::new());
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.org"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.org"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // Searches are written back as they were, with updated titles
+    let file_1_written = writes.get(&PathBuf::from("file_1.org")).unwrap();
+    assert!(file_1_written.contains("See [[*Beta][File 1/Beta]]."));
+    assert!(file_1_written.contains("Back to [[#alpha-id]]."));
+    assert!(writes
+        .get(&PathBuf::from("file_2.org"))
+        .unwrap()
+        .contains("[[#alpha-id][File 1/Alpha]]"));
+
+    let node = graph
+        .get_node(
+            "e097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Org).connections(true),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks.keys().collect::<HashSet<_>>(),
+        HashSet::from([
+            &"e097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            &"e097edb8-7a66-45fe-aec3-eb957f511ab3".uuid()
+        ])
+    );
+    assert_eq!(
+        node.connections.keys().collect::<Vec<_>>(),
+        vec![&"e097edb8-7a66-45fe-aec3-eb957f511ab2".uuid()]
+    );
+    assert!(graph
+        .errors(&PathBuf::from("file_1.org"))
+        .await
+        .unwrap()
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        graph
+            .errors(&PathBuf::from("file_2.org"))
+            .await
+            .unwrap()
+            .unwrap(),
+        vec![missing_name("*Alpha")]
+    );
+}
+
+#[tokio::test]
+async fn backlinks_should_have_inverse_types() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: f097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+This [backs up](supports:f097edb8-7a66-45fe-aec3-eb957f511ab1) and [relates to](related:f097edb8-7a66-45fe-aec3-eb957f511ab1) the other one, and [links](link:f097edb8-7a66-45fe-aec3-eb957f511ab1) to it."#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: f097edb8-7a66-45fe-aec3-eb957f511ab1
+-->"#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    let from = "f097edb8-7a66-45fe-aec3-eb957f511ab0".uuid();
+    let to = "f097edb8-7a66-45fe-aec3-eb957f511ab1".uuid();
+    let node = graph
+        .get_node(to, NodeOptions::new(Format::Markdown).connections(true))
+        .await
+        .unwrap();
+    // Types without an inverse are the same both ways
+    assert_eq!(
+        node.backlinks[&from].types,
+        ["supported-by", "related", "link"].into_hs()
+    );
+    let node = graph
+        .get_node(from, NodeOptions::new(Format::Markdown).connections(true))
+        .await
+        .unwrap();
+    assert_eq!(
+        node.connections[&to].types,
+        ["supports", "related", "link"].into_hs()
+    );
+
+    // Filtering works on either direction
+    let node = graph
+        .get_node(
+            to,
+            NodeOptions::new(Format::Markdown)
+                .connections(true)
+                .link_types(&["supported-by", "related"]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks[&from].types,
+        ["supported-by", "related"].into_hs()
+    );
+    let node = graph
+        .get_node(
+            from,
+            NodeOptions::new(Format::Markdown)
+                .connections(true)
+                .link_types(&["supported-by"]),
+        )
+        .await
+        .unwrap();
+    assert!(node.connections.is_empty());
+}
+
+#[tokio::test]
+async fn link_constraints_should_be_checked() {
+    setup_config();
+
+    let file = r#"#+title: Constraints
+
+* Concept :concept:
+:PROPERTIES:
+:ID: 1097edb8-7a66-45fe-aec3-eb957f511ab1
+:END:
+Child of [[parent:1097edb8-7a66-45fe-aec3-eb957f511ab2][Other]].
+* Other :concept:
+:PROPERTIES:
+:ID: 1097edb8-7a66-45fe-aec3-eb957f511ab2
+:END:
+Child of [[parent:1097edb8-7a66-45fe-aec3-eb957f511ab1][Concept]].
+* TODO Task
+:PROPERTIES:
+:ID: 1097edb8-7a66-45fe-aec3-eb957f511ab3
+:END:
+Child of [[parent:1097edb8-7a66-45fe-aec3-eb957f511ab4][Other task]], which it [[blocks:1097edb8-7a66-45fe-aec3-eb957f511ab4][blocks]], like it [[blocks:1097edb8-7a66-45fe-aec3-eb957f511ab1][blocks]] this.
+* TODO Other task
+:PROPERTIES:
+:ID: 1097edb8-7a66-45fe-aec3-eb957f511ab4
+:END:"#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("constraints.org"),
+                contents_res: Ok(file.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    let violation = |from: &str, to: &str, ty: &str, kind| ConstraintViolation {
+        from: from.uuid(),
+        to: to.uuid(),
+        ty: ty.to_string(),
+        kind,
+    };
+    let path = PathBuf::from("constraints.org");
+    // These are still perfectly valid connections
+    assert!(graph.errors(&path).await.unwrap().unwrap().is_empty());
+    assert_eq!(
+        graph.constraint_violations(&path).await.unwrap(),
+        vec![
+            violation(
+                "1097edb8-7a66-45fe-aec3-eb957f511ab1",
+                "1097edb8-7a66-45fe-aec3-eb957f511ab2",
+                "parent",
+                ViolationKind::Cycle
+            ),
+            violation(
+                "1097edb8-7a66-45fe-aec3-eb957f511ab2",
+                "1097edb8-7a66-45fe-aec3-eb957f511ab1",
+                "parent",
+                ViolationKind::Cycle
+            ),
+            violation(
+                "1097edb8-7a66-45fe-aec3-eb957f511ab3",
+                "1097edb8-7a66-45fe-aec3-eb957f511ab1",
+                "blocks",
+                ViolationKind::NotActionItem
+            ),
+            violation(
+                "1097edb8-7a66-45fe-aec3-eb957f511ab3",
+                "1097edb8-7a66-45fe-aec3-eb957f511ab4",
+                "parent",
+                ViolationKind::TargetTags
+            ),
+        ]
+    );
+
+    // Breaking the cycle and tagging the target should fix those violations
+    let file_new = file
+        .replace(
+            "Child of [[parent:1097edb8-7a66-45fe-aec3-eb957f511ab1][Concept]].",
+            "",
+        )
+        .replace("* TODO Other task", "* TODO Other task :concept:");
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: path.clone(),
+                contents_res: Ok(file_new),
+            }],
+        })
+        .await;
+    assert_eq!(
+        graph.constraint_violations(&path).await.unwrap(),
+        vec![violation(
+            "1097edb8-7a66-45fe-aec3-eb957f511ab3",
+            "1097edb8-7a66-45fe-aec3-eb957f511ab1",
+            "blocks",
+            ViolationKind::NotActionItem
+        )]
+    );
+}
+
+#[tokio::test]
+async fn property_schemas_should_be_checked() {
+    setup_config();
+
+    let file = r#"#+title: Papers
+
+* Good paper :paper:
+:PROPERTIES:
+:ID: 2097edb8-7a66-45fe-aec3-eb957f511ab1
+:AUTHOR: Someone
+:YEAR: 2019
+:EFFORT: 1:30
+:END:
+* Bad paper :paper:
+:PROPERTIES:
+:ID: 2097edb8-7a66-45fe-aec3-eb957f511ab2
+:YEAR: soon
+:END:
+* Not a paper
+:PROPERTIES:
+:ID: 2097edb8-7a66-45fe-aec3-eb957f511ab3
+:YEAR: soon
+:EFFORT: ages
+:END:"#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("papers.org"),
+                contents_res: Ok(file.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    let metadata = graph
+        .get_node(
+            "2097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Org).metadata(true),
+        )
+        .await
+        .unwrap()
+        .metadata
+        .unwrap();
+    assert_eq!(
+        metadata.typed_properties,
+        map! {
+            "YEAR".to_string() => PropertyValue::Int(2019),
+            "EFFORT".to_string() => PropertyValue::Duration(90)
+        }
+    );
+    assert!(metadata.property_errors.is_empty());
+
+    // Schemas for tags only apply to nodes with those tags, but global types apply everywhere
+    assert_eq!(
+        graph
+            .property_errors(&PathBuf::from("papers.org"))
+            .await
+            .unwrap(),
+        vec![
+            PropertyError {
+                id: "2097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+                property: "AUTHOR".to_string(),
+                kind: PropertyErrorKind::Missing {
+                    tag: "paper".to_string()
+                }
+            },
+            PropertyError {
+                id: "2097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+                property: "YEAR".to_string(),
+                kind: PropertyErrorKind::Invalid {
+                    value: "soon".to_string()
+                }
+            },
+            PropertyError {
+                id: "2097edb8-7a66-45fe-aec3-eb957f511ab3".uuid(),
+                property: "EFFORT".to_string(),
+                kind: PropertyErrorKind::Invalid {
+                    value: "ages".to_string()
+                }
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn hierarchical_tags_should_match_ancestors() {
+    setup_config();
+
+    let file = r#"#+title: Algorithms
+#+filetags: :algo:
+
+* Graphs :concept:
+:PROPERTIES:
+:ID: 3097edb8-7a66-45fe-aec3-eb957f511ab1
+:END:
+* Sorting :algo:
+:PROPERTIES:
+:ID: 3097edb8-7a66-45fe-aec3-eb957f511ab2
+:END:"#;
+
+    let mut indices: HashMap<String, IndexCriteria> = HashMap::new();
+    indices.insert("cs".to_string(), Arc::new(|node| node_has_tag(node, "cs")));
+    let graph = Graph::new(indices);
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("algorithms.org"),
+                contents_res: Ok(file.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+    // Aliases on headings should be normalized, but the file tags are left as written
+    let contents = &writes[0].contents;
+    assert!(contents.contains(":cs/algorithms:"));
+    assert!(contents.contains("#+filetags: :algo:"));
+
+    let node = graph
+        .get_node(
+            "3097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Org),
+        )
+        .await
+        .unwrap();
+    assert_eq!(node.tags, ["concept"].into_hs());
+    assert_eq!(node.parent_tags, ["algo"].into_hs());
+    assert_eq!(
+        node.expanded_tags,
+        ["concept", "cs/algorithms", "cs"].into_hs()
+    );
+
+    // Queries for a tag should match anything more specific, and aliases should work too
+    let ids = |nodes: Vec<Node>| nodes.into_iter().map(|n| n.id).collect::<Vec<_>>();
+    let all = vec![
+        graph
+            .root_id(&PathBuf::from("algorithms.org"))
+            .await
+            .unwrap(),
+        "3097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+        "3097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+    ];
+    let mut sorted_all = all.clone();
+    sorted_all.sort();
+    assert_eq!(
+        ids(graph.tagged("cs", NodeOptions::new(Format::Org)).await),
+        sorted_all
+    );
+    assert_eq!(
+        ids(graph.tagged("algo", NodeOptions::new(Format::Org)).await),
+        sorted_all
+    );
+    assert_eq!(
+        ids(graph.tagged("concept", NodeOptions::new(Format::Org)).await),
+        vec!["3097edb8-7a66-45fe-aec3-eb957f511ab1".uuid()]
+    );
+
+    // Index criteria only see the node itself, so inherited tags don't count
+    let mut indexed = vec![all[0], all[2]];
+    indexed.sort();
+    assert_eq!(
+        ids(graph.nodes(Some("cs"), NodeOptions::new(Format::Org)).await),
+        indexed
+    );
+}
+
+#[tokio::test]
+async fn lenient_mode_should_flag_nodes() {
+    setup_config();
+
+    let file = r#"#+title: Lenient
+
+* Typo :concpet:
+:PROPERTIES:
+:ID: 4097edb8-7a66-45fe-aec3-eb957f511ab1
+:END:
+* Copy
+:PROPERTIES:
+:ID: 4097edb8-7a66-45fe-aec3-eb957f511ab1
+:END:"#;
+
+    let path = PathBuf::from("lenient.org");
+    let graph = Graph::new(HashMap::new());
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: path.clone(),
+                contents_res: Ok(file.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    // The path should still be parsed, with both nodes in it
+    assert!(graph.errors(&path).await.unwrap().is_ok());
+    let diagnostics = graph.diagnostics(&path).await.unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    let typo_id = "4097edb8-7a66-45fe-aec3-eb957f511ab1".uuid();
+    let copy_id = diagnostics
+        .iter()
+        .filter_map(|d| d.id)
+        .find(|id| *id != typo_id)
+        .unwrap();
+    // The new ID should be written back
+    assert!(writes[0].contents.contains(&copy_id.to_string()));
+    assert_eq!(
+        graph
+            .get_node(copy_id, NodeOptions::new(Format::Org))
+            .await
+            .unwrap()
+            .title,
+        ["Lenient", "Copy"].into_v()
+    );
+
+    // The offending node should be flagged
+    let metadata = graph
+        .get_node(typo_id, NodeOptions::new(Format::Org).metadata(true))
+        .await
+        .unwrap()
+        .metadata
+        .unwrap();
+    assert_eq!(metadata.diagnostics.len(), 1);
+    assert!(metadata.diagnostics[0].message.contains("concpet"));
+    assert_eq!(
+        metadata.diagnostics[0].span,
+        Some(Span {
+            start: Position { line: 3, column: 9 },
+            end: Position {
+                line: 3,
+                column: 16
+            },
+        })
+    );
+
+    // Approving the unknown tag should clear its diagnostic
+    assert!(graph.pending_tags().await.contains(&"concpet".to_string()));
+    graph.approve_tag("concpet").await.unwrap();
+    assert!(!graph.pending_tags().await.contains(&"concpet".to_string()));
+    assert_eq!(graph.diagnostics(&path).await.unwrap().len(), 1);
+    assert!(matches!(
+        graph.approve_tag("concpet").await,
+        Err(ApiError::TagNotPending { .. })
+    ));
+
+    // The approval should last when the path is parsed again, without touching the configuration
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: path.clone(),
+                contents_res: Ok(file.into()),
+            }],
+        })
+        .await;
+    assert!(!graph.pending_tags().await.contains(&"concpet".to_string()));
+    assert!(!STARLING_CONFIG.get().is_valid_tag("concpet"));
+}
+
+#[tokio::test]
+async fn resources_should_be_tracked() {
+    setup_config();
+
+    let file = r#"---
+title: File
+---
+<!--PROPERTIES
+ID: d097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+See [the site](https://example.com), [the image](./image.png#top), and [the paper](../paper.pdf)."#;
+
+    let dir = std::env::temp_dir().join(format!("starling-resources-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("notes")).unwrap();
+    std::fs::write(dir.join("notes/image.png"), "").unwrap();
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("notes/file.md"),
+                contents_res: Ok(file.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    let id = "d097edb8-7a66-45fe-aec3-eb957f511ab0".uuid();
+    assert_eq!(graph.citations("https://example.com").await, vec![id]);
+    // File keys are relative to the root, without anchors
+    assert_eq!(graph.citations("notes/image.png").await, vec![id]);
+    assert_eq!(graph.citations("paper.pdf").await, vec![id]);
+    assert_eq!(
+        graph
+            .dangling_resources(&PathBuf::from("notes/file.md"), &dir)
+            .await
+            .unwrap(),
+        vec!["../paper.pdf".to_string()]
+    );
+
+    // Moving the path should move the files it points to with it
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: vec![(PathBuf::from("notes/file.md"), PathBuf::from("file.md"))],
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: Vec::new(),
+        })
+        .await;
+    assert!(graph.citations("notes/image.png").await.is_empty());
+    assert_eq!(graph.citations("image.png").await, vec![id]);
+    assert_eq!(graph.citations("https://example.com").await, vec![id]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn renames_should_work() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+tags:
+    - hello
+    - world
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+# TODO [#A] Node 1 :child_tag:
+SCHEDULED: <2024-01-01>
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+LOCATION: Test
+-->
+
+This is a test file."#;
+    let file_2 = r#"---
+title: File 2
+---
+
+Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: vec![(PathBuf::from("file_1.md"), PathBuf::from("file_1_new.md"))],
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: Vec::new(),
+        })
+        .await;
+    // No writes should result from a rename
+    assert!(writes.is_empty());
+    assert_eq!(
+        graph
+            .get_node(
+                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+                NodeOptions::new(Format::Markdown)
+            )
+            .await
+            .unwrap()
+            .path,
+        PathBuf::from("file_1_new.md")
+    );
+}
+
+#[tokio::test]
+async fn node_deletion_should_remove_backlinks() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+# Node 1
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+-->
+
+This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    // We should initially have a backlink on file 2 coming from node 1
+    assert_eq!(
+        graph
+            .get_node(
+                "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+                NodeOptions::new(Format::Markdown).connections(true)
+            )
+            .await
+            .unwrap()
+            .backlinks,
+        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid() => NodeConnection {
+            title: ["File 1", "Node 1"].into_v(),
+            types: ["link"].into_hs()
+        }}
+    );
+
+    let file_1_updated = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->"#;
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("file_1.md"),
+                contents_res: Ok(file_1_updated.into()),
+            }],
+        })
+        .await;
+    assert!(writes.is_empty());
+    // And now we should have no backlinks on file 2 (node 1's reference is gone)
+    assert!(graph
+        .get_node(
+            "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true)
+        )
+        .await
+        .unwrap()
+        .backlinks
+        .is_empty());
+    // And there should be no valid connections in file 2 either
+    assert!(graph
+        .get_node(
+            "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true)
+        )
+        .await
+        .unwrap()
+        .connections
+        .is_empty());
+    // But the invalid connection should be there
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_2.md")).await,
+        Some(Ok(vec![missing_id("7097edb8-7a66-45fe-aec3-eb957f511ab1")]))
+    );
+}
+
+#[tokio::test]
+async fn file_deletion_should_work() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+# Node 1
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+-->
+
+This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+Here's [File 1/Node 1](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+
+    // We should initially have a backlink on node 1 coming from file 2
+    assert_eq!(
+        graph
+            .get_node(
+                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+                NodeOptions::new(Format::Markdown).connections(true)
+            )
+            .await
+            .unwrap()
+            .backlinks,
+        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid() => NodeConnection {
+            title: ["File 2"].into_v(),
+            types: ["link"].into_hs()
+        }}
+    );
+
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: vec![PathBuf::from("file_2.md")],
+            creations: Vec::new(),
+            modifications: Vec::new(),
+        })
+        .await;
+    assert!(writes.is_empty());
+    // And now we should have no backlinks on node 1
+    assert!(graph
+        .get_node(
+            "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true)
+        )
+        .await
+        .unwrap()
+        .backlinks
+        .is_empty());
+    // And no valid connections either
+    assert!(graph
+        .get_node(
+            "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true)
+        )
+        .await
+        .unwrap()
+        .connections
+        .is_empty());
+    // But the invalid connection should be there
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_1.md")).await,
+        Some(Ok(vec![missing_id("7097edb8-7a66-45fe-aec3-eb957f511ab2")]))
+    );
+}
+
+#[tokio::test]
+async fn new_node_should_validate_existing_references() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->"#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
+-->
+
+Here's [some node](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+    // We should have an invalid connection in file 1
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_2.md")).await,
+        Some(Ok(vec![missing_id("7097edb8-7a66-45fe-aec3-eb957f511ab1")]))
+    );
+
+    let file_1_updated = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+# Node 1
+<!--PROPERTIES
+ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
+-->
+
+This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("file_1.md"),
+                contents_res: Ok(file_1_updated.into()),
+            }],
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // New node and the backlink should be updated, so both files should be written to
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1_updated
+    );
+    assert_eq!(
+        writes.get(&PathBuf::from("file_2.md")).unwrap(),
+        &file_2.replace("[some node]", "[File 1/Node 1]")
+    );
+
+    // There should be a valid connection on the new node and a backlink from it on file 2
+    assert_eq!(
+        graph
+            .get_node(
+                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+                NodeOptions::new(Format::Markdown).connections(true)
+            )
+            .await
+            .unwrap()
+            .connections,
+        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid() => NodeConnection {
+            title: ["File 2"].into_v(),
+            types: ["link"].into_hs()
+        }}
+    );
+    assert_eq!(
+        graph
+            .get_node(
+                "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+                NodeOptions::new(Format::Markdown).connections(true)
+            )
+            .await
+            .unwrap()
+            .backlinks,
+        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid() => NodeConnection {
+            title: ["File 1", "Node 1"].into_v(),
+            types: ["link"].into_hs()
+        }}
+    );
+    // And the invalid connections should be gone from file 2
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_2.md")).await,
+        Some(Ok(Vec::new()))
+    );
+}
+
+#[tokio::test]
+async fn new_unlinked_node_should_validate_existing_references() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 1b97edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+Here's [some node](link:1b97edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("file_1.md"),
+                contents_res: Ok(file_1.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_1.md")).await,
+        Some(Ok(vec![missing_id("1b97edb8-7a66-45fe-aec3-eb957f511ab1")]))
+    );
+
+    // The new node has no connections of its own, so only the one it validates should be
+    // checked
+    let file_2 = r#"---
 title: File 2
 ---
 <!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab2
+ID: 1b97edb8-7a66-45fe-aec3-eb957f511ab1
+-->"#;
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("file_2.md"),
+                contents_res: Ok(file_2.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1.replace("[some node]", "[File 2]")
+    );
+    assert_eq!(
+        graph
+            .get_node(
+                "1b97edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
+                NodeOptions::new(Format::Markdown).connections(true)
+            )
+            .await
+            .unwrap()
+            .backlinks,
+        map! { "1b97edb8-7a66-45fe-aec3-eb957f511ab0".uuid() => NodeConnection {
+            title: ["File 1"].into_v(),
+            types: ["link"].into_hs()
+        }}
+    );
+    assert_eq!(
+        graph.errors(&PathBuf::from("file_1.md")).await,
+        Some(Ok(Vec::new()))
+    );
+}
+
+#[tokio::test]
+async fn new_nameless_node_should_validate_existing_references() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: 1c97edb8-7a66-45fe-aec3-eb957f511ab0
 -->
 
-Here's [some node](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
+Here's [some node](link:1c97edb8-7a66-45fe-aec3-eb957f511ab2)."#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: 1c97edb8-7a66-45fe-aec3-eb957f511ab1
+-->"#;
 
     let graph = Graph::new(HashMap::new());
     graph
@@ -1035,83 +2561,39 @@ Here's [some node](link:7097edb8-7a66-45fe-aec3-eb957f511ab1)."#;
             modifications: Vec::new(),
         })
         .await;
-    // We should have an invalid connection in file 1
     assert_eq!(
-        graph.errors(&PathBuf::from("file_2.md")).await,
-        Some(Ok(vec!["7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid()]))
+        graph.errors(&PathBuf::from("file_1.md")).await,
+        Some(Ok(vec![missing_id("1c97edb8-7a66-45fe-aec3-eb957f511ab2")]))
     );
 
-    let file_1_updated = r#"---
-title: File 1
----
-<!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab0
--->
-
-# Node 1
-<!--PROPERTIES
-ID: 7097edb8-7a66-45fe-aec3-eb957f511ab1
--->
-
-This is a test file. Here's [File 2](link:7097edb8-7a66-45fe-aec3-eb957f511ab2)"#;
-    let writes = graph
+    // A heading with no title has no names, so nothing else in this update would need the names
+    // map, but checking the connection it validates still does
+    let file_2_updated =
+        format!("{file_2}\n\n#\n<!--PROPERTIES\nID: 1c97edb8-7a66-45fe-aec3-eb957f511ab2\n-->");
+    graph
         .process_fs_patch(GraphPatch {
             renames: Vec::new(),
             deletions: Vec::new(),
             creations: Vec::new(),
             modifications: vec![PathPatch {
-                path: PathBuf::from("file_1.md"),
-                contents_res: Ok(file_1_updated.into()),
+                path: PathBuf::from("file_2.md"),
+                contents_res: Ok(file_2_updated),
             }],
         })
-        .await
-        .into_iter()
-        .map(|w| (w.path, w.contents))
-        .collect::<HashMap<_, _>>();
-    // New node and the backlink should be updated, so both files should be written to
-    assert_eq!(
-        writes.get(&PathBuf::from("file_1.md")).unwrap(),
-        &file_1_updated
-    );
-    assert_eq!(
-        writes.get(&PathBuf::from("file_2.md")).unwrap(),
-        &file_2.replace("[some node]", "[File 1/Node 1]")
-    );
-
-    // There should be a valid connection on the new node and a backlink from it on file 2
-    assert_eq!(
-        graph
-            .get_node(
-                "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid(),
-                NodeOptions::new(Format::Markdown).connections(true)
-            )
-            .await
-            .unwrap()
-            .connections,
-        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid() => NodeConnection {
-            title: ["File 2"].into_v(),
-            types: ["link"].into_hs()
-        }}
-    );
-    assert_eq!(
-        graph
-            .get_node(
-                "7097edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
-                NodeOptions::new(Format::Markdown).connections(true)
-            )
-            .await
-            .unwrap()
-            .backlinks,
-        map! { "7097edb8-7a66-45fe-aec3-eb957f511ab1".uuid() => NodeConnection {
-            title: ["File 1", "Node 1"].into_v(),
-            types: ["link"].into_hs()
-        }}
-    );
-    // And the invalid connections should be gone from file 2
+        .await;
     assert_eq!(
-        graph.errors(&PathBuf::from("file_2.md")).await,
+        graph.errors(&PathBuf::from("file_1.md")).await,
         Some(Ok(Vec::new()))
     );
+    assert!(graph
+        .get_node(
+            "1c97edb8-7a66-45fe-aec3-eb957f511ab2".uuid(),
+            NodeOptions::new(Format::Markdown).connections(true)
+        )
+        .await
+        .unwrap()
+        .backlinks
+        .contains_key(&"1c97edb8-7a66-45fe-aec3-eb957f511ab0".uuid()));
 }
 
 #[tokio::test]
@@ -1398,6 +2880,7 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
                 path: PathBuf::from("file_1.md"),
                 tags: ["hello"].into_hs(),
                 parent_tags: [].into(),
+                expanded_tags: ["hello"].into_hs(),
                 parent_id: Some("7097edb8-7a66-45fe-aec3-eb957f511ab0".uuid()),
                 children: Vec::new(),
                 metadata: None,
@@ -1405,7 +2888,8 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
                 connections: map! {},
                 backlinks: map! {},
                 child_connections: map! {},
-                child_backlinks: map! {}
+                child_backlinks: map! {},
+                resources: HashSet::new()
             },
             Node {
                 id: "7097edb8-7a66-45fe-aec3-eb957f511ab3".uuid(),
@@ -1413,6 +2897,7 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
                 path: PathBuf::from("file_2.md"),
                 tags: ["hello"].into_hs(),
                 parent_tags: [].into(),
+                expanded_tags: ["hello"].into_hs(),
                 parent_id: None,
                 children: Vec::new(),
                 metadata: None,
@@ -1420,7 +2905,8 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
                 connections: map! {},
                 backlinks: map! {},
                 child_connections: map! {},
-                child_backlinks: map! {}
+                child_backlinks: map! {},
+                resources: HashSet::new()
             },
         ]
     );
@@ -1443,6 +2929,7 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
             path: PathBuf::from("file_1.md"),
             tags: ["hello"].into_hs(),
             parent_tags: [].into(),
+            expanded_tags: ["hello"].into_hs(),
             parent_id: Some("7097edb8-7a66-45fe-aec3-eb957f511ab0".uuid()),
             children: Vec::new(),
             metadata: None,
@@ -1450,7 +2937,8 @@ ID: 7097edb8-7a66-45fe-aec3-eb957f511ab3
             connections: map! {},
             backlinks: map! {},
             child_connections: map! {},
-            child_backlinks: map! {}
+            child_backlinks: map! {},
+            resources: HashSet::new()
         },]
     );
 
@@ -1491,3 +2979,245 @@ This one doesn't have a special tag!
         Vec::new()
     );
 }
+
+/// Gets the ID of the node with the given title in the given path.
+async fn node_id(graph: &Graph, path: &str, title: &[&str]) -> Uuid {
+    graph
+        .nodes(None, NodeOptions::new(Format::Markdown))
+        .await
+        .into_iter()
+        .find(|node| node.path == Path::new(path) && node.title == title)
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn lazy_ids_should_not_be_written() {
+    setup_config();
+
+    let file = "---\ntitle: File\n---\n\n# Heading\n\nSome text.\n";
+    let graph = Graph::new(HashMap::new());
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("lazy/file.md"),
+                    contents_res: Ok(file.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("eager.md"),
+                    contents_res: Ok(file.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await;
+    // Outside the lazy directory, new IDs should be written straight away
+    assert!(writes
+        .iter()
+        .any(|write| write.path == Path::new("eager.md")));
+    assert!(!writes
+        .iter()
+        .any(|write| write.path == Path::new("lazy/file.md")));
+    // But the nodes should still be in the graph
+    let root = node_id(&graph, "lazy/file.md", &["File"]).await;
+    let heading = node_id(&graph, "lazy/file.md", &["File", "Heading"]).await;
+    assert_ne!(root, heading);
+}
+
+#[tokio::test]
+async fn lazy_ids_should_be_stable() {
+    setup_config();
+
+    let file = "---\ntitle: File\n---\n\n# Heading\n\nSome text.\n";
+    let creation = || GraphPatch {
+        renames: Vec::new(),
+        deletions: Vec::new(),
+        creations: vec![PathPatch {
+            path: PathBuf::from("lazy/stable.md"),
+            contents_res: Ok(file.into()),
+        }],
+        modifications: Vec::new(),
+    };
+    let graph = Graph::new(HashMap::new());
+    graph.process_fs_patch(creation()).await;
+    let heading = node_id(&graph, "lazy/stable.md", &["File", "Heading"]).await;
+
+    // Changing the file without moving the heading shouldn't change its ID
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("lazy/stable.md"),
+                contents_res: Ok(file.replace("Some text.", "Some other text.")),
+            }],
+        })
+        .await;
+    assert!(writes.is_empty());
+    assert_eq!(
+        node_id(&graph, "lazy/stable.md", &["File", "Heading"]).await,
+        heading
+    );
+
+    // And neither should parsing the file from scratch
+    let other_graph = Graph::new(HashMap::new());
+    other_graph.process_fs_patch(creation()).await;
+    assert_eq!(
+        node_id(&other_graph, "lazy/stable.md", &["File", "Heading"]).await,
+        heading
+    );
+}
+
+#[tokio::test]
+async fn linking_to_lazy_node_should_pin_its_id() {
+    setup_config();
+
+    let target = "---\ntitle: Target\n---\n\n# Heading\n\nSome text.\n";
+    let graph = Graph::new(HashMap::new());
+    graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("lazy/target.md"),
+                contents_res: Ok(target.into()),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+    let heading = node_id(&graph, "lazy/target.md", &["Target", "Heading"]).await;
+
+    let linker = format!(
+        "---\ntitle: Linker\n---\n<!--PROPERTIES\nID: 3a97edb8-7a66-45fe-aec3-eb957f511ab0\n-->\n\nSee [Heading](link:{heading})."
+    );
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![PathPatch {
+                path: PathBuf::from("linker.md"),
+                contents_res: Ok(linker),
+            }],
+            modifications: Vec::new(),
+        })
+        .await;
+    assert_eq!(
+        graph.errors(&PathBuf::from("linker.md")).await,
+        Some(Ok(Vec::new()))
+    );
+    // Only the heading that was linked to should have its ID written
+    let target_write = writes
+        .into_iter()
+        .find(|write| write.path == Path::new("lazy/target.md"))
+        .unwrap();
+    assert!(target_write.contents.contains(&heading.to_string()));
+    assert_eq!(target_write.contents.matches("ID:").count(), 1);
+
+    // Once that's on the disk, it shouldn't need writing again
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: Vec::new(),
+            modifications: vec![PathPatch {
+                path: PathBuf::from("lazy/target.md"),
+                contents_res: Ok(target_write.contents),
+            }],
+        })
+        .await;
+    assert!(!writes
+        .iter()
+        .any(|write| write.path == Path::new("lazy/target.md")));
+    assert_eq!(
+        node_id(&graph, "lazy/target.md", &["Target", "Heading"]).await,
+        heading
+    );
+}
+
+#[tokio::test]
+async fn ulids_should_be_kept_as_written() {
+    setup_config();
+
+    let file_1 = r#"---
+title: File 1
+---
+<!--PROPERTIES
+ID: e097edb8-7a66-45fe-aec3-eb957f511ab0
+-->
+
+Links to [Old Title](01ARZ3NDEKTSV4RRFFQ69G5FAV) and [Old Title](01arz3nd)."#;
+    let file_2 = r#"---
+title: File 2
+---
+<!--PROPERTIES
+ID: 01ARZ3NDEKTSV4RRFFQ69G5FAV
+-->
+
+# Heading
+<!--PROPERTIES
+ID: 01BRZ3NDEKTSV4RRFFQ69G5FAV
+-->
+
+Links back to [Old Title](e097edb8-7a66-45fe-aec3-eb957f511ab0)."#;
+
+    let graph = Graph::new(HashMap::new());
+    let writes = graph
+        .process_fs_patch(GraphPatch {
+            renames: Vec::new(),
+            deletions: Vec::new(),
+            creations: vec![
+                PathPatch {
+                    path: PathBuf::from("file_1.md"),
+                    contents_res: Ok(file_1.into()),
+                },
+                PathPatch {
+                    path: PathBuf::from("file_2.md"),
+                    contents_res: Ok(file_2.into()),
+                },
+            ],
+            modifications: Vec::new(),
+        })
+        .await
+        .into_iter()
+        .map(|w| (w.path, w.contents))
+        .collect::<HashMap<_, _>>();
+    // Links by ULIDs and their short forms should resolve, and be written back the same way, as
+    // should IDs written as ULIDs
+    assert_eq!(
+        writes.get(&PathBuf::from("file_1.md")).unwrap(),
+        &file_1.replace("[Old Title]", "[File 2]")
+    );
+    assert_eq!(
+        writes.get(&PathBuf::from("file_2.md")).unwrap(),
+        &file_2.replace("[Old Title]", "[File 1]")
+    );
+    assert!(graph
+        .errors(&PathBuf::from("file_1.md"))
+        .await
+        .unwrap()
+        .unwrap()
+        .is_empty());
+    let id = "01563e3a-b5d3-d676-4c61-efb99302bd5b".uuid();
+    assert_eq!(
+        graph.resolve_id("01ARZ3NDEKTSV4RRFFQ69G5FAV").await,
+        Some(id)
+    );
+    assert_eq!(graph.resolve_id("01arz3nd").await, Some(id));
+    let node = graph
+        .get_node(id, NodeOptions::new(Format::Markdown).connections(true))
+        .await
+        .unwrap();
+    assert_eq!(
+        node.backlinks,
+        map! {
+            "e097edb8-7a66-45fe-aec3-eb957f511ab0".uuid() => NodeConnection {
+                title: ["File 1"].into_v(),
+                types: ["link"].into_hs()
+            }
+        }
+    );
+}
@@ -0,0 +1,108 @@
+use super::graph::setup_config;
+use crate::{
+    config::STARLING_CONFIG,
+    conflict_detector::{Conflict, WriteReason, WriteSource},
+    error::ApiError,
+    journal::{content_hash, Journal},
+};
+use std::path::Path;
+
+#[test]
+fn journal_should_list_newest_first() {
+    setup_config();
+    let journal = Journal::new();
+    let first = journal.record(
+        Path::new("foo.md"),
+        None,
+        "one\n",
+        WriteSource::Filesystem,
+        vec![WriteReason::NewNodes],
+    );
+    let second = journal.record(
+        Path::new("foo.md"),
+        Some("one\n"),
+        "two\n",
+        WriteSource::Other,
+        vec![WriteReason::LinkTitles],
+    );
+
+    let entries = journal.list(10);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id, second);
+    assert_eq!(entries[0].before_hash, Some(content_hash("one\n")));
+    assert_eq!(entries[0].after_hash, content_hash("two\n"));
+    assert_eq!(entries[0].reasons, vec![WriteReason::LinkTitles]);
+    assert_eq!(entries[1].id, first);
+    assert_eq!(entries[1].before_hash, None);
+
+    assert_eq!(journal.list(1).len(), 1);
+}
+
+#[test]
+fn undo_should_restore_previous_contents() {
+    setup_config();
+    let journal = Journal::new();
+    let id = journal.record(
+        Path::new("foo.md"),
+        Some("one\n"),
+        "two\n",
+        WriteSource::Filesystem,
+        vec![WriteReason::LinkTitles],
+    );
+    journal.undo(id).unwrap();
+
+    let undos = journal.take_undos();
+    assert_eq!(undos.len(), 1);
+    assert_eq!(undos[0].path, Path::new("foo.md"));
+    assert_eq!(undos[0].contents, "one\n");
+    assert_eq!(undos[0].base, Some("two\n".to_string()));
+    assert_eq!(undos[0].conflict, Conflict::None);
+    assert_eq!(undos[0].reasons, vec![WriteReason::Undo]);
+    assert!(journal.take_undos().is_empty());
+}
+
+#[test]
+fn undo_should_fail_without_previous_contents() {
+    setup_config();
+    let journal = Journal::new();
+    let id = journal.record(
+        Path::new("foo.md"),
+        None,
+        "one\n",
+        WriteSource::Filesystem,
+        vec![WriteReason::NewNodes],
+    );
+    assert!(matches!(journal.undo(id), Err(ApiError::CannotUndo { .. })));
+    assert!(matches!(
+        journal.undo(uuid::Uuid::new_v4()),
+        Err(ApiError::JournalEntryNotFound { .. })
+    ));
+    assert!(journal.take_undos().is_empty());
+}
+
+#[test]
+fn old_writes_should_be_pruned() {
+    setup_config();
+    let journal = Journal::new();
+    let record = |n: usize| {
+        journal.record(
+            Path::new("foo.md"),
+            Some(&format!("{n}\n")),
+            &format!("{}\n", n + 1),
+            WriteSource::Filesystem,
+            vec![WriteReason::LinkTitles],
+        )
+    };
+    let retention = STARLING_CONFIG.get().journal_retention;
+    let oldest = record(0);
+    let ids = (1..=retention).map(record).collect::<Vec<_>>();
+
+    // The oldest write should still be listed, but its contents are gone
+    assert_eq!(journal.list(usize::MAX).len(), retention + 1);
+    assert!(matches!(
+        journal.undo(oldest),
+        Err(ApiError::CannotUndo { .. })
+    ));
+    journal.undo(ids[0]).unwrap();
+    assert_eq!(journal.take_undos()[0].contents, "1\n");
+}
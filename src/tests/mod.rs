@@ -1,3 +1,17 @@
+mod attributes;
+mod config;
 mod conflict_detector;
+mod conflict_store;
 mod debouncer;
+mod diff;
+mod error;
+mod exclude;
 mod graph;
+mod journal;
+mod parse_bench;
+mod path_node;
+mod pending_writes;
+mod recovery;
+mod schema;
+mod ulid;
+mod writer;
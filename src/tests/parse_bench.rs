@@ -0,0 +1,84 @@
+//! A benchmark for parsing a large synthetic vault. This is ignored by default because it takes a
+//! while; run it with `cargo test --release parse_bench -- --ignored --nocapture`.
+
+use super::graph::setup_config;
+use crate::{
+    graph::Graph,
+    patch::{GraphPatch, PathPatch},
+    path_node::PathNode,
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+use uuid::Uuid;
+
+/// The number of files in the synthetic vault.
+const NUM_FILES: usize = 10_000;
+/// The number of headings in each file of the synthetic vault.
+const HEADINGS_PER_FILE: usize = 5;
+
+/// Generates a synthetic vault of Markdown files, where each heading links to the root of the next
+/// file along (so there's a healthy number of connections to parse and validate).
+fn synthetic_vault() -> Vec<PathPatch> {
+    let root_ids = (0..NUM_FILES).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    (0..NUM_FILES)
+        .map(|i| {
+            let mut contents = format!(
+                "---\ntitle: File {i}\n---\n<!--PROPERTIES\nID: {}\n-->\n\nSome text in the root.\n",
+                root_ids[i]
+            );
+            for j in 0..HEADINGS_PER_FILE {
+                contents.push_str(&format!(
+                    "\n# Heading {j}\n<!--PROPERTIES\nID: {}\n-->\n\nHere's a link to [File {}]({}).\n",
+                    Uuid::new_v4(),
+                    (i + 1) % NUM_FILES,
+                    root_ids[(i + 1) % NUM_FILES],
+                ));
+            }
+
+            PathPatch {
+                path: PathBuf::from(format!("file_{i}.md")),
+                contents_res: Ok(contents),
+            }
+        })
+        .collect()
+}
+
+#[tokio::test]
+#[ignore]
+async fn parse_bench() {
+    setup_config();
+
+    // Parsing alone, sequentially and then in parallel
+    let patches = synthetic_vault();
+    let start = Instant::now();
+    for patch in patches {
+        PathNode::new(patch.path, patch.contents_res, &Vec::new());
+    }
+    let sequential = start.elapsed();
+
+    let patches = synthetic_vault();
+    let start = Instant::now();
+    patches.into_par_iter().for_each(|patch| {
+        PathNode::new(patch.path, patch.contents_res, &Vec::new());
+    });
+    let parallel = start.elapsed();
+
+    // And then a full initial scan through the graph
+    let graph = Graph::new(HashMap::new());
+    let patch = GraphPatch {
+        renames: Vec::new(),
+        deletions: Vec::new(),
+        creations: synthetic_vault(),
+        modifications: Vec::new(),
+    };
+    let start = Instant::now();
+    graph.process_fs_patch(patch).await;
+    let full = start.elapsed();
+
+    println!("parsed {NUM_FILES} files sequentially in {sequential:?}");
+    println!(
+        "parsed {NUM_FILES} files in parallel in {parallel:?} ({:.2}x speedup)",
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+    println!("processed initial scan of {NUM_FILES} files in {full:?}");
+}
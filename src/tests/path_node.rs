@@ -0,0 +1,60 @@
+use crate::path_node::{read_ulids, strip_ids, write_ulids};
+use orgish::Format;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+static STRIPPED: &str = "5d93b936-5952-4707-89dd-69ca06c60850";
+static KEPT: &str = "5d93b936-5952-4707-89dd-69ca06c60851";
+
+fn ids() -> HashSet<Uuid> {
+    [Uuid::parse_str(STRIPPED).unwrap()].into_iter().collect()
+}
+
+#[test]
+fn markdown_ids_should_be_stripped() {
+    let contents = format!(
+        "# Node 1\n<!--PROPERTIES\nID: {STRIPPED}\n-->\nBody\n\n# Node 2\n<!--PROPERTIES\nID: {KEPT}\n-->\n\n# Node 3\n<!--PROPERTIES\nID: {STRIPPED}\nEFFORT: 1h\n-->\n"
+    );
+    assert_eq!(
+        strip_ids(&contents, &ids(), Format::Markdown),
+        format!("# Node 1\nBody\n\n# Node 2\n<!--PROPERTIES\nID: {KEPT}\n-->\n\n# Node 3\n<!--PROPERTIES\nEFFORT: 1h\n-->\n")
+    );
+}
+
+#[test]
+fn org_ids_should_be_stripped() {
+    let contents = format!(
+        "* Node 1\n:PROPERTIES:\n:ID: {STRIPPED}\n:END:\nBody\n* Node 2\n:PROPERTIES:\n:ID: {KEPT}\n:END:\n"
+    );
+    assert_eq!(
+        strip_ids(&contents, &ids(), Format::Org),
+        format!("* Node 1\nBody\n* Node 2\n:PROPERTIES:\n:ID: {KEPT}\n:END:\n")
+    );
+}
+
+#[test]
+fn nothing_should_be_stripped_without_ids() {
+    let contents = format!("# Node 1\n<!--PROPERTIES\nID: {STRIPPED}\n-->\n");
+    assert_eq!(
+        strip_ids(&contents, &HashSet::new(), Format::Markdown),
+        contents
+    );
+}
+
+#[test]
+fn ulids_should_be_read_and_written_back() {
+    let ulid = "01ARZ3NDEKTSV4RRFFQ69G5FAV";
+    let uuid = "01563e3a-b5d3-d676-4c61-efb99302bd5b";
+    let markdown = format!(
+        "# Node 1\n<!--PROPERTIES\nID: {ulid}\n-->\n\n# Node 2\n<!--PROPERTIES\nID: {KEPT}\n-->\n"
+    );
+    let (read, ids) = read_ulids(&markdown, Format::Markdown);
+    assert_eq!(read, markdown.replace(ulid, uuid));
+    assert_eq!(ids, HashSet::from([Uuid::parse_str(uuid).unwrap()]));
+    assert_eq!(write_ulids(&read, &ids, Format::Markdown), markdown);
+
+    let org = format!("* Node 1\n:PROPERTIES:\n  :ID:  {ulid}  \n:END:\n* Node 2\n:PROPERTIES:\n:ID: {KEPT}\n:END:\n");
+    let (read, ids) = read_ulids(&org, Format::Org);
+    assert_eq!(read, org.replace(ulid, uuid));
+    assert_eq!(write_ulids(&read, &ids, Format::Org), org);
+}
@@ -0,0 +1,62 @@
+use crate::{
+    conflict_detector::{Conflict, Write, WriteSource},
+    error::ApiError,
+    pending_writes::PendingWrites,
+};
+use std::path::PathBuf;
+
+/// Creates a filesystem [`Write`] of the given contents to the given path, computed from the
+/// given base, for testing.
+fn fs_write(path: &str, contents: &str, base: Option<&str>) -> Write {
+    Write {
+        path: PathBuf::from(path),
+        contents: contents.to_string(),
+        source: WriteSource::Filesystem,
+        conflict: Conflict::None,
+        base: base.map(|base| base.to_string()),
+        reasons: Vec::new(),
+    }
+}
+
+#[test]
+fn pending_writes_should_have_diffs() {
+    let store = PendingWrites::new();
+    let id = store.record(fs_write("foo.md", "new\n", Some("old\n")));
+
+    let writes = store.list();
+    assert_eq!(writes.len(), 1);
+    assert_eq!(writes[0].id, id);
+    assert_eq!(
+        writes[0].diff,
+        "--- disk/foo.md\n+++ starling/foo.md\n@@ -1,1 +1,1 @@\n-old\n+new\n"
+    );
+    assert_eq!(writes[0].base, Some("old\n".to_string()));
+
+    // Newer writes to the same path should replace older ones
+    let new_id = store.record(fs_write("foo.md", "newer\n", Some("old\n")));
+    store.record(fs_write("bar.md", "new\n", None));
+    let writes = store.list();
+    assert_eq!(writes.len(), 2);
+    assert_eq!(writes[0].id, new_id);
+}
+
+#[test]
+fn pending_writes_should_be_applied_selectively() {
+    let store = PendingWrites::new();
+    let foo = store.record(fs_write("foo.md", "new\n", None));
+    let bar = store.record(fs_write("bar.md", "new\n", None));
+
+    store.apply(foo).unwrap();
+    store.discard(bar).unwrap();
+    assert!(store.list().is_empty());
+
+    let approved = store.take_approved();
+    assert_eq!(approved.len(), 1);
+    assert_eq!(approved[0].path, PathBuf::from("foo.md"));
+    assert!(store.take_approved().is_empty());
+
+    assert!(matches!(
+        store.apply(bar),
+        Err(ApiError::PendingWriteNotFound { .. })
+    ));
+}
@@ -0,0 +1,67 @@
+use crate::{
+    error::{Position, Span},
+    recovery::{block_span, find_span, heading_lines, LineMap},
+};
+use orgish::Format;
+
+#[test]
+fn headings_should_be_found() {
+    let org = "#+title: Test\n\n* One\n*bold* text\n** Two\n* Three";
+    assert_eq!(
+        heading_lines(org, Format::Org),
+        vec![(2, 1), (4, 2), (5, 1)]
+    );
+
+    // Headings in code blocks shouldn't count
+    let markdown = "# One\n\n```bash\n# comment\n```\n\n## Two";
+    assert_eq!(
+        heading_lines(markdown, Format::Markdown),
+        vec![(0, 1), (6, 2)]
+    );
+}
+
+#[test]
+fn spans_should_be_found() {
+    let contents = "#+title: Test\n\n* Heading :foo:\nBody with foo";
+    assert_eq!(
+        find_span(contents, 2, 4, "foo"),
+        Some(Span {
+            start: Position {
+                line: 3,
+                column: 12
+            },
+            end: Position {
+                line: 3,
+                column: 15
+            },
+        })
+    );
+    // The whole first line should be used if the text isn't there
+    assert_eq!(
+        find_span(contents, 2, 4, "bar"),
+        Some(Span {
+            start: Position { line: 3, column: 1 },
+            end: Position {
+                line: 3,
+                column: 16
+            },
+        })
+    );
+    assert_eq!(find_span(contents, 10, 12, "foo"), None);
+
+    assert_eq!(
+        block_span("---\ntitle: Test\n---\n\nBody", 0, 3),
+        Some(Span {
+            start: Position { line: 1, column: 1 },
+            end: Position { line: 3, column: 4 },
+        })
+    );
+    assert_eq!(block_span("", 0, 0), None);
+}
+
+#[test]
+fn lines_should_map_to_original() {
+    let map = LineMap::offset(3);
+    assert_eq!(map.original_line(0), 3);
+    assert_eq!(map.original_line(5), 8);
+}
@@ -0,0 +1,64 @@
+use crate::{config::PropertyType, schema::PropertyValue};
+use chrono::NaiveDate;
+
+#[test]
+fn property_types_should_parse() {
+    assert_eq!(
+        PropertyType::Int.parse(" 42 "),
+        Some(PropertyValue::Int(42))
+    );
+    assert_eq!(PropertyType::Int.parse("4.2"), None);
+    assert_eq!(
+        PropertyType::Float.parse("4.2"),
+        Some(PropertyValue::Float(4.2))
+    );
+    assert_eq!(
+        PropertyType::Bool.parse("true"),
+        Some(PropertyValue::Bool(true))
+    );
+    let date = NaiveDate::parse_from_str("2024-01-01", "%Y-%m-%d").unwrap();
+    assert_eq!(
+        PropertyType::Date.parse("2024-01-01"),
+        Some(PropertyValue::Date(date))
+    );
+    assert_eq!(
+        PropertyType::Date.parse("<2024-01-01 Mon>"),
+        Some(PropertyValue::Date(date))
+    );
+    assert_eq!(PropertyType::Date.parse("tomorrow"), None);
+    assert_eq!(
+        PropertyType::List.parse("a, b,c"),
+        Some(PropertyValue::List(vec![
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string()
+        ]))
+    );
+    let status = PropertyType::Enum(vec!["draft".to_string(), "final".to_string()]);
+    assert_eq!(
+        status.parse("final"),
+        Some(PropertyValue::Enum("final".to_string()))
+    );
+    assert_eq!(status.parse("other"), None);
+}
+
+#[test]
+fn durations_should_parse() {
+    for (raw, minutes) in [
+        ("1:30", 90),
+        ("0:05", 5),
+        ("2h", 120),
+        ("1h 30min", 90),
+        ("1d", 60 * 24),
+        ("1w2d", 60 * 24 * 9),
+    ] {
+        assert_eq!(
+            PropertyType::Duration.parse(raw),
+            Some(PropertyValue::Duration(minutes)),
+            "{raw}"
+        );
+    }
+    for raw in ["", "1:75", "lots", "2 fortnights", "h"] {
+        assert_eq!(PropertyType::Duration.parse(raw), None, "{raw}");
+    }
+}
@@ -0,0 +1,48 @@
+use crate::ulid::*;
+use uuid::Uuid;
+
+static ULID: &str = "01ARZ3NDEKTSV4RRFFQ69G5FAV";
+static UUID: &str = "01563e3a-b5d3-d676-4c61-efb99302bd5b";
+
+#[test]
+fn ulids_should_round_trip() {
+    let id = Uuid::parse_str(UUID).unwrap();
+    assert_eq!(encode(id), ULID);
+    assert_eq!(decode(ULID), Some(id));
+    assert_eq!(decode(&ULID.to_lowercase()), Some(id));
+    assert_eq!(decode(&encode(Uuid::max())), Some(Uuid::max()));
+
+    // Too long, too short, too big, and not Crockford's base 32
+    assert_eq!(decode(&format!("{ULID}0")), None);
+    assert_eq!(decode(&ULID[1..]), None);
+    assert_eq!(decode(&format!("8{}", &ULID[1..])), None);
+    assert_eq!(decode(&ULID.replace('Z', "U")), None);
+}
+
+#[test]
+fn ids_should_parse_in_either_form() {
+    let id = Uuid::parse_str(UUID).unwrap();
+    assert_eq!(parse_id(UUID), Some(id));
+    assert_eq!(parse_id(ULID), Some(id));
+    assert_eq!(parse_id("Some Title"), None);
+}
+
+#[test]
+fn ulids_should_record_their_time() {
+    let before = chrono::Utc::now().timestamp_millis() as u128;
+    let id = generate();
+    let after = chrono::Utc::now().timestamp_millis() as u128;
+    let millis = id.as_u128() >> 80;
+    assert!(before <= millis && millis <= after);
+    // The random part shouldn't be the same twice
+    assert_ne!(generate().as_u128() << 48, id.as_u128() << 48);
+}
+
+#[test]
+fn ulid_prefixes_should_be_recognised() {
+    assert!(is_prefix("01ARZ3ND"));
+    assert!(is_prefix("01arz3nd"));
+    assert!(!is_prefix("81ARZ3ND"));
+    assert!(!is_prefix("Research"));
+    assert!(!is_prefix(&format!("{ULID}0")));
+}
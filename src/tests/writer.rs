@@ -0,0 +1,80 @@
+use super::graph::setup_config;
+use crate::writer::{back_up, is_temp_file, replace, write_file};
+use std::path::{Path, PathBuf};
+
+/// Creates a fresh, empty directory for testing, returning its canonicalized path.
+fn setup_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("starling-writer-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+#[tokio::test]
+async fn writes_should_replace_files() {
+    setup_config();
+    let dir = setup_dir();
+    std::fs::write(dir.join("test.md"), "old").unwrap();
+
+    let previous = write_file(&dir, Path::new("test.md"), "new".to_string())
+        .await
+        .unwrap();
+    assert_eq!(previous, Some("old".to_string()));
+    assert_eq!(std::fs::read_to_string(dir.join("test.md")).unwrap(), "new");
+    // The temporary file should have been moved into place
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // New files should be created
+    let previous = write_file(&dir, Path::new("other.md"), "new".to_string())
+        .await
+        .unwrap();
+    assert_eq!(previous, None);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("other.md")).unwrap(),
+        "new"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn writes_should_preserve_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = setup_dir();
+    let path = dir.join("test.md");
+    std::fs::write(&path, "old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    replace(&path, b"new").unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn backups_should_be_limited() {
+    let dir = setup_dir();
+    let path = dir.join("test.md");
+    let backup_dir = dir.join("backups/test.md");
+
+    // Nothing to back up yet
+    back_up(&path, &backup_dir, 2).unwrap();
+    assert!(!backup_dir.exists());
+
+    for version in ["one", "two", "three"] {
+        std::fs::write(&path, version).unwrap();
+        back_up(&path, &backup_dir, 2).unwrap();
+    }
+    let mut backups = std::fs::read_dir(&backup_dir)
+        .unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect::<Vec<_>>();
+    backups.sort();
+    assert_eq!(backups, vec!["three".to_string(), "two".to_string()]);
+}
+
+#[test]
+fn temp_files_should_be_recognized() {
+    assert!(is_temp_file(Path::new(
+        "notes/.test.md.starling-tmp-3c8b8f0e-5c4a-4d5b-9a8e-0f6e3f1c2b1a"
+    )));
+    assert!(!is_temp_file(Path::new("notes/test.md")));
+}
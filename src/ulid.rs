@@ -0,0 +1,60 @@
+use uuid::Uuid;
+
+/// The digits of Crockford's base 32, which ULIDs are written in.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a new ULID, made of the milliseconds since the Unix epoch in its first 48 bits, and
+/// 80 random bits after that, so ULIDs sort in the order they were created. Like every other ID in
+/// Starling, these are held as [`Uuid`]s, and only written as ULIDs (see [`encode`]).
+pub fn generate() -> Uuid {
+    let millis = chrono::Utc::now().timestamp_millis() as u128 & ((1 << 48) - 1);
+    // Two version 4 UUIDs are as good a source of randomness as any, as long as one is shifted so
+    // the bits they fix for their version and variant are covered by random ones
+    let random = Uuid::new_v4().as_u128() ^ Uuid::new_v4().as_u128().rotate_left(8);
+
+    Uuid::from_u128(millis << 80 | random & ((1 << 80) - 1))
+}
+
+/// Writes the given ID as a ULID.
+pub fn encode(id: Uuid) -> String {
+    let value = id.as_u128();
+    (0..26)
+        .rev()
+        .map(|digit| ALPHABET[(value >> (digit * 5)) as usize & 31] as char)
+        .collect()
+}
+
+/// Parses the given ULID, if it is one. Like UUIDs, these are case-insensitive.
+pub fn decode(text: &str) -> Option<Uuid> {
+    if text.len() != 26 {
+        return None;
+    }
+    let mut value = 0_u128;
+    for (idx, c) in text.bytes().enumerate() {
+        let digit = ALPHABET.iter().position(|d| *d == c.to_ascii_uppercase())? as u128;
+        // 26 digits is 130 bits, so the first can only use three of its five
+        if idx == 0 && digit > 7 {
+            return None;
+        }
+        value = value << 5 | digit;
+    }
+
+    Some(Uuid::from_u128(value))
+}
+
+/// Returns whether or not the given text could be the start of a ULID.
+pub fn is_prefix(text: &str) -> bool {
+    text.len() <= 26
+        && text.bytes().enumerate().all(|(idx, c)| {
+            ALPHABET
+                .iter()
+                .position(|d| *d == c.to_ascii_uppercase())
+                // The first digit can only use three bits (see `decode`)
+                .is_some_and(|digit| idx > 0 || digit < 8)
+        })
+}
+
+/// Parses the given ID, which could be written as either a UUID or a ULID.
+pub fn parse_id(text: &str) -> Option<Uuid> {
+    Uuid::try_parse(text).ok().or_else(|| decode(text))
+}
@@ -0,0 +1,126 @@
+use crate::config::STARLING_CONFIG;
+use std::{
+    fs::{self, File},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// The marker in the names of the temporary files writes are made to before they're moved into
+/// place.
+static TEMP_MARKER: &str = ".starling-tmp-";
+
+/// Writes the given contents to the given path (relative to the given root directory) atomically,
+/// so a crash or full disk part-way through will never leave the file truncated. The contents
+/// are written to a temporary file next to the original, which is flushed to the disk and then
+/// renamed over the original, keeping its permissions (and, on Unix, its ownership, if we're
+/// allowed to).
+///
+/// If backups are enabled, the previous contents of the file will be saved to the backup
+/// directory first, and old backups of it beyond the retention limit will be removed.
+///
+/// This returns the contents the file had before it was written to, if it existed (and was valid
+/// UTF-8), so the write can be journalled.
+///
+/// Note that the rename will show up as a creation of the path, rather than a modification.
+pub async fn write_file(dir: &Path, path: &Path, contents: String) -> io::Result<Option<String>> {
+    let backups = {
+        let config = STARLING_CONFIG.get();
+        config
+            .backups
+            .then(|| config.backup_directory.clone())
+            .flatten()
+            .map(|backup_dir| (backup_dir, config.backup_retention))
+    };
+    let full_path = dir.join(path);
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let previous = fs::read_to_string(&full_path).ok();
+        if let Some((backup_dir, retention)) = backups {
+            // A failed backup shouldn't stop the write, it's the original that matters
+            if let Err(err) = back_up(&full_path, &backup_dir.join(&path), retention) {
+                warn!("failed to back up {:?} before writing to it: {}", path, err);
+            }
+        }
+        replace(&full_path, contents.as_bytes())?;
+        Ok(previous)
+    })
+    .await?
+}
+
+/// Returns whether or not the given path is a temporary file that a write is being made to.
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(TEMP_MARKER))
+}
+
+/// Atomically replaces the contents of the file at the given path with the given contents (see
+/// [`write_file`]).
+pub fn replace(full_path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = full_path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy();
+    let temp_path =
+        full_path.with_file_name(format!(".{file_name}{TEMP_MARKER}{}", Uuid::new_v4()));
+
+    let res = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+        // If the file already exists, the new version should look the same to everything else
+        if let Ok(metadata) = fs::metadata(full_path) {
+            file.set_permissions(metadata.permissions())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                // Only root can give files away, so this will often fail, which is fine if we
+                // own the original
+                if let Err(err) =
+                    std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()))
+                {
+                    debug!("couldn't preserve ownership of {:?}: {}", full_path, err);
+                }
+            }
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, full_path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    res?;
+
+    // Make sure the rename itself is on the disk (directories can't be opened for this on Windows)
+    #[cfg(unix)]
+    if let Some(parent) = full_path.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Saves the current contents of the file at the given path (if it exists) to a new timestamped
+/// file in the given directory, and then removes the oldest backups in there until only the given
+/// number are left (unless that's zero, in which case all backups are kept).
+pub fn back_up(full_path: &Path, backup_dir: &Path, retention: usize) -> io::Result<()> {
+    if !full_path.is_file() {
+        return Ok(());
+    }
+    fs::create_dir_all(backup_dir)?;
+    // These sort in the order they were made
+    let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.6fZ");
+    fs::copy(full_path, backup_dir.join(timestamp.to_string()))?;
+
+    if retention > 0 {
+        let mut backups = fs::read_dir(backup_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<PathBuf>>();
+        backups.sort();
+        let excess = backups.len().saturating_sub(retention);
+        for old in &backups[..excess] {
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(())
+}